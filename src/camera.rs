@@ -0,0 +1,175 @@
+use crate::matrix::{axis_angle_matrix, invert, look_at, multiply_matrix_vector4, ndc_translation, perspective, transform_direction, viewport_transform};
+use raylib::prelude::{Matrix, Vector2, Vector3, Vector4};
+use std::f32::consts::PI;
+
+/// Vertical field of view of every camera, in radians
+pub const FIELD_OF_VIEW: f32 = PI / 3.0;
+
+/// A point of view on the world: where it is seen from (`look_at`), how it is projected
+/// (`perspective`) and the framebuffer the projection lands on.
+///
+/// The world keeps the orientation the scene was laid out in: x to the right, y down and z away
+/// from the viewer, with the system center at the origin. An unrotated camera looks down +Z from
+/// the -Z side, so world (x, y) map to screen right/down like the loader's flipped models expect.
+#[derive(Clone, Copy, Debug)]
+pub struct Camera {
+    pub eye: Vector3,         // Camera position in world space
+    pub view: Matrix,         // World to camera space
+    pub projection: Matrix,   // Camera to clip space
+    pub viewport: (u32, u32), // Framebuffer size in pixels
+}
+
+impl Camera {
+    /// Camera `distance` away from `target`, looking at it, orbited around it by `rotation`
+    /// (X, then Y, then Z angles): the view of the scene turned by `rotation` in front of an
+    /// unrotated camera. `shift` moves the whole image by that many pixels (shake, stereo).
+    /// `near`/`far` are the clip plane distances from the eye.
+    pub fn orbit(
+        target: Vector3,
+        rotation: Vector3,
+        distance: f32,
+        shift: Vector2,
+        viewport: (u32, u32),
+        near: f32,
+        far: f32,
+    ) -> Self {
+        // Turning the scene by X, Y, Z is the camera turning the other way, Z first
+        let unrotate = axis_angle_matrix(Vector3::new(0.0, 0.0, 1.0), -rotation.z)
+            * axis_angle_matrix(Vector3::new(0.0, 1.0, 0.0), -rotation.y)
+            * axis_angle_matrix(Vector3::new(1.0, 0.0, 0.0), -rotation.x);
+        let eye = target + transform_direction(&unrotate, Vector3::new(0.0, 0.0, -distance));
+        let up = transform_direction(&unrotate, Vector3::new(0.0, -1.0, 0.0));
+
+        let (width, height) = (viewport.0 as f32, viewport.1 as f32);
+        // NDC spans 2 units across the viewport and its +Y is up
+        let shift = ndc_translation(2.0 * shift.x / width, -2.0 * shift.y / height);
+        Camera {
+            eye,
+            view: look_at(eye, target, up),
+            projection: perspective(FIELD_OF_VIEW, width / height, near, far) * shift,
+            viewport,
+        }
+    }
+
+    /// Camera framing the z = 0 plane like the screen: the world point (x, y, 0) lands on pixel
+    /// (x, y). For layouts built in pixels, such as the shader gallery.
+    pub fn screen(viewport: (u32, u32), near: f32, far: f32) -> Self {
+        let center = Vector3::new(viewport.0 as f32 / 2.0, viewport.1 as f32 / 2.0, 0.0);
+        Camera::orbit(center, Vector3::zero(), Camera::pixel_distance(viewport), Vector2::zero(), viewport, near, far)
+    }
+
+    /// Distance from the camera at which one world unit spans one pixel
+    pub fn pixel_distance(viewport: (u32, u32)) -> f32 {
+        viewport.1 as f32 / 2.0 / (FIELD_OF_VIEW / 2.0).tan()
+    }
+
+    /// Clip-space position of a world point
+    pub fn clip(&self, point: Vector3) -> Vector4 {
+        multiply_matrix_vector4(&(self.view * self.projection), &Vector4::new(point.x, point.y, point.z, 1.0))
+    }
+
+    /// Pixels one world unit spans at the depth of `point`; 0 at or behind the camera
+    pub fn pixels_per_unit(&self, point: Vector3) -> f32 {
+        let w = self.clip(point).w;
+        if w <= 0.0 {
+            return 0.0;
+        }
+        // The projection's y scale takes a view-space unit to NDC, which spans height / 2 pixels per unit
+        self.projection.m5 * self.viewport.1 as f32 / 2.0 / w
+    }
+
+    /// Screen directions in world space: (right, up)
+    pub fn screen_axes(&self) -> (Vector3, Vector3) {
+        let view = &self.view;
+        (Vector3::new(view.m0, view.m4, view.m8), Vector3::new(view.m1, view.m5, view.m9))
+    }
+
+    /// World point under `pixel` at the depth of `reference`, by running the projection backwards.
+    /// None when `reference` is behind the camera or the matrices can't be inverted.
+    pub fn unproject(&self, pixel: Vector2, reference: Vector3) -> Option<Vector3> {
+        let clip = self.clip(reference);
+        if clip.w <= 0.0 {
+            return None;
+        }
+        let (width, height) = (self.viewport.0 as f32, self.viewport.1 as f32);
+        let ndc = Vector4::new(2.0 * pixel.x / width - 1.0, 1.0 - 2.0 * pixel.y / height, clip.z / clip.w, 1.0);
+        let world = multiply_matrix_vector4(&invert(&(self.view * self.projection))?, &ndc);
+        (world.w.abs() > f32::EPSILON).then(|| Vector3::new(world.x / world.w, world.y / world.w, world.z / world.w))
+    }
+
+    /// Pixel position (and NDC depth) of a world point in front of the camera
+    pub fn screen_position(&self, point: Vector3) -> Option<Vector3> {
+        let clip = self.clip(point);
+        (clip.w > 0.0).then(|| {
+            let ndc = Vector3::new(clip.x / clip.w, clip.y / clip.w, clip.z / clip.w);
+            viewport_transform(ndc, self.viewport.0, self.viewport.1)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIEWPORT: (u32, u32) = (800, 600);
+
+    fn assert_close(actual: Vector3, expected: Vector3, tolerance: f32) {
+        assert!((actual - expected).length() < tolerance, "expected {:?}, got {:?}", expected, actual);
+    }
+
+    #[test]
+    fn target_projects_to_the_screen_center() {
+        let target = Vector3::new(120.0, -40.0, 15.0);
+        for rotation in [Vector3::zero(), Vector3::new(0.7, -1.2, 0.3), Vector3::new(PI / 2.0, 0.0, 0.0)] {
+            let camera = Camera::orbit(target, rotation, 500.0, Vector2::zero(), VIEWPORT, 10.0, 4000.0);
+            let screen = camera.screen_position(target).expect("target in front of the camera");
+            assert_close(Vector3::new(screen.x, screen.y, 0.0), Vector3::new(400.0, 300.0, 0.0), 1e-2);
+        }
+    }
+
+    #[test]
+    fn shift_moves_the_image_by_pixels() {
+        let camera = Camera::orbit(Vector3::zero(), Vector3::zero(), 500.0, Vector2::new(12.0, -5.0), VIEWPORT, 10.0, 4000.0);
+        let screen = camera.screen_position(Vector3::zero()).unwrap();
+        assert_close(Vector3::new(screen.x, screen.y, 0.0), Vector3::new(412.0, 295.0, 0.0), 1e-2);
+    }
+
+    #[test]
+    fn unrotated_camera_keeps_world_x_right_and_y_down() {
+        let camera = Camera::orbit(Vector3::zero(), Vector3::zero(), 500.0, Vector2::zero(), VIEWPORT, 10.0, 4000.0);
+        let right = camera.screen_position(Vector3::new(10.0, 0.0, 0.0)).unwrap();
+        let down = camera.screen_position(Vector3::new(0.0, 10.0, 0.0)).unwrap();
+        assert!(right.x > 400.0 && (right.y - 300.0).abs() < 1e-3);
+        assert!(down.y > 300.0 && (down.x - 400.0).abs() < 1e-3);
+        // Nearer points (smaller z) get a smaller depth
+        let near = camera.screen_position(Vector3::new(0.0, 0.0, -100.0)).unwrap();
+        let far = camera.screen_position(Vector3::new(0.0, 0.0, 100.0)).unwrap();
+        assert!(near.z < far.z);
+    }
+
+    #[test]
+    fn rotation_orbits_the_eye_around_the_target() {
+        // Turning the scene +90° around Y brings what was at +X in front of the camera
+        let camera = Camera::orbit(Vector3::zero(), Vector3::new(0.0, PI / 2.0, 0.0), 500.0, Vector2::zero(), VIEWPORT, 10.0, 4000.0);
+        assert_close(camera.eye, Vector3::new(500.0, 0.0, 0.0), 1e-2);
+    }
+
+    #[test]
+    fn screen_camera_maps_the_z0_plane_onto_pixels() {
+        let camera = Camera::screen(VIEWPORT, 10.0, 4000.0);
+        for (x, y) in [(0.0, 0.0), (123.0, 456.0), (800.0, 600.0)] {
+            let screen = camera.screen_position(Vector3::new(x, y, 0.0)).unwrap();
+            assert_close(Vector3::new(screen.x, screen.y, 0.0), Vector3::new(x, y, 0.0), 1e-2);
+        }
+        assert!((camera.pixels_per_unit(Vector3::new(10.0, 20.0, 0.0)) - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn unproject_inverts_the_projection() {
+        let camera = Camera::orbit(Vector3::new(30.0, 0.0, 0.0), Vector3::new(0.4, 0.9, 0.0), 300.0, Vector2::zero(), VIEWPORT, 10.0, 4000.0);
+        let point = Vector3::new(60.0, 25.0, -40.0);
+        let screen = camera.screen_position(point).unwrap();
+        let back = camera.unproject(Vector2::new(screen.x, screen.y), point).unwrap();
+        assert_close(back, point, 0.1);
+    }
+}
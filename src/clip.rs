@@ -0,0 +1,159 @@
+use crate::matrix::viewport_transform;
+use crate::vertex::Vertex;
+use raylib::math::{Vector2, Vector3, Vector4};
+
+/// Where a clip-space position sits relative to the near plane. The view volume keeps z >= -w
+/// (NDC z >= -1, see `matrix::perspective`), so this is >= 0 on the visible side and negative
+/// between the plane and the camera or behind it, where w shrinks to 0 and flips sign.
+/// It is linear along a clip-space segment, so crossings can be interpolated.
+pub fn near_plane_distance(clip: Vector4) -> f32 {
+    clip.z + clip.w
+}
+
+/// Clips a triangle with at least one vertex in front of the near plane (`behind_camera`) against
+/// the plane z + w = 0 in homogeneous clip space, keeping the visible part. Gives no triangle
+/// when nothing is left, else one or two (the clipped polygon as a fan), wound like the input.
+/// The new vertices interpolate every attribute linearly in clip space and are projected onto
+/// `viewport` like the vertex shader does.
+pub fn clip_near(tri: [&Vertex; 3], viewport: (u32, u32)) -> Vec<[Vertex; 3]> {
+    let mut polygon: Vec<Vertex> = Vec::with_capacity(4);
+    for i in 0..3 {
+        let (a, b) = (tri[i], tri[(i + 1) % 3]);
+        if !a.behind_camera {
+            polygon.push(a.clone());
+        }
+        // The edge crosses the plane: keep the crossing point
+        if a.behind_camera != b.behind_camera {
+            polygon.push(near_crossing(a, b, viewport));
+        }
+    }
+
+    (1..polygon.len().saturating_sub(1))
+        .map(|i| [polygon[0].clone(), polygon[i].clone(), polygon[i + 1].clone()])
+        .collect()
+}
+
+/// Clips a line segment against the near plane like `clip_near` does triangles: None when both
+/// ends are nearer than the plane, else the visible part, with the same direction
+pub fn clip_segment_near(a: &Vertex, b: &Vertex, viewport: (u32, u32)) -> Option<(Vertex, Vertex)> {
+    match (a.behind_camera, b.behind_camera) {
+        (true, true) => None,
+        (false, false) => Some((a.clone(), b.clone())),
+        (false, true) => Some((a.clone(), near_crossing(a, b, viewport))),
+        (true, false) => Some((near_crossing(a, b, viewport), b.clone())),
+    }
+}
+
+/// Point where the segment from `a` to `b` (on opposite sides) meets the near plane
+fn near_crossing(a: &Vertex, b: &Vertex, viewport: (u32, u32)) -> Vertex {
+    let (distance_a, distance_b) = (near_plane_distance(a.clip_position), near_plane_distance(b.clip_position));
+    lerp_vertex(a, b, distance_a / (distance_a - distance_b), viewport)
+}
+
+fn lerp_vertex(a: &Vertex, b: &Vertex, t: f32, viewport: (u32, u32)) -> Vertex {
+    let lerp3 = |a: Vector3, b: Vector3| a + (b - a) * t;
+    let lerp2 = |a: Vector2, b: Vector2| a + (b - a) * t;
+    let clip_position = Vector4::new(
+        a.clip_position.x + (b.clip_position.x - a.clip_position.x) * t,
+        a.clip_position.y + (b.clip_position.y - a.clip_position.y) * t,
+        a.clip_position.z + (b.clip_position.z - a.clip_position.z) * t,
+        a.clip_position.w + (b.clip_position.w - a.clip_position.w) * t,
+    );
+    let ndc = Vector3::new(clip_position.x, clip_position.y, clip_position.z) * (1.0 / clip_position.w);
+
+    Vertex {
+        position: lerp3(a.position, b.position),
+        normal: lerp3(a.normal, b.normal),
+        tangent: lerp3(a.tangent, b.tangent),
+        tex_coords: lerp2(a.tex_coords, b.tex_coords),
+        color: lerp3(a.color, b.color),
+        transformed_position: viewport_transform(ndc, viewport.0, viewport.1),
+        transformed_normal: lerp3(a.transformed_normal, b.transformed_normal),
+        clip_position,
+        behind_camera: false,
+        inv_w: 1.0 / clip_position.w,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::{multiply_matrix_vector4, perspective};
+    use std::f32::consts::PI;
+
+    const VIEWPORT: (u32, u32) = (800, 600);
+    const NEAR: f32 = 10.0;
+
+    /// A view-space point through a real perspective projection, flagged like the vertex shader does
+    fn projected(x: f32, y: f32, z: f32) -> Vertex {
+        let projection = perspective(PI / 3.0, 800.0 / 600.0, NEAR, 1000.0);
+        let clip = multiply_matrix_vector4(&projection, &Vector4::new(x, y, z, 1.0));
+        let mut vertex = Vertex::new(Vector3::new(x, y, z), Vector3::new(0.0, 0.0, 1.0), Vector2::zero());
+        vertex.clip_position = clip;
+        vertex.behind_camera = near_plane_distance(clip) < 0.0;
+        if !vertex.behind_camera {
+            let ndc = Vector3::new(clip.x, clip.y, clip.z) * (1.0 / clip.w);
+            vertex.transformed_position = viewport_transform(ndc, VIEWPORT.0, VIEWPORT.1);
+        }
+        vertex
+    }
+
+    fn assert_on_visible_side(triangles: &[[Vertex; 3]]) {
+        for vertex in triangles.iter().flatten() {
+            assert!(near_plane_distance(vertex.clip_position) > -1e-3, "{:?}", vertex.clip_position);
+            assert!(vertex.transformed_position.z >= -1.0 - 1e-4);
+            assert!(vertex.transformed_position.x.is_finite() && vertex.transformed_position.y.is_finite());
+        }
+    }
+
+    #[test]
+    fn near_plane_is_where_ndc_depth_is_minus_one() {
+        // Between the eye and the near plane w is still positive, but the point is clipped
+        assert!(near_plane_distance(projected(0.0, 0.0, -NEAR * 0.5).clip_position) < 0.0);
+        assert!(near_plane_distance(projected(0.0, 0.0, -NEAR).clip_position).abs() < 1e-4);
+        assert!(near_plane_distance(projected(0.0, 0.0, -NEAR * 2.0).clip_position) > 0.0);
+    }
+
+    #[test]
+    fn one_vertex_past_the_near_plane_leaves_a_quad() {
+        let (a, b, c) = (projected(-20.0, 0.0, -100.0), projected(20.0, 0.0, -100.0), projected(0.0, 5.0, -5.0));
+        assert!(c.clip_position.w > 0.0 && c.behind_camera);
+        let clipped = clip_near([&a, &b, &c], VIEWPORT);
+        assert_eq!(clipped.len(), 2);
+        assert_on_visible_side(&clipped);
+    }
+
+    #[test]
+    fn two_vertices_behind_the_camera_leave_a_triangle() {
+        let (a, b, c) = (projected(0.0, 0.0, -100.0), projected(-20.0, 0.0, 30.0), projected(20.0, 0.0, 30.0));
+        let clipped = clip_near([&a, &b, &c], VIEWPORT);
+        assert_eq!(clipped.len(), 1);
+        assert_on_visible_side(&clipped);
+        // The untouched vertex is kept as it was
+        assert_eq!(clipped[0][0].clip_position, a.clip_position);
+    }
+
+    #[test]
+    fn segment_is_cut_where_it_crosses_the_near_plane() {
+        let (a, b) = (projected(10.0, 0.0, -100.0), projected(-10.0, 0.0, 50.0));
+        let (start, end) = clip_segment_near(&a, &b, VIEWPORT).expect("part of the segment is visible");
+        assert_eq!(start.clip_position, a.clip_position);
+        assert!(near_plane_distance(end.clip_position).abs() < 1e-3);
+        // Reversed, the visible end comes second
+        let (start, end) = clip_segment_near(&b, &a, VIEWPORT).unwrap();
+        assert!(near_plane_distance(start.clip_position).abs() < 1e-3);
+        assert_eq!(end.clip_position, a.clip_position);
+    }
+
+    #[test]
+    fn segment_nearer_than_the_near_plane_is_dropped() {
+        let (a, b) = (projected(10.0, 0.0, -5.0), projected(-10.0, 0.0, 50.0));
+        assert!(clip_segment_near(&a, &b, VIEWPORT).is_none());
+    }
+
+    #[test]
+    fn triangle_entirely_past_the_near_plane_is_dropped() {
+        let (a, b, c) = (projected(0.0, 0.0, -5.0), projected(-20.0, 0.0, 30.0), projected(20.0, 0.0, 2.0));
+        assert!(clip_near([&a, &b, &c], VIEWPORT).is_empty());
+    }
+}
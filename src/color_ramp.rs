@@ -0,0 +1,118 @@
+use raylib::prelude::Vector3;
+
+/// Piecewise-linear color gradient defined by `(position, color)` stops
+#[derive(Clone, Debug)]
+pub struct ColorRamp {
+    stops: Vec<(f32, Vector3)>, // Sorted by position, never empty
+}
+
+impl ColorRamp {
+    /// Builds a ramp from stops in any order; an empty list gives a black ramp
+    pub fn new(mut stops: Vec<(f32, Vector3)>) -> Self {
+        if stops.is_empty() {
+            stops.push((0.0, Vector3::zero()));
+        }
+        // Stable sort keeps the given order of stops sharing a position (hard color edges)
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        ColorRamp { stops }
+    }
+
+    /// Color at `t`; positions before the first or after the last stop take that stop's color
+    pub fn sample(&self, t: f32) -> Vector3 {
+        let (first_position, first_color) = self.stops[0];
+        if t <= first_position {
+            return first_color;
+        }
+
+        for pair in self.stops.windows(2) {
+            let (start, start_color) = pair[0];
+            let (end, end_color) = pair[1];
+            if t <= end {
+                let span = end - start;
+                if span <= f32::EPSILON {
+                    return end_color;
+                }
+                let f = (t - start) / span;
+                return start_color * (1.0 - f) + end_color * f;
+            }
+        }
+
+        self.stops[self.stops.len() - 1].1
+    }
+
+    /// Dark crust through red-hot to bright orange (Venus volcanoes)
+    pub fn lava() -> Self {
+        ColorRamp::new(vec![
+            (0.0, Vector3::new(0.25, 0.05, 0.0)), // Cooled crust
+            (0.3, Vector3::new(1.0, 0.2, 0.0)),   // Red-hot
+            (1.0, Vector3::new(1.0, 0.6, 0.0)),   // Orange hot
+        ])
+    }
+
+    /// Deep to shallow water, ending in coastal turquoise
+    pub fn ocean() -> Self {
+        ColorRamp::new(vec![
+            (0.0, Vector3::new(0.0, 0.2, 0.5)),  // Deep ocean
+            (0.8, Vector3::new(0.0, 0.4, 0.8)),  // Shallow ocean
+            (1.0, Vector3::new(0.1, 0.6, 0.75)), // Coastal water
+        ])
+    }
+
+    /// Bluish glacial ice to white with a warm sparkle
+    pub fn ice() -> Self {
+        ColorRamp::new(vec![
+            (0.0, Vector3::new(0.6, 0.75, 0.9)),  // Glacial ice
+            (0.5, Vector3::new(0.9, 0.95, 1.0)),  // Pure ice
+            (1.0, Vector3::new(1.0, 1.0, 0.95)),  // Ice sparkle
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Vector3, expected: Vector3) {
+        assert!((actual - expected).length() < 1e-5, "expected {:?}, got {:?}", expected, actual);
+    }
+
+    const RED: Vector3 = Vector3 { x: 1.0, y: 0.0, z: 0.0 };
+    const GREEN: Vector3 = Vector3 { x: 0.0, y: 1.0, z: 0.0 };
+    const BLUE: Vector3 = Vector3 { x: 0.0, y: 0.0, z: 1.0 };
+
+    #[test]
+    fn stops_are_hit_exactly_and_interpolated_between() {
+        let ramp = ColorRamp::new(vec![(0.0, RED), (0.5, GREEN), (1.0, BLUE)]);
+        assert_close(ramp.sample(0.0), RED);
+        assert_close(ramp.sample(0.5), GREEN);
+        assert_close(ramp.sample(1.0), BLUE);
+        assert_close(ramp.sample(0.25), Vector3::new(0.5, 0.5, 0.0));
+        assert_close(ramp.sample(0.875), Vector3::new(0.0, 0.25, 0.75));
+    }
+
+    #[test]
+    fn samples_outside_the_stops_clamp_to_the_ends() {
+        let ramp = ColorRamp::new(vec![(0.2, RED), (0.8, BLUE)]);
+        assert_close(ramp.sample(-1.0), RED);
+        assert_close(ramp.sample(0.1), RED);
+        assert_close(ramp.sample(0.9), BLUE);
+        assert_close(ramp.sample(5.0), BLUE);
+    }
+
+    #[test]
+    fn stops_are_sorted_and_shared_positions_make_hard_edges() {
+        // Given out of order, with two stops at 0.5: red up to 0.5, green right after
+        let ramp = ColorRamp::new(vec![(1.0, GREEN), (0.5, RED), (0.0, RED), (0.5, GREEN)]);
+        assert_close(ramp.sample(0.49), RED);
+        assert_close(ramp.sample(0.51), GREEN);
+        assert_close(ramp.sample(1.0), GREEN);
+    }
+
+    #[test]
+    fn empty_ramp_is_black_and_presets_start_at_their_first_stop() {
+        assert_close(ColorRamp::new(Vec::new()).sample(0.3), Vector3::zero());
+        assert_close(ColorRamp::lava().sample(0.0), Vector3::new(0.25, 0.05, 0.0));
+        assert_close(ColorRamp::ocean().sample(1.0), Vector3::new(0.1, 0.6, 0.75));
+        assert_close(ColorRamp::ice().sample(0.5), Vector3::new(0.9, 0.95, 1.0));
+    }
+}
@@ -0,0 +1,727 @@
+use crate::obj::{Handedness, UpAxis};
+use crate::shaders::{AccretionDisk, SurfaceMaterial};
+use raylib::prelude::Vector3;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Config file read at startup (if present) and written by the save key
+pub const DEFAULT_CONFIG_PATH: &str = "solar_system.cfg";
+
+/// Default depth limits: wide enough that nothing in the default scene is clipped
+pub const DEFAULT_NEAR_PLANE: f32 = 10.0;
+pub const DEFAULT_FAR_PLANE: f32 = 4000.0;
+
+/// Scene settings loaded from a `key = value` config file and overridable from the command line
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub path: String,     // Where the config was read from and is saved to
+    pub profile_csv: Option<String>, // Per-frame render stats are logged here (command line only, not saved)
+    pub scene_seed: u64,  // Every procedural process derives its own seed from this one
+    pub fix_winding: bool,
+    pub smooth_normals: bool, // Normals computed for models without them are smoothed (false: flat per face)
+    pub cull_backfaces: bool, // Skip back-facing triangles of closed meshes (off while X shows back faces)
+    pub post_effects: Vec<String>, // Post-process effects in the order they run (e.g. `vignette`)
+    pub hidden_bodies: Vec<String>,     // Names of bodies not rendered
+    pub hidden_satellites: Vec<String>, // Names of bodies whose moon/rings are not rendered
+    pub backface_color: Vector3,        // Tint of back faces in the double-sided debug view
+    pub timeline: Option<String>,       // Presentation timeline script run as time advances
+    pub near_plane: f32,                // Distance from the camera to the near clip plane
+    pub far_plane: f32,                 // Distance from the camera to the far clip plane
+    pub log_depth: bool,                // Depth test on logarithmic depth instead of the projection's z (`)
+    pub depth_prepass: bool,            // Rasterize every body's depth first and shade only the visible fragments
+    pub depth_peels: u32,               // Translucent layers per pixel composited by depth peeling (0 = off, blended in draw order)
+    pub star_antialiasing: bool,        // Soft sub-pixel stars instead of single pixels
+    pub missing_texture_checker: bool,  // Checkerboard in place of textures that fail to load
+    pub sun_light_color: Vector3,       // Color of the sun's light (components above 1 brighten it)
+    pub companion_stars: Vec<CompanionStar>, // Extra stars circling the barycenter with the sun, which planets orbit
+    pub flip_texcoord_v: Vec<String>,   // Model files (name or path) loaded with V = 1 - V
+    pub z_up_models: Vec<String>,       // Model files exported Z-up, rotated to Y-up on load
+    pub left_handed_models: Vec<String>, // Model files exported left-handed, mirrored on load
+    pub cinematic_camera: bool,         // Start with the cinematic auto-orbit camera on (F7)
+    pub cinematic: CinematicCamera,
+    pub accretion_disk: AccretionDisk,  // Temperature colors and swirl speed of accretion-disk bodies
+    pub grid_extent: f32,               // Half-size of the reference grid (F8), in world units
+    pub grid_spacing: f32,              // World units between grid lines
+    pub line_thickness: f32,            // Width in pixels of the gizmo and grid lines (1 = single pixel)
+    pub velocity_arrow_scale: f32,      // World units of velocity arrow (F10) per unit of orbital speed
+    pub ring_shadow_softness: f32,      // Blur of the ring shadow's edges on the gas giant, in planet radii
+    pub ring_shadow_darkness: f32,      // Sunlight the rings block (0 = no shadow, 1 = black band)
+    pub time_scale: f32,                // Simulation time per second of real time (1 = real time)
+    pub target_fps: u32,                // Frame rate cap of the window (0 = uncapped)
+    pub lod_radius: f32,                // Screen radius in pixels below which bodies use the low-detail sphere (0 = always the model)
+    pub lod_fade: f32,                  // Seconds the cross-fade between detail levels lasts (0 = instant switch)
+    pub body_materials: Vec<(String, SurfaceMaterial)>, // Per-body material overrides, by body name
+}
+
+/// Star joining the sun (`star_<name> = scale, r, g, b`): its size and the color of the light it
+/// casts, which adds to the sun's on every body it reaches
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompanionStar {
+    pub name: String,
+    pub scale: f32,
+    pub light_color: Vector3,
+}
+
+impl Default for CompanionStar {
+    /// The smaller, orange "Sol B" of the binary star scene (`--binary-star`)
+    fn default() -> Self {
+        CompanionStar {
+            name: "Sol B".to_string(),
+            scale: 30.0,
+            light_color: Vector3::new(0.5, 0.42, 0.3),
+        }
+    }
+}
+
+/// Settings of the cinematic camera, which slowly circles the system while bobbing in elevation
+#[derive(Clone, Copy, Debug)]
+pub struct CinematicCamera {
+    pub speed: f32, // Radians per second around the system (negative circles the other way)
+    pub zoom: f32,  // Zoom the camera settles at: its distance from the target shrinks as the zoom grows
+    pub bob: f32,   // Elevation swing in radians above and below the starting tilt
+}
+
+impl Default for CinematicCamera {
+    fn default() -> Self {
+        CinematicCamera {
+            speed: 0.15,
+            zoom: 0.5,
+            bob: 0.25,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            path: DEFAULT_CONFIG_PATH.to_string(),
+            profile_csv: None,
+            scene_seed: 12345,
+            fix_winding: false,
+            smooth_normals: true,
+            cull_backfaces: true,
+            post_effects: Vec::new(),
+            hidden_bodies: Vec::new(),
+            hidden_satellites: Vec::new(),
+            backface_color: Vector3::new(1.0, 0.0, 1.0),
+            timeline: None,
+            near_plane: DEFAULT_NEAR_PLANE,
+            far_plane: DEFAULT_FAR_PLANE,
+            log_depth: false,
+            depth_prepass: false,
+            depth_peels: 0,
+            star_antialiasing: true,
+            missing_texture_checker: false,
+            sun_light_color: Vector3::new(1.0, 1.0, 1.0),
+            companion_stars: Vec::new(),
+            flip_texcoord_v: Vec::new(),
+            z_up_models: Vec::new(),
+            left_handed_models: Vec::new(),
+            cinematic_camera: false,
+            cinematic: CinematicCamera::default(),
+            accretion_disk: AccretionDisk::default(),
+            grid_extent: 450.0,
+            grid_spacing: 50.0,
+            line_thickness: 1.0,
+            velocity_arrow_scale: 2.0,
+            ring_shadow_softness: 0.08,
+            ring_shadow_darkness: 0.65,
+            time_scale: 1.0,
+            target_fps: 60,
+            lod_radius: 12.0,
+            lod_fade: 0.35,
+            body_materials: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Builds the configuration from the config file (if it exists) and then applies
+    /// command line overrides: `--config <path>`, `--seed <n>`, `--fix-winding`, `--timeline <path>`,
+    /// `--near <depth>`, `--far <depth>`, `--log-depth`, `--depth-prepass`, `--depth-peels <n>`, `--checker-missing`, `--binary-star`,
+    /// `--flip-v <models>`, `--cinematic`, `--profile-csv <path>`
+    pub fn from_args(args: &[String]) -> Self {
+        let path = arg_value(args, "--config").unwrap_or(DEFAULT_CONFIG_PATH);
+
+        let mut config = if Path::new(path).exists() {
+            match Config::load(path) {
+                Ok(config) => {
+                    println!("✓ Loaded config: {}", path);
+                    config
+                }
+                Err(e) => {
+                    eprintln!("✗ Failed to load config {}: {}", path, e);
+                    Config::default()
+                }
+            }
+        } else {
+            Config::default()
+        };
+        config.path = path.to_string();
+
+        if let Some(seed) = arg_value(args, "--seed") {
+            match seed.parse() {
+                Ok(seed) => config.scene_seed = seed,
+                Err(_) => eprintln!("✗ Invalid --seed value: {}", seed),
+            }
+        }
+        if args.iter().any(|arg| arg == "--fix-winding") {
+            config.fix_winding = true;
+        }
+        if args.iter().any(|arg| arg == "--log-depth") {
+            config.log_depth = true;
+        }
+        if args.iter().any(|arg| arg == "--depth-prepass") {
+            config.depth_prepass = true;
+        }
+        if let Some(peels) = arg_value(args, "--depth-peels") {
+            match peels.parse() {
+                Ok(peels) => config.depth_peels = peels,
+                Err(_) => eprintln!("✗ Invalid --depth-peels value: {}", peels),
+            }
+        }
+        if args.iter().any(|arg| arg == "--checker-missing") {
+            config.missing_texture_checker = true;
+        }
+        if args.iter().any(|arg| arg == "--binary-star") {
+            config.add_binary_companion();
+        }
+        if let Some(models) = arg_value(args, "--flip-v") {
+            config.flip_texcoord_v.extend(parse_list(models));
+        }
+        if args.iter().any(|arg| arg == "--cinematic") {
+            config.cinematic_camera = true;
+        }
+        if let Some(timeline) = arg_value(args, "--timeline") {
+            config.timeline = Some(timeline.to_string());
+        }
+        config.profile_csv = arg_value(args, "--profile-csv").map(|path| path.to_string());
+        for (flag, plane) in [("--near", &mut config.near_plane), ("--far", &mut config.far_plane)] {
+            if let Some(value) = arg_value(args, flag) {
+                match value.parse() {
+                    Ok(value) => *plane = value,
+                    Err(_) => eprintln!("✗ Invalid {} value: {}", flag, value),
+                }
+            }
+        }
+        if config.near_plane <= 0.0 || config.near_plane >= config.far_plane {
+            eprintln!("✗ near ({}) must be positive and below far ({}), using the defaults", config.near_plane, config.far_plane);
+            config.near_plane = DEFAULT_NEAR_PLANE;
+            config.far_plane = DEFAULT_FAR_PLANE;
+        }
+
+        config
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut config = Config { path: path.to_string(), ..Config::default() };
+
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected `key = value`", line_number + 1))?;
+            let (key, value) = (key.trim(), value.trim());
+
+            match key {
+                "scene_seed" => {
+                    config.scene_seed = value
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid scene_seed `{}`", line_number + 1, value))?;
+                }
+                "cull_backfaces" => {
+                    config.cull_backfaces = value
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid cull_backfaces `{}`", line_number + 1, value))?;
+                }
+                "smooth_normals" => {
+                    config.smooth_normals = value
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid smooth_normals `{}`", line_number + 1, value))?;
+                }
+                "fix_winding" => {
+                    config.fix_winding = value
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid fix_winding `{}`", line_number + 1, value))?;
+                }
+                "post_effects" => config.post_effects = parse_list(value),
+                "hidden_bodies" => config.hidden_bodies = parse_list(value),
+                "flip_texcoord_v" => config.flip_texcoord_v = parse_list(value),
+                "z_up_models" => config.z_up_models = parse_list(value),
+                "left_handed_models" => config.left_handed_models = parse_list(value),
+                "cinematic_camera" => {
+                    config.cinematic_camera = value
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid cinematic_camera `{}`", line_number + 1, value))?;
+                }
+                "cinematic_speed" | "cinematic_zoom" | "cinematic_bob" => {
+                    let number: f32 = value
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid {} `{}`", line_number + 1, key, value))?;
+                    match key {
+                        "cinematic_speed" => config.cinematic.speed = number,
+                        "cinematic_zoom" => config.cinematic.zoom = number.clamp(0.3, 3.0),
+                        _ => config.cinematic.bob = number,
+                    }
+                }
+                "accretion_inner_color" | "accretion_outer_color" => {
+                    let color = parse_color(value)
+                        .ok_or_else(|| format!("line {}: invalid {} `{}`", line_number + 1, key, value))?;
+                    match key {
+                        "accretion_inner_color" => config.accretion_disk.inner_color = color,
+                        _ => config.accretion_disk.outer_color = color,
+                    }
+                }
+                "accretion_swirl_speed" => {
+                    config.accretion_disk.swirl_speed = value
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid accretion_swirl_speed `{}`", line_number + 1, value))?;
+                }
+                "grid_extent" | "grid_spacing" => {
+                    let number: f32 = value
+                        .parse()
+                        .ok()
+                        .filter(|number: &f32| *number > 0.0)
+                        .ok_or_else(|| format!("line {}: invalid {} `{}`", line_number + 1, key, value))?;
+                    match key {
+                        "grid_extent" => config.grid_extent = number,
+                        _ => config.grid_spacing = number,
+                    }
+                }
+                "line_thickness" => {
+                    config.line_thickness = value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|thickness| *thickness > 0.0)
+                        .ok_or_else(|| format!("line {}: invalid line_thickness `{}`", line_number + 1, value))?;
+                }
+                "velocity_arrow_scale" => {
+                    config.velocity_arrow_scale = value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|scale| *scale >= 0.0)
+                        .ok_or_else(|| format!("line {}: invalid velocity_arrow_scale `{}`", line_number + 1, value))?;
+                }
+                "ring_shadow_softness" => {
+                    config.ring_shadow_softness = value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|softness| *softness >= 0.0)
+                        .ok_or_else(|| format!("line {}: invalid ring_shadow_softness `{}`", line_number + 1, value))?;
+                }
+                "ring_shadow_darkness" => {
+                    config.ring_shadow_darkness = value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|darkness| (0.0..=1.0).contains(darkness))
+                        .ok_or_else(|| format!("line {}: invalid ring_shadow_darkness `{}`", line_number + 1, value))?;
+                }
+                "time_scale" => {
+                    config.time_scale = value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|scale| scale.is_finite())
+                        .ok_or_else(|| format!("line {}: invalid time_scale `{}`", line_number + 1, value))?;
+                }
+                "target_fps" => {
+                    config.target_fps = value
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid target_fps `{}`", line_number + 1, value))?;
+                }
+                "lod_radius" | "lod_fade" => {
+                    let number: f32 = value
+                        .parse()
+                        .ok()
+                        .filter(|number: &f32| *number >= 0.0)
+                        .ok_or_else(|| format!("line {}: invalid {} `{}`", line_number + 1, key, value))?;
+                    match key {
+                        "lod_radius" => config.lod_radius = number,
+                        _ => config.lod_fade = number,
+                    }
+                }
+                "hidden_satellites" => config.hidden_satellites = parse_list(value),
+                "timeline" => config.timeline = Some(value.to_string()).filter(|path| !path.is_empty()),
+                "near_plane" => {
+                    config.near_plane = value
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid near_plane `{}`", line_number + 1, value))?;
+                }
+                "far_plane" => {
+                    config.far_plane = value
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid far_plane `{}`", line_number + 1, value))?;
+                }
+                "log_depth" => {
+                    config.log_depth = value
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid log_depth `{}`", line_number + 1, value))?;
+                }
+                "depth_prepass" => {
+                    config.depth_prepass = value
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid depth_prepass `{}`", line_number + 1, value))?;
+                }
+                "depth_peels" => {
+                    config.depth_peels = value
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid depth_peels `{}`", line_number + 1, value))?;
+                }
+                "star_antialiasing" => {
+                    config.star_antialiasing = value
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid star_antialiasing `{}`", line_number + 1, value))?;
+                }
+                "missing_texture_checker" => {
+                    config.missing_texture_checker = value
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid missing_texture_checker `{}`", line_number + 1, value))?;
+                }
+                "binary_star" => {
+                    let binary: bool = value
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid binary_star `{}`", line_number + 1, value))?;
+                    if binary {
+                        config.add_binary_companion();
+                    }
+                }
+                "sun_light_color" => {
+                    config.sun_light_color = parse_color(value)
+                        .ok_or_else(|| format!("line {}: invalid sun_light_color `{}`", line_number + 1, value))?;
+                }
+                "backface_color" => {
+                    config.backface_color = parse_color(value)
+                        .ok_or_else(|| format!("line {}: invalid backface_color `{}`", line_number + 1, value))?;
+                }
+                _ if key.starts_with("star_") => {
+                    let (scale, light_color) = parse_star(value)
+                        .ok_or_else(|| format!("line {}: invalid {} `{}`", line_number + 1, key, value))?;
+                    let name = key["star_".len()..].to_string();
+                    config.companion_stars.retain(|star| star.name != name);
+                    config.companion_stars.push(CompanionStar { name, scale, light_color });
+                }
+                _ if key.starts_with("material_") => {
+                    let material = parse_material(value)
+                        .ok_or_else(|| format!("line {}: invalid {} `{}`", line_number + 1, key, value))?;
+                    let name = key["material_".len()..].to_string();
+                    config.body_materials.retain(|(body, _)| *body != name);
+                    config.body_materials.push((name, material));
+                }
+                _ => eprintln!("✗ Unknown config key `{}` in {}", key, path),
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Adds the default companion star (`--binary-star`), unless it is already in the system
+    pub fn add_binary_companion(&mut self) {
+        let companion = CompanionStar::default();
+        if !self.companion_stars.iter().any(|star| star.name == companion.name) {
+            self.companion_stars.push(companion);
+        }
+    }
+
+    /// Whether `model_path` is listed in `flip_texcoord_v`, by full path or by file name
+    pub fn flips_texcoord_v(&self, model_path: &str) -> bool {
+        lists_model(&self.flip_texcoord_v, model_path)
+    }
+
+    /// Up axis `model_path` was exported with (`z_up_models`, Y otherwise)
+    pub fn up_axis(&self, model_path: &str) -> UpAxis {
+        if lists_model(&self.z_up_models, model_path) { UpAxis::Z } else { UpAxis::Y }
+    }
+
+    /// Handedness `model_path` was exported with (`left_handed_models`, right-handed otherwise)
+    pub fn handedness(&self, model_path: &str) -> Handedness {
+        if lists_model(&self.left_handed_models, model_path) { Handedness::Left } else { Handedness::Right }
+    }
+
+    /// Writes every saved setting to `path` as one `key = value` line, in the format `load` reads
+    pub fn save(&self) -> std::io::Result<()> {
+        let mut file = BufWriter::new(fs::File::create(&self.path)?);
+        writeln!(file, "# Sistema Solar - configuración")?;
+        writeln!(file, "scene_seed = {}", self.scene_seed)?;
+        writeln!(file, "fix_winding = {}", self.fix_winding)?;
+        writeln!(file, "smooth_normals = {}", self.smooth_normals)?;
+        writeln!(file, "cull_backfaces = {}", self.cull_backfaces)?;
+        writeln!(file, "post_effects = {}", self.post_effects.join(", "))?;
+        writeln!(file, "hidden_bodies = {}", self.hidden_bodies.join(", "))?;
+        writeln!(file, "hidden_satellites = {}", self.hidden_satellites.join(", "))?;
+        writeln!(file, "backface_color = {}, {}, {}", self.backface_color.x, self.backface_color.y, self.backface_color.z)?;
+        writeln!(file, "timeline = {}", self.timeline.as_deref().unwrap_or(""))?;
+        writeln!(file, "near_plane = {}", self.near_plane)?;
+        writeln!(file, "far_plane = {}", self.far_plane)?;
+        writeln!(file, "log_depth = {}", self.log_depth)?;
+        writeln!(file, "depth_prepass = {}", self.depth_prepass)?;
+        writeln!(file, "depth_peels = {}", self.depth_peels)?;
+        writeln!(file, "star_antialiasing = {}", self.star_antialiasing)?;
+        writeln!(file, "missing_texture_checker = {}", self.missing_texture_checker)?;
+        writeln!(file, "sun_light_color = {}, {}, {}", self.sun_light_color.x, self.sun_light_color.y, self.sun_light_color.z)?;
+        writeln!(file, "flip_texcoord_v = {}", self.flip_texcoord_v.join(", "))?;
+        writeln!(file, "z_up_models = {}", self.z_up_models.join(", "))?;
+        writeln!(file, "left_handed_models = {}", self.left_handed_models.join(", "))?;
+        writeln!(file, "cinematic_camera = {}", self.cinematic_camera)?;
+        writeln!(file, "cinematic_speed = {}", self.cinematic.speed)?;
+        writeln!(file, "cinematic_zoom = {}", self.cinematic.zoom)?;
+        writeln!(file, "cinematic_bob = {}", self.cinematic.bob)?;
+        writeln!(file, "accretion_inner_color = {}, {}, {}", self.accretion_disk.inner_color.x, self.accretion_disk.inner_color.y, self.accretion_disk.inner_color.z)?;
+        writeln!(file, "accretion_outer_color = {}, {}, {}", self.accretion_disk.outer_color.x, self.accretion_disk.outer_color.y, self.accretion_disk.outer_color.z)?;
+        writeln!(file, "accretion_swirl_speed = {}", self.accretion_disk.swirl_speed)?;
+        writeln!(file, "grid_extent = {}", self.grid_extent)?;
+        writeln!(file, "grid_spacing = {}", self.grid_spacing)?;
+        writeln!(file, "line_thickness = {}", self.line_thickness)?;
+        writeln!(file, "velocity_arrow_scale = {}", self.velocity_arrow_scale)?;
+        writeln!(file, "ring_shadow_softness = {}", self.ring_shadow_softness)?;
+        writeln!(file, "ring_shadow_darkness = {}", self.ring_shadow_darkness)?;
+        writeln!(file, "time_scale = {}", self.time_scale)?;
+        writeln!(file, "target_fps = {}", self.target_fps)?;
+        writeln!(file, "lod_radius = {}", self.lod_radius)?;
+        writeln!(file, "lod_fade = {}", self.lod_fade)?;
+        for star in &self.companion_stars {
+            writeln!(file, "star_{} = {}, {}, {}, {}", star.name, star.scale, star.light_color.x, star.light_color.y, star.light_color.z)?;
+        }
+        for (name, material) in &self.body_materials {
+            let kind = if material.metallic { "metallic" } else { "dielectric" };
+            writeln!(file, "material_{} = {}, {}, {}", name, material.diffuse, material.specular, kind)?;
+        }
+        file.flush()
+    }
+}
+
+/// Headless batch render requested on the command line with `--render-frames <n>`
+#[derive(Clone, Debug)]
+pub struct BatchRender {
+    pub frames: u32,
+    pub out_dir: String, // `--out <dir>`, `frames` by default
+    pub step: f32,       // `--frame-step <seconds>` of animation time per frame, 0.016 by default
+    pub width: u32,      // `--size <W>x<H>`, 800x600 (the window size) by default
+    pub height: u32,
+    pub depth: bool,     // `--depth`: also write each frame's depth buffer as a 16-bit PNG
+}
+
+impl BatchRender {
+    /// None unless `--render-frames` was given; invalid values are reported and replaced by defaults
+    pub fn from_args(args: &[String]) -> Option<Self> {
+        let frames = arg_value(args, "--render-frames")?;
+        let frames = match frames.parse() {
+            Ok(frames) => frames,
+            Err(_) => {
+                eprintln!("✗ Invalid --render-frames value: {}", frames);
+                return None;
+            }
+        };
+
+        let mut batch = BatchRender {
+            frames,
+            out_dir: arg_value(args, "--out").unwrap_or("frames").to_string(),
+            step: 0.016,
+            width: 800,
+            height: 600,
+            depth: args.iter().any(|arg| arg == "--depth"),
+        };
+        if let Some(step) = arg_value(args, "--frame-step") {
+            match step.parse::<f32>() {
+                Ok(step) if step.is_finite() => batch.step = step,
+                _ => eprintln!("✗ Invalid --frame-step value: {}", step),
+            }
+        }
+        if let Some(size) = arg_value(args, "--size") {
+            let parsed = size
+                .split_once('x')
+                .and_then(|(width, height)| Some((width.parse::<u32>().ok()?, height.parse::<u32>().ok()?)))
+                .filter(|&(width, height)| width > 0 && height > 0);
+            match parsed {
+                Some((width, height)) => (batch.width, batch.height) = (width, height),
+                None => eprintln!("✗ Invalid --size value (expected WxH): {}", size),
+            }
+        }
+        Some(batch)
+    }
+}
+
+/// Shader gallery requested on the command line with `--shader-gallery`: every shader on its
+/// own sphere, side by side, at a fixed time
+#[derive(Clone, Debug)]
+pub struct ShaderGallery {
+    pub time: f32,           // `--gallery-time <seconds>` the shaders are evaluated at, 0 by default
+    pub out: Option<String>, // `--gallery-out <file>`: save the gallery (with labels) there and exit
+}
+
+impl ShaderGallery {
+    /// None unless `--shader-gallery` was given; an invalid time is reported and replaced by 0
+    pub fn from_args(args: &[String]) -> Option<Self> {
+        if !args.iter().any(|arg| arg == "--shader-gallery") {
+            return None;
+        }
+
+        let mut gallery = ShaderGallery {
+            time: 0.0,
+            out: arg_value(args, "--gallery-out").map(|path| path.to_string()),
+        };
+        if let Some(time) = arg_value(args, "--gallery-time") {
+            match time.parse::<f32>() {
+                Ok(time) if time.is_finite() => gallery.time = time,
+                _ => eprintln!("✗ Invalid --gallery-time value: {}", time),
+            }
+        }
+        Some(gallery)
+    }
+}
+
+/// Whether a list of model files names `model_path`, by full path or by file name
+fn lists_model(models: &[String], model_path: &str) -> bool {
+    let file_name = Path::new(model_path).file_name().and_then(|name| name.to_str());
+    models.iter().any(|model| model == model_path || Some(model.as_str()) == file_name)
+}
+
+/// Comma-separated list value, e.g. `vignette, bloom`
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// `diffuse, specular, metallic|dielectric` surface material, e.g. `0.65, 0.5, dielectric`
+fn parse_material(value: &str) -> Option<SurfaceMaterial> {
+    let parts: Vec<&str> = value.split(',').map(str::trim).collect();
+    match parts.as_slice() {
+        [diffuse, specular, kind] => Some(SurfaceMaterial {
+            diffuse: diffuse.parse().ok().filter(|weight: &f32| *weight >= 0.0)?,
+            specular: specular.parse().ok().filter(|weight: &f32| *weight >= 0.0)?,
+            metallic: match *kind {
+                "metallic" => true,
+                "dielectric" => false,
+                _ => return None,
+            },
+        }),
+        _ => None,
+    }
+}
+
+/// `scale, r, g, b` companion star: a positive size and the color of its light, e.g. `30, 0.5, 0.42, 0.3`
+fn parse_star(value: &str) -> Option<(f32, Vector3)> {
+    let (scale, color) = value.split_once(',')?;
+    let scale = scale.trim().parse().ok().filter(|scale: &f32| *scale > 0.0)?;
+    Some((scale, parse_color(color)?))
+}
+
+/// `r, g, b` color with components in [0, 1]
+fn parse_color(value: &str) -> Option<Vector3> {
+    let components: Vec<f32> = value
+        .split(',')
+        .map(|component| component.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    match components.as_slice() {
+        [r, g, b] => Some(Vector3::new(*r, *g, *b)),
+        _ => None,
+    }
+}
+
+/// Value following `flag` on the command line, e.g. `--seed 42`
+fn arg_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|index| args.get(index + 1))
+        .map(|value| value.as_str())
+}
+
+/// Derives the seed of one procedural process (`stream`, e.g. "stars") from the scene seed,
+/// so each process is independent but fully determined by `scene_seed` (SplitMix64 mixing)
+pub fn derive_seed(scene_seed: u64, stream: &str) -> u64 {
+    // FNV-1a hash of the stream name
+    let mut stream_hash = 0xcbf29ce484222325u64;
+    for byte in stream.bytes() {
+        stream_hash ^= byte as u64;
+        stream_hash = stream_hash.wrapping_mul(0x100000001b3);
+    }
+
+    let mut z = scene_seed ^ stream_hash;
+    z = z.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Scratch config file for one test, removed when the test is done with it
+    fn scratch_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("solar_system_{}_{}.cfg", name, std::process::id())).to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn saved_config_loads_back_unchanged() {
+        let path = scratch_path("round_trip");
+        let config = Config {
+            path: path.clone(),
+            scene_seed: 987654321,
+            fix_winding: true,
+            post_effects: vec!["vignette".to_string(), "bloom".to_string()],
+            hidden_bodies: vec!["Luna".to_string()],
+            backface_color: Vector3::new(0.25, 0.5, 0.125),
+            timeline: Some("intro.timeline".to_string()),
+            near_plane: 2.5,
+            far_plane: 1234.5,
+            log_depth: true,
+            depth_peels: 3,
+            z_up_models: vec!["ship.obj".to_string()],
+            cinematic: CinematicCamera { speed: -0.3, zoom: 1.75, bob: 0.1 },
+            grid_spacing: 0.1,
+            time_scale: 2.5,
+            target_fps: 0,
+            sun_light_color: Vector3::new(1.2, 1.0, 0.9),
+            companion_stars: vec![CompanionStar { name: "Sol C".to_string(), scale: 12.5, light_color: Vector3::new(0.3, 0.4, 0.75) }],
+            body_materials: vec![("Sol".to_string(), SurfaceMaterial { diffuse: 0.3, specular: 1.45, metallic: true })],
+            ..Config::default()
+        };
+        config.save().unwrap();
+        let saved = fs::read_to_string(&path);
+        let loaded = Config::load(&path);
+        let _ = fs::remove_file(&path);
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.scene_seed, 987654321);
+        assert!(loaded.fix_winding && loaded.log_depth);
+        assert_eq!(loaded.post_effects, config.post_effects);
+        assert_eq!(loaded.hidden_bodies, config.hidden_bodies);
+        assert!(loaded.hidden_satellites.is_empty());
+        assert_eq!(loaded.backface_color, config.backface_color);
+        assert_eq!(loaded.timeline, config.timeline);
+        assert_eq!((loaded.near_plane, loaded.far_plane), (2.5, 1234.5));
+        assert_eq!(loaded.depth_peels, 3);
+        assert_eq!(loaded.z_up_models, config.z_up_models);
+        assert_eq!((loaded.cinematic.speed, loaded.cinematic.zoom, loaded.cinematic.bob), (-0.3, 1.75, 0.1));
+        assert_eq!((loaded.grid_spacing, loaded.time_scale, loaded.target_fps), (0.1, 2.5, 0));
+        assert_eq!(loaded.sun_light_color, config.sun_light_color);
+        assert_eq!(loaded.companion_stars, config.companion_stars);
+        let (name, material) = &loaded.body_materials[0];
+        assert_eq!((name.as_str(), material.diffuse, material.specular, material.metallic), ("Sol", 0.3, 1.45, true));
+
+        // Every other key too: saving what was loaded writes the same file again
+        let resaved_path = scratch_path("round_trip_resaved");
+        Config { path: resaved_path.clone(), ..loaded }.save().unwrap();
+        let resaved = fs::read_to_string(&resaved_path);
+        let _ = fs::remove_file(&resaved_path);
+        assert_eq!(resaved.unwrap(), saved.unwrap());
+    }
+
+    #[test]
+    fn derived_seeds_depend_only_on_the_scene_seed_and_stream() {
+        assert_eq!(derive_seed(12345, "stars"), derive_seed(12345, "stars"));
+        assert_ne!(derive_seed(12345, "stars"), derive_seed(12346, "stars"));
+        assert_ne!(derive_seed(12345, "stars"), derive_seed(12345, "moons"));
+        // Nearby scene seeds still land far apart
+        let (a, b) = (derive_seed(0, "stars"), derive_seed(1, "stars"));
+        assert!((a ^ b).count_ones() > 16);
+    }
+}
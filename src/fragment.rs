@@ -5,7 +5,13 @@ use raylib::math::{Vector2, Vector3};
 pub struct Fragment {
     pub position: Vector2,
     pub color: Vector3,
+    pub normal: Vector3,     // Interpolated object-space normal
+    pub object_position: Vector3, // Interpolated object-space position (shader patterns)
+    pub transformed_normal: Vector3, // Interpolated vertex shader normal, used for lighting
+    pub tangent: Vector3,    // Interpolated tangent (normal mapping)
+    pub tex_coords: Vector2, // Interpolated UV (normal mapping)
     pub depth: f32,
+    pub front_facing: bool, // Clockwise on screen, like OBJ front faces after the loader's Y flip
 }
 
 impl Fragment {
@@ -13,7 +19,13 @@ impl Fragment {
         Fragment {
             position: Vector2::new(x, y),
             color,
+            normal: Vector3::new(0.0, 1.0, 0.0),
+            object_position: Vector3::new(0.0, 0.0, 0.0),
+            transformed_normal: Vector3::new(0.0, 1.0, 0.0),
+            tangent: Vector3::new(1.0, 0.0, 0.0),
+            tex_coords: Vector2::new(0.0, 0.0),
             depth,
+            front_facing: true,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,143 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::time::Duration;
+
+/// Ring buffer of the most recent frame times (seconds), for the frame-time overlay
+pub struct FrameTimeHistory {
+    samples: Vec<f32>,
+    next: usize, // Slot the next sample is written to
+    len: usize,  // Number of valid samples (<= capacity)
+}
+
+impl FrameTimeHistory {
+    pub fn new(capacity: usize) -> Self {
+        FrameTimeHistory {
+            samples: vec![0.0; capacity.max(1)],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, frame_time: f32) {
+        self.samples[self.next] = frame_time;
+        self.next = (self.next + 1) % self.samples.len();
+        self.len = (self.len + 1).min(self.samples.len());
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Samples from oldest to newest
+    pub fn iter(&self) -> impl Iterator<Item = f32> + '_ {
+        let start = (self.next + self.samples.len() - self.len) % self.samples.len();
+        (0..self.len).map(move |i| self.samples[(start + i) % self.samples.len()])
+    }
+
+    /// (min, max, average) of the stored samples, or None before the first frame
+    pub fn stats(&self) -> Option<(f32, f32, f32)> {
+        if self.len == 0 {
+            return None;
+        }
+        let (mut min, mut max, mut sum) = (f32::MAX, f32::MIN, 0.0);
+        for sample in self.iter() {
+            min = min.min(sample);
+            max = max.max(sample);
+            sum += sample;
+        }
+        Some((min, max, sum / self.len as f32))
+    }
+}
+
+/// Time spent in each pipeline stage during one frame, summed over every draw
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StageTimings {
+    pub vertex: Duration,   // Vertex shader
+    pub raster: Duration,   // Primitive assembly + triangle rasterization
+    pub fragment: Duration, // Fragment shading and framebuffer writes
+    pub post: Duration,     // Post-process chain
+    pub frame: Duration,    // Whole software render, from clear to texture upload
+}
+
+impl StageTimings {
+    /// (label, duration) per stage, plus whatever the frame spent outside them
+    pub fn rows(&self) -> [(&'static str, Duration); 5] {
+        let stages = self.vertex + self.raster + self.fragment + self.post;
+        [
+            ("Vertices", self.vertex),
+            ("Rasterizado", self.raster),
+            ("Fragmentos", self.fragment),
+            ("Post-proceso", self.post),
+            ("Otros", self.frame.saturating_sub(stages)),
+        ]
+    }
+}
+
+/// Counters of one frame of the software render, reset at the start of every frame
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderStats {
+    pub timings: StageTimings,
+    pub triangles: usize,                // Triangles rasterized (after the behind-camera reject)
+    pub fragments: usize,                // Fragments produced by rasterization, before clipping
+    pub depth_range: Option<(f32, f32)>, // Min/max fragment depth rasterized, before clipping
+    pub clipped_fragments: usize,        // Fragments dropped by the near/far planes
+    pub behind_camera_triangles: usize,  // Triangles entirely nearer than the near plane (nothing left after clipping)
+    pub occluded_triangles: usize,       // Triangles skipped because earlier draws already hide their whole bounding box
+    pub shaded_fragments: usize,         // Fragment shader runs; with the depth prepass only the visible fragments
+}
+
+impl RenderStats {
+    pub fn record_depth(&mut self, depth: f32) {
+        self.depth_range = Some(match self.depth_range {
+            Some((min, max)) => (min.min(depth), max.max(depth)),
+            None => (depth, depth),
+        });
+    }
+}
+
+/// Per-frame `RenderStats` written as CSV rows (`--profile-csv <path>`), for plotting a session.
+/// Rows go through a buffer; call `finish` on exit so the tail reaches the file.
+pub struct ProfileCsv {
+    writer: BufWriter<File>,
+}
+
+impl ProfileCsv {
+    pub fn create(path: &str) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(
+            writer,
+            "frame,time,fps,triangles,fragments,clipped_fragments,behind_camera_triangles,occluded_triangles,shaded_fragments,vertex_ms,raster_ms,fragment_ms,post_ms,other_ms,frame_ms"
+        )?;
+        Ok(ProfileCsv { writer })
+    }
+
+    /// One row: `frame_time` is the wall time of the whole frame in seconds (the fps column is its inverse)
+    pub fn write_frame(&mut self, frame: u64, time: f32, frame_time: f32, stats: &RenderStats) -> io::Result<()> {
+        let fps = if frame_time > 0.0 { 1.0 / frame_time } else { 0.0 };
+        let ms = |duration: Duration| duration.as_secs_f32() * 1000.0;
+        let [vertex, raster, fragment, post, other] = stats.timings.rows().map(|(_, duration)| ms(duration));
+        writeln!(
+            self.writer,
+            "{},{:.3},{:.1},{},{},{},{},{},{},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3}",
+            frame,
+            time,
+            fps,
+            stats.triangles,
+            stats.fragments,
+            stats.clipped_fragments,
+            stats.behind_camera_triangles,
+            stats.occluded_triangles,
+            stats.shaded_fragments,
+            vertex,
+            raster,
+            fragment,
+            post,
+            other,
+            ms(stats.timings.frame),
+        )
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -1,12 +1,100 @@
+use crate::line::{bresenham, clip_segment};
 use raylib::prelude::*;
+use std::fmt;
+
+const DEFAULT_STAR_SEED: u64 = 12345;
+
+/// Gray level of the farthest surface in a depth PNG; the empty background gets `u16::MAX`
+const FAR_DEPTH_LEVEL: u16 = u16::MAX - 1;
+
+/// Apparent diameter in pixels of the brightest star when stars are anti-aliased
+const STAR_MAX_SIZE: f32 = 2.0;
+
+/// How a point combines its color (`src`, with opacity `alpha`) with the pixel already stored
+/// (`dst`); set on the framebuffer with `Framebuffer::set_blend_mode`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    /// `src`: overwrites the pixel, whatever `alpha` (opaque surfaces)
+    #[default]
+    Replace,
+    /// Alpha-over: `dst * (1 - alpha) + src * alpha` (translucent surfaces)
+    AlphaBlend,
+    /// `dst + src * alpha` (glows)
+    Additive,
+    /// `dst * mix(1, src, alpha)` (tinting/shadowing)
+    Multiply,
+}
+
+impl BlendMode {
+    /// Next translucent mode in AlphaBlend -> Additive -> Multiply order, for cycling from the keyboard
+    pub fn next(self) -> BlendMode {
+        match self {
+            BlendMode::Replace | BlendMode::Multiply => BlendMode::AlphaBlend,
+            BlendMode::AlphaBlend => BlendMode::Additive,
+            BlendMode::Additive => BlendMode::Multiply,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BlendMode::Replace => "Reemplazar",
+            BlendMode::AlphaBlend => "Alpha",
+            BlendMode::Additive => "Aditivo",
+            BlendMode::Multiply => "Multiplicar",
+        }
+    }
+
+    /// Blends one channel in [0, 1]
+    fn blend(self, dst: f32, src: f32, alpha: f32) -> f32 {
+        match self {
+            BlendMode::Replace => src,
+            BlendMode::AlphaBlend => dst * (1.0 - alpha) + src * alpha,
+            BlendMode::Additive => dst + src * alpha,
+            BlendMode::Multiply => dst * (1.0 + (src - 1.0) * alpha),
+        }
+    }
+}
+
+/// Failures of the GPU texture that presents the framebuffer, or of saving it as an image
+#[derive(Debug)]
+pub enum FramebufferError {
+    /// `update_texture` was called before `init_texture`
+    TextureNotInitialized,
+    /// raylib could not create the texture from the image
+    TextureLoad(String),
+    /// raylib rejected the pixel upload
+    TextureUpdate(String),
+    /// The image could not be written to this path
+    Export(String),
+}
+
+impl fmt::Display for FramebufferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FramebufferError::TextureNotInitialized => write!(
+                f,
+                "framebuffer texture has not been initialized; call init_texture after creating the RaylibHandle"
+            ),
+            FramebufferError::TextureLoad(e) => write!(f, "failed to create framebuffer texture: {}", e),
+            FramebufferError::TextureUpdate(e) => write!(f, "failed to update framebuffer texture: {}", e),
+            FramebufferError::Export(path) => write!(f, "failed to write framebuffer image to {}", path),
+        }
+    }
+}
+
+impl std::error::Error for FramebufferError {}
 
 pub struct Framebuffer {
     pub width: u32,
     pub height: u32,
     image: Image,
+    depth: Vec<f32>, // Depth of the nearest surface written at each pixel (smaller = closer), row-major
     background_color: Vector3,
     texture: Option<Texture2D>,
-    star_field: Vec<(i32, i32, f32)>, // (x, y, brightness)
+    star_field: Vec<(f32, f32, f32)>, // (x, y, brightness), x/y with 1/8 pixel precision
+    show_stars: bool,
+    star_antialiasing: bool, // Soft sub-pixel splats instead of single pixels/crosses
+    blend_mode: BlendMode,   // How `point` and friends combine colors with the stored pixels
 }
 
 impl Framebuffer {
@@ -14,24 +102,33 @@ impl Framebuffer {
         let image = Image::gen_image_color(width as i32, height as i32, Color::BLACK);
         
         // Generate star field
-        let star_field = Self::generate_stars(width, height);
+        let star_field = Self::generate_stars(width, height, DEFAULT_STAR_SEED);
         
         Framebuffer {
             width,
             height,
             image,
+            depth: vec![f32::INFINITY; (width * height) as usize],
             background_color: Vector3::zero(),
             texture: None,
             star_field,
+            show_stars: true,
+            star_antialiasing: true,
+            blend_mode: BlendMode::Replace,
         }
     }
     
-    fn generate_stars(width: u32, height: u32) -> Vec<(i32, i32, f32)> {
+    /// Regenerates the star field from a new seed (same seed, same sky)
+    pub fn set_star_seed(&mut self, seed: u64) {
+        self.star_field = Self::generate_stars(self.width, self.height, seed);
+    }
+    
+    fn generate_stars(width: u32, height: u32, seed: u64) -> Vec<(f32, f32, f32)> {
         let mut stars = Vec::new();
         let star_count = 800; // Number of stars
         
         // Simple pseudo-random number generator using LCG
-        let mut seed = 12345u64;
+        let mut seed = seed;
         let a = 1103515245u64;
         let c = 12345u64;
         let m = 2u64.pow(31);
@@ -39,11 +136,11 @@ impl Framebuffer {
         for _ in 0..star_count {
             // Generate pseudo-random x
             seed = (a.wrapping_mul(seed).wrapping_add(c)) % m;
-            let x = (seed % width as u64) as i32;
+            let x = (seed % (width as u64 * 8)) as f32 / 8.0;
             
             // Generate pseudo-random y
             seed = (a.wrapping_mul(seed).wrapping_add(c)) % m;
-            let y = (seed % height as u64) as i32;
+            let y = (seed % (height as u64 * 8)) as f32 / 8.0;
             
             // Generate pseudo-random brightness (0.3 to 1.0)
             seed = (a.wrapping_mul(seed).wrapping_add(c)) % m;
@@ -55,11 +152,17 @@ impl Framebuffer {
         stars
     }
 
-    pub fn init_texture(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread) {
-        self.texture = Some(rl.load_texture_from_image(thread, &self.image).unwrap());
+    pub fn init_texture(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread) -> Result<(), FramebufferError> {
+        let texture = rl
+            .load_texture_from_image(thread, &self.image)
+            .map_err(|e| FramebufferError::TextureLoad(e.to_string()))?;
+        self.texture = Some(texture);
+        Ok(())
     }
 
     pub fn clear(&mut self) {
+        self.depth.fill(f32::INFINITY);
+
         // Draw deep space background
         let bg_color = Color::new(
             (self.background_color.x * 255.0) as u8,
@@ -68,9 +171,19 @@ impl Framebuffer {
             255,
         );
         self.image.clear_background(bg_color);
+
+        if !self.show_stars {
+            return;
+        }
         
+        if self.star_antialiasing {
+            self.draw_soft_stars();
+            return;
+        }
+
         // Draw stars
         for &(x, y, brightness) in &self.star_field {
+            let (x, y) = (x as i32, y as i32);
             let star_color = Color::new(
                 (255.0 * brightness) as u8,
                 (255.0 * brightness) as u8,
@@ -118,23 +231,281 @@ impl Framebuffer {
         }
     }
 
+    /// Stars as soft splats centered on their sub-pixel position, sized by brightness.
+    /// Stars smaller than a pixel fade with their area instead of snapping to full pixels.
+    fn draw_soft_stars(&mut self) {
+        // Taken out while drawing because soft_point needs `&mut self`
+        let stars = std::mem::take(&mut self.star_field);
+        for &(x, y, brightness) in &stars {
+            let size = STAR_MAX_SIZE * brightness;
+            let coverage = (size * size).min(1.0);
+            let color = Vector3::new(brightness, brightness, brightness * 0.9) * coverage; // Slight blue tint
+            self.soft_point(x, y, size.max(1.0), color);
+        }
+        self.star_field = stars;
+    }
+
+    /// Writes `color` at (x, y) with the current blend mode, fully opaque
     pub fn point(&mut self, x: i32, y: i32, color: Vector3) {
+        self.point_blended(x, y, color, 1.0);
+    }
+
+    /// Depth-tested write: `color` is written only when `depth` is closer (smaller) than what is
+    /// already at (x, y), with the current blend mode (see `point_blended_with_depth`).
+    /// Overlays that ignore occlusion (stars, gizmos) keep using `point`.
+    pub fn point_with_depth(&mut self, x: i32, y: i32, depth: f32, color: Vector3) {
+        self.point_blended_with_depth(x, y, depth, color, 1.0);
+    }
+
+    /// Records a surface at (x, y): keeps the nearer of `depth` and the stored depth and leaves
+    /// the color alone
+    pub fn write_depth(&mut self, x: i32, y: i32, depth: f32) {
         if x >= 0 && y >= 0 && x < self.width as i32 && y < self.height as i32 {
-            let pixel_color = Color::new(
-                (color.x.clamp(0.0, 1.0) * 255.0) as u8,
-                (color.y.clamp(0.0, 1.0) * 255.0) as u8,
-                (color.z.clamp(0.0, 1.0) * 255.0) as u8,
-                255,
-            );
+            let index = y as usize * self.width as usize + x as usize;
+            self.depth[index] = self.depth[index].min(depth);
+        }
+    }
+
+    /// Depth of the nearest surface at every pixel, row-major (smaller = closer)
+    pub fn depth_buffer(&self) -> &[f32] {
+        &self.depth
+    }
+
+    /// Whether the depth stored at (x, y) is exactly `depth`: once the prepass has written the
+    /// nearest depth of every pixel, only the fragment that put it there passes
+    pub fn depth_matches(&self, x: i32, y: i32, depth: f32) -> bool {
+        x >= 0
+            && y >= 0
+            && x < self.width as i32
+            && y < self.height as i32
+            && self.depth[y as usize * self.width as usize + x as usize] == depth
+    }
+
+    /// Whether every pixel of the box (min_x, min_y, max_x, max_y), inclusive, already holds a
+    /// surface at least as near as `depth`: nothing at `depth` or farther can show there.
+    /// Pixels outside the framebuffer are ignored.
+    pub fn depth_hides(&self, bounds: (i32, i32, i32, i32), depth: f32) -> bool {
+        let (min_x, min_y) = (bounds.0.max(0) as usize, bounds.1.max(0) as usize);
+        let (max_x, max_y) = (bounds.2.min(self.width as i32 - 1), bounds.3.min(self.height as i32 - 1));
+        if max_x < min_x as i32 || max_y < min_y as i32 {
+            return false;
+        }
+        let width = self.width as usize;
+        (min_y..=max_y as usize).all(|y| {
+            self.depth[y * width + min_x..=y * width + max_x as usize].iter().all(|&stored| stored <= depth)
+        })
+    }
+
+    /// Depth-tested write with opacity `alpha`: combined with the current blend mode only when
+    /// `depth` is in front of the stored depth. `Replace` stores `depth` too, so the surface hides
+    /// what is drawn behind it later; the blending modes leave it as is, so translucent surfaces
+    /// let what lies behind them show through.
+    pub fn point_blended_with_depth(&mut self, x: i32, y: i32, depth: f32, color: Vector3, alpha: f32) {
+        if x >= 0 && y >= 0 && x < self.width as i32 && y < self.height as i32 {
+            let index = y as usize * self.width as usize + x as usize;
+            if depth < self.depth[index] {
+                if self.blend_mode == BlendMode::Replace {
+                    self.depth[index] = depth;
+                }
+                self.point_blended(x, y, color, alpha);
+            }
+        }
+    }
+
+    /// Adds `color` to the pixel already stored at (x, y), clamping each channel, whatever the
+    /// current blend mode
+    pub fn point_additive(&mut self, x: i32, y: i32, color: Vector3) {
+        let color = Vector3::new(color.x.max(0.0), color.y.max(0.0), color.z.max(0.0));
+        self.blend_pixel(x, y, color, 1.0, BlendMode::Additive);
+    }
+
+    /// Combines `color`, with opacity `alpha`, with the pixel already stored at (x, y) using the
+    /// current blend mode, clamping each channel
+    pub fn point_blended(&mut self, x: i32, y: i32, color: Vector3, alpha: f32) {
+        self.blend_pixel(x, y, color, alpha, self.blend_mode);
+    }
+
+    /// How `point` and the other writes combine colors with the stored pixels from now on
+    pub fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.blend_mode = mode;
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    /// Runs `draw` with `mode` as the blend mode, then restores the previous one
+    pub fn with_blend_mode<R>(&mut self, mode: BlendMode, draw: impl FnOnce(&mut Self) -> R) -> R {
+        let previous = self.blend_mode();
+        self.set_blend_mode(mode);
+        let result = draw(self);
+        self.set_blend_mode(previous);
+        result
+    }
+
+    fn blend_pixel(&mut self, x: i32, y: i32, color: Vector3, alpha: f32, mode: BlendMode) {
+        if x >= 0 && y >= 0 && x < self.width as i32 && y < self.height as i32 {
+            let color = sanitize_color(color);
+            let pixel_color = if mode == BlendMode::Replace {
+                Color::new(
+                    (color.x.clamp(0.0, 1.0) * 255.0) as u8,
+                    (color.y.clamp(0.0, 1.0) * 255.0) as u8,
+                    (color.z.clamp(0.0, 1.0) * 255.0) as u8,
+                    255,
+                )
+            } else {
+                let existing = self.image.get_color(x, y);
+                let alpha = if alpha.is_finite() { alpha.clamp(0.0, 1.0) } else { 0.0 };
+                let channel = |dst: u8, src: f32| {
+                    (mode.blend(dst as f32 / 255.0, src, alpha).clamp(0.0, 1.0) * 255.0) as u8
+                };
+                Color::new(
+                    channel(existing.r, color.x),
+                    channel(existing.g, color.y),
+                    channel(existing.b, color.z),
+                    255,
+                )
+            };
             self.image.draw_pixel(x, y, pixel_color);
         }
     }
 
+    /// Draws a 1-pixel line from (x0, y0) to (x1, y1), both ends included, with the same
+    /// Bresenham walk as the scene's lines (`line::bresenham`). The segment is first clipped to
+    /// the framebuffer, so lines reaching far off screen only walk their visible pixels. The ends
+    /// are taken in a fixed order, so a segment lights the same pixels whichever way it is drawn.
+    pub fn line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: Vector3) {
+        let (from, to) = if (x0, y0) <= (x1, y1) { ((x0, y0), (x1, y1)) } else { ((x1, y1), (x0, y0)) };
+        let Some((start, end)) = self.clip_segment(from, to) else {
+            return;
+        };
+        for (x, y) in bresenham(start, end) {
+            self.point(x, y, color);
+        }
+    }
+
+    /// Liang-Barsky clip of a segment to the pixel grid (`line::clip_segment`); `None` when it
+    /// misses the framebuffer. Clipped ends are rounded to the nearest pixel.
+    fn clip_segment(&self, start: (i32, i32), end: (i32, i32)) -> Option<((i32, i32), (i32, i32))> {
+        let (x0, y0) = (start.0 as f64, start.1 as f64);
+        let (x1, y1) = (end.0 as f64, end.1 as f64);
+        let max = (self.width as f64 - 1.0, self.height as f64 - 1.0);
+        let (t_min, t_max) = clip_segment((x0, y0), (x1, y1), (0.0, 0.0), max)?;
+
+        let at = |t: f64| ((x0 + (x1 - x0) * t).round() as i32, (y0 + (y1 - y0) * t).round() as i32);
+        Some((at(t_min), at(t_max)))
+    }
+
+    /// Additively draws a soft round splat centered at a sub-pixel position.
+    /// Intensity falls off smoothly from `color` at the center to zero at `radius`.
+    pub fn soft_point(&mut self, x: f32, y: f32, radius: f32, color: Vector3) {
+        // In front of everything: nothing stored can hide it
+        self.soft_point_with_depth(x, y, f32::NEG_INFINITY, radius, color);
+    }
+
+    /// `soft_point` at `depth`: the splat only shows on pixels where nothing nearer is stored.
+    /// Like the other additive writes it leaves the depth alone.
+    pub fn soft_point_with_depth(&mut self, x: f32, y: f32, depth: f32, radius: f32, color: Vector3) {
+        if radius <= 0.0 {
+            return;
+        }
+
+        let min_x = (x - radius).floor().max(0.0) as i32;
+        let max_x = (x + radius).ceil().min(self.width as f32 - 1.0) as i32;
+        let min_y = (y - radius).floor().max(0.0) as i32;
+        let max_y = (y + radius).ceil().min(self.height as f32 - 1.0) as i32;
+
+        for py in min_y..=max_y {
+            for px in min_x..=max_x {
+                if depth >= self.depth[py as usize * self.width as usize + px as usize] {
+                    continue;
+                }
+                let dx = px as f32 + 0.5 - x;
+                let dy = py as f32 + 0.5 - y;
+                let t = 1.0 - (dx * dx + dy * dy).sqrt() / radius;
+                if t > 0.0 {
+                    let falloff = t * t;
+                    self.point_additive(px, py, color * falloff);
+                }
+            }
+        }
+    }
+
     pub fn set_background_color(&mut self, color: Vector3) {
         self.background_color = color;
     }
 
-    pub fn update_texture(&mut self) {
+    /// Draw stars as anti-aliased sub-pixel splats (default) or as the classic single pixels
+    pub fn set_star_antialiasing(&mut self, enabled: bool) {
+        self.star_antialiasing = enabled;
+    }
+
+    /// Enable or disable the star field drawn by `clear` (plain background when disabled)
+    pub fn set_stars_visible(&mut self, visible: bool) {
+        self.show_stars = visible;
+    }
+
+    /// Writes the depth buffer as a 16-bit grayscale PNG through the `image` crate, for checking
+    /// occlusion offline or compositing in external tools (see `depth_levels` for the scale)
+    pub fn save_depth_png(&self, path: &str) -> Result<(), FramebufferError> {
+        let png = image::ImageBuffer::<image::Luma<u16>, Vec<u16>>::from_raw(self.width, self.height, depth_levels(&self.depth))
+            .ok_or_else(|| FramebufferError::Export(path.to_string()))?;
+        png.save_with_format(path, image::ImageFormat::Png)
+            .map_err(|_| FramebufferError::Export(path.to_string()))
+    }
+
+    /// Copy of the current pixels, row by row
+    pub fn snapshot(&self) -> Vec<Color> {
+        self.image.get_image_data().to_vec()
+    }
+
+    /// Side-by-side stereo packing: `left` (a `snapshot` of the left-eye frame) is squeezed into
+    /// the left half and the current frame into the right half, averaging each pair of columns
+    pub fn compose_side_by_side(&mut self, left: &[Color]) {
+        let right = self.snapshot();
+        let width = self.width as usize;
+        let half = width / 2;
+        let average = |a: Color, b: Color| {
+            Color::new(
+                ((a.r as u16 + b.r as u16) / 2) as u8,
+                ((a.g as u16 + b.g as u16) / 2) as u8,
+                ((a.b as u16 + b.b as u16) / 2) as u8,
+                255,
+            )
+        };
+
+        for y in 0..self.height as usize {
+            let row = y * width;
+            for x in 0..half {
+                let (a, b) = (row + 2 * x, row + (2 * x + 1).min(width - 1));
+                self.image.draw_pixel(x as i32, y as i32, average(left[a], left[b]));
+                self.image.draw_pixel((half + x) as i32, y as i32, average(right[a], right[b]));
+            }
+        }
+    }
+
+    /// Writes text into the pixels with raylib's default font, so it is kept by `save_png`.
+    /// The default font is loaded with the window: call only after the window is open.
+    pub fn draw_text(&mut self, text: &str, x: i32, y: i32, font_size: i32, color: Color) {
+        self.image.draw_text(text, x, y, font_size, color);
+    }
+
+    /// Writes the current pixels as an RGBA PNG through the `image` crate. It only reads the CPU
+    /// image, never the GPU texture, so it works before `init_texture` and in headless batch
+    /// renders. raylib images are stored top row first like PNG, so no row flip is needed.
+    pub fn save_png(&self, path: &str) -> Result<(), FramebufferError> {
+        let colors = self.image.get_image_data();
+        let mut rgba = Vec::with_capacity(colors.len() * 4);
+        for color in colors.iter() {
+            rgba.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+        let png = image::RgbaImage::from_raw(self.width, self.height, rgba)
+            .ok_or_else(|| FramebufferError::Export(path.to_string()))?;
+        png.save_with_format(path, image::ImageFormat::Png)
+            .map_err(|_| FramebufferError::Export(path.to_string()))
+    }
+
+    pub fn update_texture(&mut self) -> Result<(), FramebufferError> {
         if let Some(texture) = &mut self.texture {
             let colors = self.image.get_image_data();
             // Safely cast the &[Color] slice to a &[u8] slice for the update function
@@ -144,15 +515,419 @@ impl Framebuffer {
                     colors.len() * 4, // Each Color is 4 bytes (r,g,b,a)
                 )
             };
-            texture.update_texture(data).unwrap();
+            texture
+                .update_texture(data)
+                .map_err(|e| FramebufferError::TextureUpdate(e.to_string()))
         } else {
-            panic!("Framebuffer texture has not been initialized. Call init_texture after creating the RaylibHandle.");
+            Err(FramebufferError::TextureNotInitialized)
         }
     }
 
+    /// Full-screen blit at the window origin (the default presentation)
     pub fn draw(&self, draw_handle: &mut RaylibDrawHandle) {
         if let Some(texture) = &self.texture {
             draw_handle.draw_texture(texture, 0, 0, Color::WHITE);
         }
     }
-}
\ No newline at end of file
+
+    /// GPU texture holding the last uploaded frame, for hosts that place it themselves
+    pub fn as_texture(&self) -> Result<&Texture2D, FramebufferError> {
+        self.texture.as_ref().ok_or(FramebufferError::TextureNotInitialized)
+    }
+
+    /// Copies the last uploaded frame into `target`, scaled to fill it, so a larger raylib UI
+    /// can draw it inside a panel (see `draw_render_texture`)
+    pub fn render_to_texture(
+        &self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        target: &mut RenderTexture2D,
+    ) -> Result<(), FramebufferError> {
+        let texture = self.as_texture()?;
+        let (target_width, target_height) = (target.texture().width() as f32, target.texture().height() as f32);
+
+        let mut texture_mode = rl.begin_texture_mode(thread, target);
+        texture_mode.clear_background(Color::BLACK);
+        texture_mode.draw_texture_pro(
+            texture,
+            Rectangle::new(0.0, 0.0, self.width as f32, self.height as f32),
+            Rectangle::new(0.0, 0.0, target_width, target_height),
+            Vector2::zero(),
+            0.0,
+            Color::WHITE,
+        );
+        Ok(())
+    }
+}
+
+/// Draws a render texture filled by `Framebuffer::render_to_texture` into `dest`.
+/// Render textures are stored upside down, so the source rectangle flips it back.
+pub fn draw_render_texture(draw_handle: &mut RaylibDrawHandle, target: &RenderTexture2D, dest: Rectangle) {
+    let texture = target.texture();
+    let source = Rectangle::new(0.0, 0.0, texture.width() as f32, -(texture.height() as f32));
+    draw_handle.draw_texture_pro(texture, source, dest, Vector2::zero(), 0.0, Color::WHITE);
+}
+
+/// 16-bit gray levels of a depth buffer: the stored depths scaled linearly from the nearest
+/// (0, black) to the farthest (`FAR_DEPTH_LEVEL`). Pixels nothing was drawn on (infinite depth)
+/// get the maximum level, one step past the farthest surface.
+fn depth_levels(depth: &[f32]) -> Vec<u16> {
+    let (near, far) = depth
+        .iter()
+        .filter(|depth| depth.is_finite())
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(near, far), &depth| (near.min(depth), far.max(depth)));
+    let span = far - near;
+    depth
+        .iter()
+        .map(|&depth| {
+            if !depth.is_finite() {
+                u16::MAX
+            } else if span > 0.0 {
+                ((depth - near) / span * FAR_DEPTH_LEVEL as f32).round() as u16
+            } else {
+                0
+            }
+        })
+        .collect()
+}
+
+/// Replaces NaN/infinite channels with 0 so a broken shader output can't reach the image
+fn sanitize_color(color: Vector3) -> Vector3 {
+    let finite_or_zero = |channel: f32| if channel.is_finite() { channel } else { 0.0 };
+    Vector3::new(finite_or_zero(color.x), finite_or_zero(color.y), finite_or_zero(color.z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::derive_seed;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-6, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn blend_modes_match_their_formulas() {
+        // dst 0.4, src 0.8, alpha 0.25
+        assert_close(BlendMode::Replace.blend(0.4, 0.8, 0.25), 0.8);
+        assert_close(BlendMode::AlphaBlend.blend(0.4, 0.8, 0.25), 0.5);
+        assert_close(BlendMode::Additive.blend(0.4, 0.8, 0.25), 0.6);
+        assert_close(BlendMode::Multiply.blend(0.4, 0.8, 0.25), 0.38);
+
+        // Fully opaque: replace, add, multiply
+        assert_close(BlendMode::AlphaBlend.blend(0.4, 0.8, 1.0), 0.8);
+        assert_close(BlendMode::Additive.blend(0.4, 0.8, 1.0), 1.2);
+        assert_close(BlendMode::Multiply.blend(0.4, 0.8, 1.0), 0.32);
+
+        // Fully transparent leaves the destination alone
+        for mode in [BlendMode::AlphaBlend, BlendMode::Additive, BlendMode::Multiply] {
+            assert_close(mode.blend(0.4, 0.8, 0.0), 0.4);
+        }
+    }
+
+    #[test]
+    fn blended_points_combine_with_the_stored_pixel() {
+        let mut framebuffer = Framebuffer::new(4, 1);
+        let gray = Vector3::new(0.4, 0.4, 0.4);
+        for x in 0..3 {
+            framebuffer.point(x, 0, gray);
+        }
+        let white = Vector3::new(1.0, 1.0, 1.0);
+        framebuffer.set_blend_mode(BlendMode::AlphaBlend);
+        framebuffer.point_blended(0, 0, white, 0.5);
+        framebuffer.set_blend_mode(BlendMode::Additive);
+        framebuffer.point_blended(1, 0, white, 1.0);
+        framebuffer.set_blend_mode(BlendMode::Multiply);
+        framebuffer.point_blended(2, 0, Vector3::new(0.5, 0.5, 0.5), 1.0);
+
+        // Stored channels are bytes, so allow one step of rounding
+        let stored = framebuffer.snapshot();
+        for (x, expected) in [(0, 0.7), (1, 1.0), (2, 0.2)] {
+            let red = stored[x].r as f32 / 255.0;
+            assert!((red - expected).abs() <= 1.0 / 255.0 + 1e-6, "pixel {}: expected {}, got {}", x, expected, red);
+        }
+    }
+
+    #[test]
+    fn non_finite_channels_are_written_as_zero() {
+        let mut framebuffer = Framebuffer::new(2, 1);
+        framebuffer.point(0, 0, Vector3::new(f32::NAN, 0.5, f32::INFINITY));
+        framebuffer.point(1, 0, Vector3::new(0.2, 0.2, 0.2));
+        framebuffer.set_blend_mode(BlendMode::AlphaBlend);
+        framebuffer.point_blended(1, 0, Vector3::new(f32::NAN, f32::NEG_INFINITY, 1.0), f32::NAN);
+
+        let stored = framebuffer.snapshot();
+        assert_eq!((stored[0].r, stored[0].g, stored[0].b), (0, 127, 0));
+        // A NaN alpha leaves the pixel as it was
+        assert_eq!((stored[1].r, stored[1].g, stored[1].b), (51, 51, 51));
+    }
+
+    #[test]
+    fn depth_tested_points_keep_the_nearest_surface() {
+        let mut framebuffer = Framebuffer::new(2, 1);
+        let (red, blue) = (Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0));
+        // Near first, then far: the far point is rejected
+        framebuffer.point_with_depth(0, 0, 0.2, red);
+        framebuffer.point_with_depth(0, 0, 0.6, blue);
+        // Far first, then near: the near point replaces it
+        framebuffer.point_with_depth(1, 0, 0.6, blue);
+        framebuffer.point_with_depth(1, 0, 0.2, red);
+
+        let stored = framebuffer.snapshot();
+        for pixel in &stored {
+            assert_eq!((pixel.r, pixel.b), (255, 0));
+        }
+        assert_eq!(framebuffer.depth_buffer(), &[0.2, 0.2]);
+    }
+
+    #[test]
+    fn point_writes_follow_the_stored_blend_mode() {
+        let mut framebuffer = Framebuffer::new(3, 1);
+        assert_eq!(framebuffer.blend_mode(), BlendMode::Replace);
+        let gray = Vector3::new(0.4, 0.4, 0.4);
+        for x in 0..3 {
+            framebuffer.point_with_depth(x, 0, 0.5, gray);
+        }
+        // Replace overwrites the color and the depth
+        framebuffer.point_with_depth(0, 0, 0.2, Vector3::new(0.1, 0.1, 0.1));
+        assert!(framebuffer.depth_matches(0, 0, 0.2));
+
+        // Additive sums and clamps, without taking over the depth
+        framebuffer.set_blend_mode(BlendMode::Additive);
+        framebuffer.point(1, 0, Vector3::new(0.8, 0.8, 0.8));
+        framebuffer.point_with_depth(2, 0, 0.2, Vector3::new(0.2, 0.2, 0.2));
+        assert!(framebuffer.depth_matches(2, 0, 0.5));
+        // Behind the stored surface nothing is written
+        framebuffer.point_with_depth(2, 0, 0.9, Vector3::new(1.0, 1.0, 1.0));
+
+        let stored = framebuffer.snapshot();
+        assert_eq!([stored[0].r, stored[1].r, stored[2].r], [25, 255, 153]);
+
+        // A scoped mode is restored afterwards
+        framebuffer.with_blend_mode(BlendMode::Multiply, |framebuffer| assert_eq!(framebuffer.blend_mode(), BlendMode::Multiply));
+        assert_eq!(framebuffer.blend_mode(), BlendMode::Additive);
+    }
+
+    /// Pixels of `framebuffer` that aren't the black background, sorted by row then column
+    fn lit_pixels(framebuffer: &Framebuffer) -> Vec<(i32, i32)> {
+        let width = framebuffer.width as usize;
+        let mut lit: Vec<(i32, i32)> = framebuffer
+            .snapshot()
+            .iter()
+            .enumerate()
+            .filter(|(_, color)| (color.r, color.g, color.b) != (0, 0, 0))
+            .map(|(i, _)| ((i % width) as i32, (i / width) as i32))
+            .collect();
+        lit.sort_by_key(|&(x, y)| (y, x));
+        lit
+    }
+
+    fn line_pixels(width: u32, height: u32, start: (i32, i32), end: (i32, i32)) -> Vec<(i32, i32)> {
+        let mut framebuffer = Framebuffer::new(width, height);
+        framebuffer.set_stars_visible(false);
+        framebuffer.clear();
+        framebuffer.line(start.0, start.1, end.0, end.1, Vector3::new(1.0, 1.0, 1.0));
+        lit_pixels(&framebuffer)
+    }
+
+    #[test]
+    fn line_lights_the_bresenham_pixels() {
+        // Horizontal and vertical
+        assert_eq!(line_pixels(8, 8, (1, 3), (6, 3)), (1..=6).map(|x| (x, 3)).collect::<Vec<_>>());
+        assert_eq!(line_pixels(8, 8, (5, 1), (5, 6)), (1..=6).map(|y| (5, y)).collect::<Vec<_>>());
+        // Shallow: one pixel per column, stepping down a row halfway
+        assert_eq!(
+            line_pixels(8, 8, (0, 1), (6, 2)),
+            vec![(0, 1), (1, 1), (2, 1), (3, 2), (4, 2), (5, 2), (6, 2)]
+        );
+        // Steep: one pixel per row
+        assert_eq!(
+            line_pixels(8, 8, (2, 0), (3, 6)),
+            vec![(2, 0), (2, 1), (2, 2), (3, 3), (3, 4), (3, 5), (3, 6)]
+        );
+        // 45 degrees: both axes every pixel
+        assert_eq!(line_pixels(8, 8, (1, 6), (6, 1)), (1..=6).map(|y| (7 - y, y)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn line_lights_the_same_pixels_when_reversed() {
+        for (start, end) in [((0, 1), (6, 2)), ((2, 0), (3, 6)), ((1, 6), (6, 1)), ((7, 0), (0, 3))] {
+            assert_eq!(line_pixels(8, 8, start, end), line_pixels(8, 8, end, start), "{:?} -> {:?}", start, end);
+        }
+    }
+
+    #[test]
+    fn lines_off_screen_are_clipped_to_the_framebuffer() {
+        let framebuffer = Framebuffer::new(8, 8);
+
+        // Entirely outside: no pixels and no clipped segment
+        assert_eq!(framebuffer.clip_segment((-5, -5), (-1, 20)), None);
+        assert_eq!(framebuffer.clip_segment((9, 0), (30, 7)), None);
+        assert!(line_pixels(8, 8, (-5, -5), (-1, 20)).is_empty());
+        assert!(line_pixels(8, 8, (10, 10), (i32::MAX, i32::MAX)).is_empty());
+
+        // Partly outside: only the visible part, starting where it enters the framebuffer
+        assert_eq!(framebuffer.clip_segment((-10, 3), (20, 3)), Some(((0, 3), (7, 3))));
+        assert_eq!(line_pixels(8, 8, (-10, 3), (20, 3)), (0..8).map(|x| (x, 3)).collect::<Vec<_>>());
+
+        let (start, end) = ((-4, -2), (12, 6));
+        let (clipped_start, clipped_end) = framebuffer.clip_segment(start, end).unwrap();
+        assert_eq!((clipped_start, clipped_end), ((0, 0), (7, 4)));
+        let mut expected = bresenham(clipped_start, clipped_end);
+        expected.sort_by_key(|&(x, y)| (y, x));
+        assert_eq!(line_pixels(8, 8, start, end), expected);
+
+        // Ends far outside both axes at once
+        let lit = line_pixels(8, 8, (-1_000_000, -1_000_000), (1_000_000, 1_000_000));
+        assert_eq!(lit, (0..8).map(|i| (i, i)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn same_star_seed_gives_the_same_sky() {
+        let mut a = Framebuffer::new(64, 48);
+        let mut b = Framebuffer::new(64, 48);
+        a.set_star_seed(derive_seed(7, "stars"));
+        b.set_star_seed(derive_seed(7, "stars"));
+        assert_eq!(a.star_field, b.star_field);
+
+        b.set_star_seed(derive_seed(8, "stars"));
+        assert_ne!(a.star_field, b.star_field);
+    }
+
+    #[test]
+    fn depth_tested_soft_points_are_hidden_by_nearer_surfaces() {
+        let mut framebuffer = Framebuffer::new(8, 8);
+        framebuffer.set_stars_visible(false);
+        framebuffer.clear();
+        // A surface over the left half, in front of the splat
+        for y in 0..8 {
+            for x in 0..4 {
+                framebuffer.point_with_depth(x, y, 0.2, Vector3::zero());
+            }
+        }
+
+        framebuffer.soft_point_with_depth(4.0, 4.0, 0.5, 4.0, Vector3::new(1.0, 1.0, 1.0));
+        let lit = lit_pixels(&framebuffer);
+        assert!(!lit.is_empty());
+        assert!(lit.iter().all(|&(x, _)| x >= 4), "{:?}", lit);
+        // Additive: the stored depth is untouched
+        assert!(framebuffer.depth_matches(5, 4, f32::INFINITY));
+
+        // In front of the surface it shows on both halves
+        framebuffer.soft_point_with_depth(4.0, 4.0, 0.1, 4.0, Vector3::new(1.0, 1.0, 1.0));
+        assert!(lit_pixels(&framebuffer).iter().any(|&(x, _)| x < 4));
+    }
+
+    #[test]
+    fn depth_hides_only_boxes_covered_by_nearer_surfaces() {
+        let mut framebuffer = Framebuffer::new(8, 8);
+        framebuffer.set_stars_visible(false);
+        framebuffer.clear();
+        for y in 0..4 {
+            for x in 0..4 {
+                framebuffer.write_depth(x, y, 0.3);
+            }
+        }
+
+        assert!(framebuffer.depth_hides((0, 0, 3, 3), 0.5));
+        assert!(framebuffer.depth_hides((1, 1, 2, 3), 0.3));
+        // Something nearer than the stored surface shows
+        assert!(!framebuffer.depth_hides((0, 0, 3, 3), 0.2));
+        // One pixel of the box is still empty
+        assert!(!framebuffer.depth_hides((0, 0, 4, 3), 0.5));
+        // Off screen entirely: nothing is known to hide it
+        assert!(!framebuffer.depth_hides((10, 10, 12, 12), 0.5));
+    }
+
+    #[test]
+    fn depth_prepass_keeps_the_nearest_depth_and_leaves_the_color() {
+        let mut framebuffer = Framebuffer::new(4, 4);
+        framebuffer.set_stars_visible(false);
+        framebuffer.clear();
+        let background = framebuffer.image.get_color(1, 1);
+        for depth in [0.6, 0.2, 0.4] {
+            framebuffer.write_depth(1, 1, depth);
+        }
+        framebuffer.write_depth(9, 9, 0.1);
+
+        assert_eq!(framebuffer.image.get_color(1, 1), background);
+        assert!(framebuffer.depth_matches(1, 1, 0.2));
+        assert!(!framebuffer.depth_matches(1, 1, 0.4) && !framebuffer.depth_matches(1, 1, 0.6));
+        assert!(!framebuffer.depth_matches(9, 9, 0.1));
+    }
+
+    #[test]
+    fn depth_levels_span_the_stored_range_with_the_background_at_the_maximum() {
+        let levels = depth_levels(&[-0.5, f32::INFINITY, 0.5, 0.0]);
+        assert_eq!(levels, vec![0, u16::MAX, FAR_DEPTH_LEVEL, FAR_DEPTH_LEVEL / 2]);
+        // A single depth (or none) has no range to spread
+        assert_eq!(depth_levels(&[0.5, 0.5, f32::INFINITY]), vec![0, 0, u16::MAX]);
+    }
+
+    #[test]
+    fn saved_png_reloads_with_the_same_pixels() {
+        let mut framebuffer = Framebuffer::new(3, 2);
+        framebuffer.set_stars_visible(false);
+        framebuffer.clear();
+        framebuffer.point(0, 0, Vector3::new(1.0, 0.0, 0.0));
+        framebuffer.point(2, 1, Vector3::new(0.0, 0.0, 1.0));
+
+        let path = std::env::temp_dir().join(format!("lab5_color_{}.png", std::process::id()));
+        framebuffer.save_png(&path.to_string_lossy()).unwrap();
+        let png = image::open(&path).unwrap().into_rgba8();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(png.dimensions(), (3, 2));
+        let stored = framebuffer.snapshot();
+        for (i, pixel) in png.pixels().enumerate() {
+            let color = stored[i];
+            assert_eq!(pixel.0, [color.r, color.g, color.b, color.a], "pixel {}", i);
+        }
+        // Rows are not flipped: the top-left pixel is still the red one
+        assert_eq!(png.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(png.get_pixel(2, 1).0, [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn depth_png_is_16_bit_grayscale() {
+        let mut framebuffer = Framebuffer::new(3, 2);
+        framebuffer.write_depth(0, 0, -0.5);
+        framebuffer.write_depth(2, 1, 0.5);
+
+        let path = std::env::temp_dir().join(format!("lab5_depth_{}.png", std::process::id()));
+        framebuffer.save_depth_png(&path.to_string_lossy()).unwrap();
+        let png = image::open(&path).unwrap().into_luma16();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(png.dimensions(), (3, 2));
+        assert_eq!(png.get_pixel(0, 0).0[0], 0);
+        assert_eq!(png.get_pixel(2, 1).0[0], FAR_DEPTH_LEVEL);
+        assert_eq!(png.get_pixel(1, 0).0[0], u16::MAX);
+    }
+
+    #[test]
+    fn half_transparent_white_over_red_is_pink() {
+        let mut framebuffer = Framebuffer::new(8, 8);
+        framebuffer.set_stars_visible(false);
+        framebuffer.set_background_color(Vector3::new(1.0, 0.0, 0.0));
+        framebuffer.clear();
+
+        // 4x4 quad in the middle, depth tested against the cleared (infinitely far) buffer
+        framebuffer.set_blend_mode(BlendMode::AlphaBlend);
+        for y in 2..6 {
+            for x in 2..6 {
+                framebuffer.point_blended_with_depth(x, y, 0.5, Vector3::new(1.0, 1.0, 1.0), 0.5);
+            }
+        }
+
+        let stored = framebuffer.snapshot();
+        for y in 0..8 {
+            for x in 0..8 {
+                let color = stored[y * 8 + x];
+                let inside = (2..6).contains(&x) && (2..6).contains(&y);
+                let expected = if inside { (255, 127, 127) } else { (255, 0, 0) };
+                assert_eq!((color.r, color.g, color.b), expected, "pixel ({}, {})", x, y);
+            }
+        }
+    }
+}
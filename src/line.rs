@@ -2,44 +2,238 @@ use crate::fragment::Fragment;
 use crate::vertex::Vertex;
 use raylib::math::Vector3;
 
-#[allow(dead_code)]
-pub fn line(a: &Vertex, b: &Vertex) -> Vec<Fragment> {
-    let mut fragments = Vec::new();
+/// Rasterizes the segment from `a` to `b`, `thickness` pixels wide (independent of zoom).
+/// Lines up to 1 pixel use Bresenham. Thicker lines cover every pixel within half the thickness
+/// of the segment, with a round cap at the start and a flat cut at the end: consecutive segments
+/// of a polyline then join with a rounded corner and don't draw the shared end twice.
+/// The segment is first clipped to the viewport, so ends projected far off screen (e.g. just past
+/// the near plane) only cost the pixels that can show.
+pub fn line(a: &Vertex, b: &Vertex, thickness: f32, viewport: (u32, u32)) -> Vec<Fragment> {
+    let radius = if thickness <= 1.0 { 0.0 } else { thickness / 2.0 };
+    let Some((start, end)) = clip_to_viewport(a.transformed_position, b.transformed_position, radius, viewport) else {
+        return Vec::new();
+    };
+    if thickness <= 1.0 {
+        thin_line(start, end)
+    } else {
+        thick_line(start, end, radius, viewport)
+    }
+}
 
-    let start = a.transformed_position;
-    let end = b.transformed_position;
+/// Liang-Barsky clip of the segment from `start` to `end` to the box from `min` to `max`: the
+/// parameters (0 = start, 1 = end) where it enters and leaves the box, or `None` when it misses
+/// it or an end isn't finite
+pub fn clip_segment(start: (f64, f64), end: (f64, f64), min: (f64, f64), max: (f64, f64)) -> Option<(f64, f64)> {
+    if ![start.0, start.1, end.0, end.1].iter().all(|coordinate| coordinate.is_finite()) {
+        return None;
+    }
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
 
-    let mut x0 = start.x as i32;
-    let mut y0 = start.y as i32;
-    let x1 = end.x as i32;
-    let y1 = end.y as i32;
+    let (mut t_min, mut t_max) = (0.0_f64, 1.0_f64);
+    // Each edge as (-direction, distance from start to the edge)
+    for (p, q) in [(-dx, start.0 - min.0), (dx, max.0 - start.0), (-dy, start.1 - min.1), (dy, max.1 - start.1)] {
+        if p == 0.0 {
+            // Parallel to this edge: either entirely outside it or unaffected
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t_min = t_min.max(t);
+            } else {
+                t_max = t_max.min(t);
+            }
+        }
+    }
+    (t_min <= t_max).then_some((t_min, t_max))
+}
 
-    let dx = (x1 - x0).abs();
-    let dy = (y1 - y0).abs();
+/// The part of a screen-space segment within `margin` pixels of the viewport, with its depth
+/// interpolated at the new ends
+fn clip_to_viewport(start: Vector3, end: Vector3, margin: f32, viewport: (u32, u32)) -> Option<(Vector3, Vector3)> {
+    let margin = margin as f64;
+    let (t_min, t_max) = clip_segment(
+        (start.x as f64, start.y as f64),
+        (end.x as f64, end.y as f64),
+        (-margin, -margin),
+        (viewport.0 as f64 + margin, viewport.1 as f64 + margin),
+    )?;
+    let at = |t: f64| start + (end - start) * t as f32;
+    Some((at(t_min), at(t_max)))
+}
 
-    let sx = if x0 < x1 { 1 } else { -1 };
-    let sy = if y0 < y1 { 1 } else { -1 };
+/// Pixels of the segment from `start` to `end`, both ends included, in drawing order (Bresenham).
+/// Every 1-pixel line in the renderer walks these pixels.
+pub fn bresenham(start: (i32, i32), end: (i32, i32)) -> Vec<(i32, i32)> {
+    let (mut x, mut y) = start;
+    let (x1, y1) = end;
 
-    let mut err = if dx > dy { dx / 2 } else { -dy / 2 };
+    let dx = (x1 - x).abs();
+    let dy = -(y1 - y).abs();
+    let sx = if x < x1 { 1 } else { -1 };
+    let sy = if y < y1 { 1 } else { -1 };
+    let mut err = dx + dy;
 
+    let mut pixels = Vec::with_capacity(dx.max(-dy) as usize + 1);
     loop {
-        let z = start.z + (end.z - start.z) * (x0 - start.x as i32) as f32 / (end.x - start.x) as f32;
-        // For now, we'll just use white for the line color.
-        // A more advanced implementation would interpolate the vertex colors.
-        fragments.push(Fragment::new(x0 as f32, y0 as f32, Vector3::new(1.0, 1.0, 1.0), z));
-
-        if x0 == x1 && y0 == y1 { break; }
-
-        let e2 = err;
-        if e2 > -dx {
-            err -= dy;
-            x0 += sx;
+        pixels.push((x, y));
+        if x == x1 && y == y1 {
+            break;
         }
-        if e2 < dy {
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
             err += dx;
-            y0 += sy;
+            y += sy;
+        }
+    }
+
+    pixels
+}
+
+fn thin_line(start: Vector3, end: Vector3) -> Vec<Fragment> {
+    let pixels = bresenham((start.x as i32, start.y as i32), (end.x as i32, end.y as i32));
+
+    // Bresenham takes one step per pixel along the major axis, so the step count is the
+    // parameter for depth whatever the direction (vertical lines included)
+    let steps = (pixels.len() - 1).max(1) as f32;
+    pixels
+        .into_iter()
+        .enumerate()
+        .map(|(i, (x, y))| {
+            let z = start.z + (end.z - start.z) * i as f32 / steps;
+            Fragment::new(x as f32, y as f32, Vector3::new(1.0, 1.0, 1.0), z)
+        })
+        .collect()
+}
+
+/// Pixels of the viewport whose center lies within `radius` of the segment, except past its end
+/// (see `line`)
+fn thick_line(start: Vector3, end: Vector3, radius: f32, viewport: (u32, u32)) -> Vec<Fragment> {
+    let mut fragments = Vec::new();
+
+    let dx = end.x - start.x;
+    let dy = end.y - start.y;
+    let length_sq = dx * dx + dy * dy;
+
+    // Only pixels on screen are visited, whatever the segment's extent
+    let min_x = ((start.x.min(end.x) - radius).floor() as i32).max(0);
+    let max_x = ((start.x.max(end.x) + radius).ceil() as i32).min(viewport.0 as i32 - 1);
+    let min_y = ((start.y.min(end.y) - radius).floor() as i32).max(0);
+    let max_y = ((start.y.max(end.y) + radius).ceil() as i32).min(viewport.1 as i32 - 1);
+
+    for y in min_y..=max_y {
+        for x in min_x..=max_x {
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+
+            // Position along the segment of the closest point (0 = start, 1 = end)
+            let t = if length_sq > 0.0 { ((px - start.x) * dx + (py - start.y) * dy) / length_sq } else { 0.0 };
+            if t > 1.0 {
+                continue;
+            }
+            let t = t.max(0.0);
+
+            let (ox, oy) = (px - (start.x + dx * t), py - (start.y + dy * t));
+            if ox * ox + oy * oy <= radius * radius {
+                let z = start.z + (end.z - start.z) * t;
+                fragments.push(Fragment::new(x as f32, y as f32, Vector3::new(1.0, 1.0, 1.0), z));
+            }
         }
     }
 
     fragments
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_connected(pixels: &[(i32, i32)]) {
+        for pair in pixels.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            assert!((a.0 - b.0).abs() <= 1 && (a.1 - b.1).abs() <= 1 && a != b, "gap between {:?} and {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn horizontal_line_covers_one_row() {
+        let pixels = bresenham((2, 5), (9, 5));
+        assert_eq!(pixels.len(), 8);
+        assert!(pixels.iter().all(|&(_, y)| y == 5));
+        assert_eq!((pixels[0], pixels[7]), ((2, 5), (9, 5)));
+    }
+
+    #[test]
+    fn vertical_line_covers_one_column_with_finite_depth() {
+        let pixels = bresenham((4, 9), (4, 1));
+        assert_eq!(pixels.len(), 9);
+        assert!(pixels.iter().all(|&(x, _)| x == 4));
+        assert_eq!((pixels[0], pixels[8]), ((4, 9), (4, 1)));
+
+        let fragments = thin_line(Vector3::new(4.0, 1.0, 0.2), Vector3::new(4.0, 9.0, 0.6));
+        assert_eq!(fragments.len(), 9);
+        assert!(fragments.iter().all(|f| f.depth.is_finite()));
+        assert!((fragments[0].depth - 0.2).abs() < 1e-6 && (fragments[8].depth - 0.6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn diagonal_line_steps_both_axes_every_pixel() {
+        let pixels = bresenham((0, 0), (6, -6));
+        assert_eq!(pixels, (0..=6).map(|i| (i, -i)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn steep_line_has_one_pixel_per_row() {
+        let pixels = bresenham((3, 0), (5, 10));
+        assert_eq!(pixels.len(), 11);
+        assert_eq!((pixels[0], pixels[10]), ((3, 0), (5, 10)));
+        for (i, &(_, y)) in pixels.iter().enumerate() {
+            assert_eq!(y, i as i32);
+        }
+        assert_connected(&pixels);
+    }
+
+    #[test]
+    fn shallow_line_has_one_pixel_per_column() {
+        let pixels = bresenham((10, 2), (0, 5));
+        assert_eq!(pixels.len(), 11);
+        assert_eq!((pixels[0], pixels[10]), ((10, 2), (0, 5)));
+        assert_connected(&pixels);
+    }
+
+    #[test]
+    fn lines_far_off_screen_only_cover_viewport_pixels() {
+        let viewport = (64, 48);
+        let point = |x: f32, y: f32, z: f32| Vertex::new_with_color(Vector3::new(x, y, z), Vector3::zero());
+        // Crosses the screen between ends that projected millions of pixels away
+        let (a, b) = (point(-4.0e6, 24.0, 0.0), point(4.0e6, 24.0, 1.0));
+
+        for thickness in [1.0, 5.0] {
+            let fragments = line(&a, &b, thickness, viewport);
+            assert!(!fragments.is_empty());
+            assert!(fragments.len() <= 6 * 64, "thickness {}: {} fragments", thickness, fragments.len());
+            for fragment in &fragments {
+                let (x, y) = (fragment.position.x, fragment.position.y);
+                assert!((-1.0..=64.0).contains(&x) && (19.0..=29.0).contains(&y), "({}, {})", x, y);
+                // The depth of the clipped ends is interpolated from the original ones
+                assert!((fragment.depth - 0.5).abs() < 1e-3, "depth {}", fragment.depth);
+            }
+        }
+
+        // Entirely outside the viewport (or not finite): nothing to draw
+        assert!(line(&point(-100.0, -5.0, 0.0), &point(200.0, -5.0, 0.0), 5.0, viewport).is_empty());
+        assert!(line(&point(f32::INFINITY, 10.0, 0.0), &point(10.0, 10.0, 0.0), 1.0, viewport).is_empty());
+    }
+
+    #[test]
+    fn single_pixel_line() {
+        assert_eq!(bresenham((7, 7), (7, 7)), vec![(7, 7)]);
+        let fragments = thin_line(Vector3::new(7.0, 7.0, 0.5), Vector3::new(7.0, 7.0, 0.5));
+        assert_eq!(fragments.len(), 1);
+        assert!(fragments[0].depth.is_finite());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::sphere::generate_uv_sphere;
+use crate::vertex::Vertex;
+use std::sync::LazyLock;
+
+/// Stacks and slices of the low-detail mesh (224 triangles)
+const LOW_DETAIL_STACKS: usize = 8;
+const LOW_DETAIL_SLICES: usize = 16;
+/// A body back at full detail must grow this much past the switch radius: without the margin, a
+/// body sitting right at the threshold would fade back and forth every few frames
+const FULL_DETAIL_MARGIN: f32 = 1.2;
+/// 4x4 ordered-dither matrix; `(value + 0.5) / 16` is the pixel's screen-door threshold
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Unit sphere drawn in place of the models of bodies too small on screen to show their detail
+pub static LOW_DETAIL_MESH: LazyLock<(Vec<Vertex>, Vec<u32>)> =
+    LazyLock::new(|| generate_uv_sphere(1.0, LOW_DETAIL_STACKS, LOW_DETAIL_SLICES));
+
+/// Mesh a body is drawn with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lod {
+    Full, // The body's own model
+    Low,  // `LOW_DETAIL_MESH`
+}
+
+/// Level of detail of one body and the cross-fade from the level it had before, so switching
+/// meshes blends over a moment instead of popping
+#[derive(Clone, Copy, Debug)]
+pub struct LodFade {
+    pub current: Lod,
+    progress: f32, // Fraction of the fade into `current` done; 1 = only `current` is drawn
+}
+
+impl Default for LodFade {
+    fn default() -> Self {
+        LodFade { current: Lod::Full, progress: 1.0 }
+    }
+}
+
+impl LodFade {
+    /// Picks the level for a body `screen_radius` pixels in radius (low detail below
+    /// `switch_radius`; 0 keeps full detail) and advances the fade by `dt` seconds out of
+    /// `duration`. Switching back during a fade picks it up from the blend on screen.
+    pub fn update(&mut self, screen_radius: f32, switch_radius: f32, dt: f32, duration: f32) {
+        let target = match self.current {
+            Lod::Full if screen_radius < switch_radius => Lod::Low,
+            Lod::Low if screen_radius > switch_radius * FULL_DETAIL_MARGIN => Lod::Full,
+            current => current,
+        };
+        if target != self.current {
+            self.current = target;
+            self.progress = 1.0 - self.progress;
+        }
+        self.progress = if duration > 0.0 { (self.progress + dt / duration).min(1.0) } else { 1.0 };
+    }
+
+    /// Meshes to draw, each with the screen-door range of dither thresholds it keeps (see
+    /// `screen_door_keeps`); None keeps every pixel. While fading, the model keeps the lowest
+    /// thresholds up to its weight and the sphere the rest, so every pixel shows exactly one of
+    /// them, both draw opaque with depth writes, and the share of the model grows or shrinks
+    /// with the fade.
+    pub fn layers(&self) -> Vec<(Lod, Option<(f32, f32)>)> {
+        if self.progress >= 1.0 {
+            return vec![(self.current, None)];
+        }
+        let full_weight = match self.current {
+            Lod::Full => self.progress,
+            Lod::Low => 1.0 - self.progress,
+        };
+        vec![(Lod::Low, Some((full_weight, 1.0))), (Lod::Full, Some((0.0, full_weight)))]
+    }
+}
+
+/// Screen-door transparency: whether the pixel's ordered-dither threshold falls in
+/// `[from, to)`. Complementary ranges split every 4x4 tile of pixels between two draws.
+pub fn screen_door_keeps(x: i32, y: i32, (from, to): (f32, f32)) -> bool {
+    let threshold = (BAYER_4X4[(y & 3) as usize][(x & 3) as usize] as f32 + 0.5) / 16.0;
+    from <= threshold && threshold < to
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_bodies_switch_to_low_detail_and_back_past_a_margin() {
+        let mut lod = LodFade::default();
+        lod.update(20.0, 12.0, 0.0, 0.0);
+        assert_eq!(lod.current, Lod::Full);
+        lod.update(10.0, 12.0, 0.0, 0.0);
+        assert_eq!(lod.current, Lod::Low);
+        // Just over the switch radius is not enough to come back
+        lod.update(13.0, 12.0, 0.0, 0.0);
+        assert_eq!(lod.current, Lod::Low);
+        lod.update(15.0, 12.0, 0.0, 0.0);
+        assert_eq!(lod.current, Lod::Full);
+        // A switch radius of 0 never leaves full detail
+        let mut off = LodFade::default();
+        off.update(0.5, 0.0, 0.1, 0.3);
+        assert_eq!(off.layers(), vec![(Lod::Full, None)]);
+    }
+
+    #[test]
+    fn the_fade_advances_with_delta_time() {
+        let mut lod = LodFade::default();
+        lod.update(5.0, 12.0, 0.0, 0.5);
+        assert_eq!(lod.layers(), vec![(Lod::Low, Some((1.0, 1.0))), (Lod::Full, Some((0.0, 1.0)))]);
+        lod.update(5.0, 12.0, 0.125, 0.5);
+        assert_eq!(lod.layers(), vec![(Lod::Low, Some((0.75, 1.0))), (Lod::Full, Some((0.0, 0.75)))]);
+        lod.update(5.0, 12.0, 0.25, 0.5);
+        assert_eq!(lod.layers(), vec![(Lod::Low, Some((0.25, 1.0))), (Lod::Full, Some((0.0, 0.25)))]);
+        lod.update(5.0, 12.0, 0.25, 0.5);
+        assert_eq!(lod.layers(), vec![(Lod::Low, None)]);
+    }
+
+    #[test]
+    fn switching_back_mid_fade_continues_from_the_blend_on_screen() {
+        let mut lod = LodFade::default();
+        lod.update(5.0, 12.0, 0.0, 1.0);
+        lod.update(5.0, 12.0, 0.25, 1.0);
+        assert_eq!(lod.layers(), vec![(Lod::Low, Some((0.75, 1.0))), (Lod::Full, Some((0.0, 0.75)))]);
+        // Zooming back in fades the model in from where it was
+        lod.update(30.0, 12.0, 0.0, 1.0);
+        assert_eq!(lod.current, Lod::Full);
+        assert_eq!(lod.layers(), vec![(Lod::Low, Some((0.75, 1.0))), (Lod::Full, Some((0.0, 0.75)))]);
+        lod.update(30.0, 12.0, 0.25, 1.0);
+        assert_eq!(lod.layers(), vec![(Lod::Full, None)]);
+    }
+
+    #[test]
+    fn complementary_screen_doors_cover_every_pixel_once() {
+        for weight in [0.0, 0.25, 0.5, 0.8, 1.0] {
+            let mut model_pixels = 0;
+            for y in 0..4 {
+                for x in 0..4 {
+                    let model = screen_door_keeps(x, y, (0.0, weight));
+                    let sphere = screen_door_keeps(x, y, (weight, 1.0));
+                    assert!(model != sphere, "pixel ({}, {}) at weight {}", x, y, weight);
+                    model_pixels += model as u32;
+                }
+            }
+            assert_eq!(model_pixels, (weight * 16.0).round() as u32);
+        }
+    }
+}
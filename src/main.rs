@@ -11,35 +11,686 @@ mod matrix;
 mod rings;
 mod moons;
 mod texture;
+mod config;
+mod subdivision;
+mod scene_graph;
+mod postprocess;
+mod frame_stats;
+mod timeline;
+mod tangents;
+mod color_ramp;
+mod sphere;
+mod scrubber;
+mod lod;
+mod orbit;
+mod peeling;
+mod clip;
+mod camera;
 
-use crate::matrix::new_matrix4;
-use crate::shaders::get_planet_color;
-use crate::texture::Texture;
-use framebuffer::Framebuffer;
+use crate::config::{derive_seed, BatchRender, CinematicCamera, CompanionStar, Config, ShaderGallery, DEFAULT_FAR_PLANE, DEFAULT_NEAR_PLANE};
+use crate::matrix::{axis_angle_matrix, new_matrix4, normal_matrix, rotation_between, transform_direction, transform_point};
+use crate::shaders::{AccretionDisk, AnimationSpeeds, RingShadow, SurfaceMaterial, DEFAULT_LIGHT_DIR, DEFAULT_STAR_LIGHT, DEFAULT_VIEW_DIR, apply_point_lights, gas_giant_lightning_flash, get_planet_color, light_angles, light_direction, log_depth, perturb_normal, representative_color, ring_shadow_factor, shade_unlit, shader_registry, FragmentShader, PlanetType, PointLight, ShaderParams, StarLight};
+use framebuffer::{draw_render_texture, BlendMode, Framebuffer};
 use vertex::Vertex;
-use triangle::triangle;
+use fragment::Fragment;
+use triangle::{pixel_bounds, triangle};
+use orbit::{axis_ends, ellipse_point, empty_focus, incline, kepler_position, orbital_plane};
+use peeling::peel_layers;
+use lod::{screen_door_keeps, Lod, LodFade, LOW_DETAIL_MESH};
+use clip::{clip_near, clip_segment_near};
+use camera::Camera;
+use line::line;
 use shaders::vertex_shader;
-use obj::Obj;
+use obj::{Material, Obj, ObjLoadOptions};
+use texture::Texture;
+use scene_graph::SceneGraph;
+use postprocess::PostProcessChain;
+use frame_stats::{FrameTimeHistory, ProfileCsv, RenderStats, StageTimings};
+use timeline::Timeline;
+use moons::Moon;
+use scrubber::TimeScrubber;
 use raylib::prelude::*;
-use std::thread;
-use std::time::Duration;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::LazyLock;
+use std::time::Instant;
 use std::f32::consts::PI;
 
-pub struct Uniforms {
+#[derive(Clone)]
+pub struct Uniforms<'a> {
     pub model_matrix: Matrix,
+    pub normal_transform: Matrix,  // Carries normals through model_matrix (matrix::normal_matrix), once per draw
+    pub view_matrix: Matrix,       // World to camera space (see matrix::look_at)
+    pub projection_matrix: Matrix, // Camera to clip space; the vertex shader divides by w afterwards
+    pub viewport: (u32, u32),      // Framebuffer size in pixels; NDC is mapped onto it
     pub time: f32,
-    pub planet_type: u32,  // 0: Sun, 1: Earth-like, 2: Gas Giant
+    pub planet_type: PlanetType, // Shader used for the body's surface
+    pub point_lights: Vec<PointLight>, // Transient lights (e.g. lightning flashes), in world space
+    pub blend: Option<(BlendMode, f32)>, // Blend mode set on the framebuffer while drawing, and opacity; None replaces (opaque)
+    pub backface_tint: Option<Vector3>, // Debug: tint back-facing fragments with this color
+    pub animation: AnimationSpeeds, // Speed of the shader's cloud/storm animation
+    pub star_lights: Vec<StarLight>, // Every star's light on the body: direction towards it (world space) and color
+    pub view_dir: Vector3,  // Direction towards the camera, for specular highlights (world space)
+    pub clip_planes: (f32, f32), // Depth range kept (NDC after the projection); fragments outside are discarded
+    pub log_depth_far: Option<f32>, // Far plane distance when depth is logarithmic (shaders::log_depth); None keeps the projection's z
+    pub ambient: Vector3, // Minimum illumination added to every lit fragment
+    pub normal_map: Option<(&'a Texture, &'a Material, f32)>, // Tangent-space normal map, the material it's sampled through and its strength
+    pub emissive_map: Option<(&'a Texture, &'a Material)>, // Glow added after lighting (MTL `map_Ke`) and the material it's sampled through
+    pub disk: AccretionDisk, // Colors and swirl of the accretion-disk shader
+    pub material: SurfaceMaterial, // Diffuse/specular weights and metallic highlight tint
+    pub ring_shadow: Option<RingShadow>, // Shadow band cast on the body by its own rings
+    pub screen_door: Option<(f32, f32)>, // Dither thresholds of the pixels kept (lod::screen_door_keeps); None keeps all
+    pub cull_backfaces: bool, // Skip triangles facing away from the camera (closed meshes only)
 }
 
 struct CelestialBody {
-    #[allow(dead_code)]
     name: String,
-    planet_type: u32,
+    planet_type: PlanetType,
     scale: f32,
-    orbit_radius: f32,
-    orbit_speed: f32,
-    rotation_speed: f32,
+    orbit_radius: f32,   // Semi-major axis of the orbit
+    orbit_speed: f32,    // Mean motion: average radians per time unit along the orbit
+    eccentricity: f32,   // 0 = circular orbit; closer to 1 = more elongated (Kepler's laws)
+    inclination: f32,    // Tilt of the orbital plane from the XY plane (radians)
+    ascending_node: f32, // Direction in the XY plane (radians from +X) of the axis the orbit tilts about
+    rotation_speed: f32, // Radians per time unit around spin_axis; negative spins retrograde
+    spin_axis: Vector3,  // Axis the surface spins around, in the body's frame (+Y by default)
+    orbit_phase: f32,    // Starting angle on the orbit (radians)
+    star: bool,          // Stars orbit the system center; everything else orbits their barycenter
+    light_color: Vector3, // Color of the light a star casts on the other bodies (black for the rest)
+    moons: Vec<Moon>,    // Satellites orbiting the body, drawn with the moon model
     model_path: String, // Path to OBJ file
+    subdivision: u32,   // Midpoint subdivision levels applied to the model at load (0 = as-is)
+    visible: bool,            // Hidden bodies skip the whole pipeline
+    satellites_visible: bool, // Render this body's moon/rings
+    animation: AnimationSpeeds, // Cloud drift / storm swirl rates, independent of rotation_speed
+    material: SurfaceMaterial,  // How the surface takes the light (see SurfaceMaterial)
+    lod: LodFade,               // Model or low-detail sphere, by size on screen, and the fade between them
+}
+
+/// Orbital geometry view (SHIFT+0): each orbit ellipse with its foci marked
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum OrbitGeometryMode {
+    Off,
+    Foci, // The ellipse and both foci
+    Axes, // Also the major and minor axes
+}
+
+impl OrbitGeometryMode {
+    /// Next mode in Off -> Foci -> Axes order, for cycling from the keyboard
+    fn next(self) -> Self {
+        match self {
+            OrbitGeometryMode::Off => OrbitGeometryMode::Foci,
+            OrbitGeometryMode::Foci => OrbitGeometryMode::Axes,
+            OrbitGeometryMode::Axes => OrbitGeometryMode::Off,
+        }
+    }
+}
+
+/// Debug view that draws the vertex shader output as dots instead of rasterizing triangles (F11)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PointCloudMode {
+    Off,
+    All,      // Every body and satellite
+    Selected, // Only the selected body (and its satellites); the rest render normally
+}
+
+impl PointCloudMode {
+    /// Next mode in Off -> All -> Selected order, for cycling from the keyboard
+    fn next(self) -> Self {
+        match self {
+            PointCloudMode::Off => PointCloudMode::All,
+            PointCloudMode::All => PointCloudMode::Selected,
+            PointCloudMode::Selected => PointCloudMode::Off,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            PointCloudMode::Off => "OFF",
+            PointCloudMode::All => "Todos",
+            PointCloudMode::Selected => "Seleccionado",
+        }
+    }
+
+    /// Whether the body at `index` is drawn as points
+    fn applies_to(self, index: usize, selected: usize) -> bool {
+        match self {
+            PointCloudMode::Off => false,
+            PointCloudMode::All => true,
+            PointCloudMode::Selected => index == selected,
+        }
+    }
+}
+
+/// Interactive state driven by the keyboard.
+struct AppState {
+    camera_target: Vector3,   // World point the camera looks at and orbits; the arrows pan it
+    camera_zoom: f32,         // Dolly: the camera distance shrinks as the zoom grows
+    camera_rotation: Vector3, // Orbit of the camera around the target: X, then Y, then Z angles
+    auto_rotate: bool,
+    auto_orbit: bool,
+    orbit_speed_mult: f32,    // Global factor applied to every body's orbit_speed
+    rotation_speed_mult: f32, // Global factor applied to every body's rotation_speed
+    selected_body: usize,
+    isolate_body: bool,      // Render only the selected body, centered and framed
+    isolate_satellites: bool, // Keep the moon/rings of the isolated body
+    show_axes: bool,          // Draw the X/Y/Z gizmo at the system center
+    show_grid: bool,          // Reference grid on the orbital plane (F8)
+    show_orbits: bool,        // Faint line along each body's orbit, under the bodies
+    orbit_geometry: OrbitGeometryMode, // Orbit ellipses with their foci (and axes)
+    show_velocities: bool,    // Arrow at each body along its orbital velocity, length proportional to speed
+    point_cloud: PointCloudMode, // Draw transformed vertices as dots instead of triangles
+    lightning_light: bool,    // Gas-giant lightning briefly lights nearby geometry
+    sun_halo: bool,           // Soft additive glow drawn behind the sun
+    sun_halo_color: Vector3,
+    sun_halo_size: f32,       // Halo radius in multiples of the sun's screen radius
+    show_legend: bool,        // Panel mapping each active shader to a color swatch
+    ring_blend: BlendMode,    // How ring fragments combine with what is behind them
+    shake_intensity: f32,     // Peak camera shake offset in pixels
+    shake_duration: f32,      // Length of the current shake in seconds
+    shake_remaining: f32,     // Seconds left in the current shake (0 = still)
+    map_view: bool,           // Show the selected body's shader as a flat equirectangular map
+    show_frame_times: bool,   // Overlay graph and min/max/avg of recent frame times
+    show_stage_timings: bool, // Overlay with the ms spent in each pipeline stage
+    show_backfaces: bool,     // Tint back-facing fragments to check winding
+    ambient_level: f32,       // Ambient light floor (gray) so the night side keeps visible detail
+    normal_mapping: bool,     // Perturb normals with the model's normal map, when it has one
+    normal_map_strength: f32, // 0 = flat, 1 = map as authored
+    light_override: bool,     // Light bodies from light_azimuth/light_elevation instead of the default
+    light_azimuth: f32,       // Radians around Y, from +Z towards +X
+    light_elevation: f32,     // Radians above the XZ plane
+    near_plane: f32,          // Distance from the camera to the near clip plane
+    far_plane: f32,           // Distance from the camera to the far clip plane
+    log_depth: bool,          // Depth test on logarithmic depth instead of the projection's z
+    stereo: bool,             // Side-by-side stereo: left eye in the left half, right eye in the right
+    stereo_separation: f32,   // Angle (radians) the two eyes orbit apart around the target
+    stereo_convergence: f32,  // Pixels the eye images are pushed apart (moves the zero-parallax depth)
+    cinematic: bool,          // Cinematic camera circles the system on its own
+    cinematic_phase: f32,     // Seconds the cinematic camera has been moving (drives the bob)
+    cinematic_idle: f32,      // Seconds since the last manual camera input
+    markers: Vec<Vector3>,    // Probe markers, in world space (offsets from the system center)
+    time_scale: f32,          // Simulation time advanced per second of real time (negative runs backwards)
+    time_paused: bool,        // Time stands still; time_scale is kept for when it resumes
+}
+
+impl AppState {
+    fn new() -> Self {
+        // The manual light starts where the default light is
+        let (default_azimuth, default_elevation) = light_angles(DEFAULT_LIGHT_DIR);
+        AppState {
+            camera_target: Vector3::new(0.0, 0.0, 0.0),
+            camera_zoom: DEFAULT_CAMERA_ZOOM,
+            camera_rotation: Vector3::new(0.0, 0.0, 0.0),
+            auto_rotate: true,
+            auto_orbit: true,
+            orbit_speed_mult: 1.0,
+            rotation_speed_mult: 1.0,
+            selected_body: 0,
+            isolate_body: false,
+            isolate_satellites: true,
+            show_axes: false,
+            show_grid: false,
+            show_orbits: false,
+            orbit_geometry: OrbitGeometryMode::Off,
+            show_velocities: false,
+            point_cloud: PointCloudMode::Off,
+            lightning_light: true,
+            sun_halo: true,
+            sun_halo_color: Vector3::new(1.0, 0.75, 0.3),
+            sun_halo_size: 2.5,
+            show_legend: false,
+            ring_blend: BlendMode::AlphaBlend,
+            shake_intensity: 0.0,
+            shake_duration: 0.0,
+            shake_remaining: 0.0,
+            map_view: false,
+            show_frame_times: false,
+            show_stage_timings: false,
+            show_backfaces: false,
+            ambient_level: DEFAULT_AMBIENT_LEVEL,
+            normal_mapping: true,
+            normal_map_strength: DEFAULT_NORMAL_MAP_STRENGTH,
+            light_override: false,
+            light_azimuth: default_azimuth,
+            light_elevation: default_elevation,
+            near_plane: DEFAULT_NEAR_PLANE,
+            far_plane: DEFAULT_FAR_PLANE,
+            log_depth: false,
+            stereo: false,
+            stereo_separation: DEFAULT_STEREO_SEPARATION,
+            stereo_convergence: 0.0,
+            cinematic: false,
+            cinematic_phase: 0.0,
+            cinematic_idle: CINEMATIC_RESUME_DELAY,
+            markers: Vec::new(),
+            time_scale: 1.0,
+            time_paused: false,
+        }
+    }
+
+    /// Starts a camera shake; a stronger shake already in progress is not cut short
+    fn trigger_shake(&mut self, intensity: f32, duration: f32) {
+        if duration <= 0.0 || self.shake_intensity * self.shake_envelope() > intensity {
+            return;
+        }
+        self.shake_intensity = intensity;
+        self.shake_duration = duration;
+        self.shake_remaining = duration;
+    }
+
+    /// Remaining fraction of the shake, eased so it decays smoothly to zero
+    fn shake_envelope(&self) -> f32 {
+        if self.shake_duration <= 0.0 {
+            return 0.0;
+        }
+        let t = (self.shake_remaining / self.shake_duration).clamp(0.0, 1.0);
+        t * t
+    }
+
+    /// Screen offset (pixels) for this frame: smooth pseudo-random noise scaled by the decaying envelope
+    fn shake_offset(&self, time: f32) -> Vector2 {
+        let amplitude = self.shake_intensity * self.shake_envelope();
+        if amplitude <= 0.0 {
+            return Vector2::zero();
+        }
+        // Incommensurate frequencies so the motion doesn't look periodic
+        let x = (time * 47.0).sin() * 0.6 + (time * 83.0 + 1.3).sin() * 0.4;
+        let y = (time * 53.0 + 2.1).sin() * 0.6 + (time * 97.0 + 0.7).sin() * 0.4;
+        Vector2::new(x, y) * amplitude
+    }
+
+    /// Simulation time per second of real time this frame: 0 while paused
+    fn current_time_scale(&self) -> f32 {
+        if self.time_paused { 0.0 } else { self.time_scale }
+    }
+
+    fn update_shake(&mut self, dt: f32) {
+        self.shake_remaining = (self.shake_remaining - dt).max(0.0);
+    }
+
+    /// Advances the cinematic camera: circles the target, bobs its tilt and eases the zoom.
+    /// Moves are applied as increments, so it picks up from wherever the manual camera left it.
+    fn update_cinematic(&mut self, dt: f32, camera: &CinematicCamera) {
+        if !self.cinematic {
+            return;
+        }
+        self.cinematic_idle += dt;
+        if self.cinematic_idle < CINEMATIC_RESUME_DELAY {
+            return; // Suspended by manual input
+        }
+
+        self.camera_rotation.y += camera.speed * dt;
+
+        let previous_bob = (self.cinematic_phase * CINEMATIC_BOB_RATE).sin();
+        self.cinematic_phase += dt;
+        let bob = (self.cinematic_phase * CINEMATIC_BOB_RATE).sin();
+        self.camera_rotation.x += camera.bob * (bob - previous_bob);
+
+        self.camera_zoom += (camera.zoom - self.camera_zoom) * (dt * CINEMATIC_ZOOM_EASE).min(1.0);
+    }
+
+    /// Camera values the user moves by hand; any change suspends the cinematic camera
+    fn manual_camera(&self) -> (Vector3, Vector3, f32) {
+        (self.camera_rotation, self.camera_target, self.camera_zoom)
+    }
+
+    /// Distance from the camera to its target: at the default zoom one world unit at the target
+    /// spans one pixel
+    fn camera_distance(&self, viewport: (u32, u32)) -> f32 {
+        Camera::pixel_distance(viewport) * DEFAULT_CAMERA_ZOOM / self.camera_zoom
+    }
+
+    /// The manual camera, without shake or stereo
+    fn camera(&self, viewport: (u32, u32)) -> Camera {
+        Camera::orbit(
+            self.camera_target,
+            self.camera_rotation,
+            self.camera_distance(viewport),
+            Vector2::zero(),
+            viewport,
+            self.near_plane,
+            self.far_plane,
+        )
+    }
+}
+
+// Cinematic camera (F7): seconds of no manual input before it resumes, bob frequency
+// (radians per second) and how fast the zoom settles
+const CINEMATIC_RESUME_DELAY: f32 = 3.0;
+const CINEMATIC_BOB_RATE: f32 = 0.4;
+const CINEMATIC_ZOOM_EASE: f32 = 0.5;
+
+// Probe markers (Insert drops one, Delete clears them): how many are kept, cross size in pixels
+const MAX_MARKERS: usize = 4;
+const MARKER_SIZE: i32 = 6;
+const MARKER_COLOR: Vector3 = Vector3 { x: 0.2, y: 1.0, z: 1.0 };
+
+// Timeline scrubber bar along the bottom edge of the window: margin to the sides/bottom and thickness
+const SCRUBBER_MARGIN: i32 = 10;
+const SCRUBBER_HEIGHT: i32 = 8;
+
+// HUD text: font size, spacing between lines and margin to the window edges
+const HUD_FONT_SIZE: i32 = 14;
+const HUD_LINE_HEIGHT: i32 = 20;
+const HUD_MARGIN: i32 = 10;
+/// Key help at the bottom of the HUD, wrapped to the window width
+const CONTROLS_HELP: [&str; 6] = [
+    "SPACE: Pausar/Reanudar rotacion | O: Pausar/Reanudar orbita",
+    "Flechas: Mover camara | S/A: Zoom | Q/W: Rot X | E/R: Rot Y | T/Y: Rot Z",
+    "TAB: Seleccionar | 1-9: Ocultar | P: Shader | I: Aislar | U: Satelites",
+    "G: Ejes | L: Rayos | H: Halo | B: Mezcla anillos | K: Sacudir | X: Caras traseras | F8: Rejilla | F10: Velocidades | F11: Puntos | 0: Órbitas | SHIFT+0: Focos | Num * /: Excentricidad",
+    "+/-: Tiempo | Retroceso: Reversa | Enter: Pausa | ,/.: Vel. rotacion | [/]: Vel. orbita | ;/': Ambiente | N/J: Relieve | Z D/F C/V: Luz",
+    "F1: Leyenda | F2: Estereo | F3: Tiempos | F4: Etapas | F5: Guardar | F6: OBJ | F7: Cinematica | Ins/Del: Marcas | M: Mapa UV | CTRL+=/-, F9: Material | `: Prof. log",
+];
+
+// Frame-time overlay: number of frames kept and the frame time at the top of the graph
+const FRAME_TIME_SAMPLES: usize = 120;
+const FRAME_TIME_GRAPH_MAX: f32 = 0.050;
+
+// Manual camera shake (K)
+const TEST_SHAKE_INTENSITY: f32 = 12.0;
+const TEST_SHAKE_DURATION: f32 = 0.6;
+
+const DEFAULT_CAMERA_ZOOM: f32 = 0.6; // Start more zoomed out to see all planets
+// Pixels the arrow keys pan the view per frame held
+const CAMERA_PAN_STEP: f32 = 10.0;
+// World radius of a body (and of its moon and rings) per unit of `scale`
+const BODY_SIZE: f32 = 0.6;
+
+// Companion stars (star_<name> / --binary-star): distance from each to the sun and orbit rate
+const BINARY_SEPARATION: f32 = 80.0;
+const BINARY_ORBIT_SPEED: f32 = 0.3;
+
+// Default spin axis of the bodies; Uranus spins on its side, tilted ~98° towards +X, and the
+// gas giant leans 0.2 rad so its rings are seen slanted
+const SPIN_AXIS_UP: Vector3 = Vector3 { x: 0.0, y: 1.0, z: 0.0 };
+const URANUS_SPIN_AXIS: Vector3 = Vector3 { x: 0.990, y: -0.139, z: 0.0 };
+const GAS_GIANT_SPIN_AXIS: Vector3 = Vector3 { x: 0.0, y: 0.980, z: 0.199 };
+
+// Ice giants are glossy dielectrics: white highlights, stronger than the default
+const ICE_GIANT_MATERIAL: SurfaceMaterial = SurfaceMaterial { diffuse: 0.65, specular: 0.5, metallic: false };
+
+// Live material tweaks on the selected body (CTRL+= / CTRL+- specular, SHIFT for diffuse, F9 metallic)
+const MATERIAL_STEP: f32 = 0.05;
+const MATERIAL_MAX: f32 = 2.0;
+
+// Panel layout (--panel): the render is shown scaled inside this rectangle of the window
+const PANEL_RECT: Rectangle = Rectangle { x: 220.0, y: 60.0, width: 560.0, height: 420.0 };
+
+// Ambient light floor, adjusted with ; and '
+const DEFAULT_AMBIENT_LEVEL: f32 = 0.2;
+const AMBIENT_STEP: f32 = 0.05;
+
+// Normal map strength, adjusted with J (SHIFT+J lowers it)
+const DEFAULT_NORMAL_MAP_STRENGTH: f32 = 1.0;
+const NORMAL_MAP_STRENGTH_STEP: f32 = 0.25;
+const NORMAL_MAP_STRENGTH_MAX: f32 = 2.0;
+
+// Near/far planes (Home/End move near, PageUp/PageDown move far), world units per frame held.
+// The near plane can't reach the eye, where the perspective divide breaks down
+const CLIP_PLANE_STEP: f32 = 5.0;
+const MIN_CLIP_RANGE: f32 = 1.0;
+const MIN_NEAR_PLANE: f32 = 1.0;
+
+// Side-by-side stereo (F2): \ changes the eye separation, / the convergence (SHIFT lowers)
+const DEFAULT_STEREO_SEPARATION: f32 = 0.06;
+const STEREO_SEPARATION_STEP: f32 = 0.01;
+const STEREO_SEPARATION_MAX: f32 = 0.3;
+const STEREO_CONVERGENCE_STEP: f32 = 4.0;
+
+// Manual light direction (Z toggles, D/F azimuth, C/V elevation), radians per frame held
+const LIGHT_ANGLE_SPEED: f32 = PI / 90.0;
+const LIGHT_ELEVATION_LIMIT: f32 = PI / 2.0;
+
+// Each key press multiplies/divides a speed multiplier by this step
+const SPEED_MULT_STEP: f32 = 1.25;
+const SPEED_MULT_MIN: f32 = 0.1;
+const SPEED_MULT_MAX: f32 = 10.0;
+
+// Simulation time scale (keypad +/-): each press multiplies/divides it by SPEED_MULT_STEP
+// within these magnitudes; Backspace reverses it and Enter pauses it
+const TIME_SCALE_MIN: f32 = 0.1;
+const TIME_SCALE_MAX: f32 = 20.0;
+
+// Longest frame the simulation advances by at once (a stall, or a dragged window, doesn't jump ahead)
+const MAX_FRAME_DELTA: f32 = 0.1;
+
+// Satellite layout (shared by the render loop and the isolation framing)
+const MOON_DISTANCE: f32 = 50.0;
+const MOON_SCALE: f32 = 8.0;
+const MOON_MODEL_PATH: &str = "assets/models/10464_Asteroid_v1_Iterations-2.obj";
+const MOON_SUBDIVISION: u32 = 1; // The asteroid used as the moon is low-poly
+const MOON_MATERIAL: SurfaceMaterial = SurfaceMaterial { diffuse: 0.9, specular: 0.03, metallic: false }; // Rough, matte rock
+const RING_SCALE: f32 = 1.8;
+const RING_ALPHA: f32 = 0.75;
+const BACKFACE_TINT_AMOUNT: f32 = 0.6;
+const RING_INNER_RADIUS: f32 = 1.0;
+const RING_OUTER_RADIUS: f32 = 1.5;
+const RING_SEGMENTS: usize = 128;
+/// The gas giant's ring mesh, with its gaps cut out; built once, as it never changes
+static GAS_GIANT_RING: LazyLock<Vec<Vertex>> =
+    LazyLock::new(|| rings::generate_flat_ring(RING_INNER_RADIUS, RING_OUTER_RADIUS, RING_SEGMENTS, rings::RING_GAPS));
+
+// Accretion-disk bodies (`PlanetType::AccretionDisk`) are drawn as a tilted glowing torus instead of their model
+const DISK_MAJOR_RADIUS: f32 = 1.0;
+const DISK_MINOR_RADIUS: f32 = 0.35;
+const DISK_TILT: f32 = 0.35;
+const DISK_MAJOR_SEGMENTS: usize = 96;
+const DISK_MINOR_SEGMENTS: usize = 24;
+/// The accretion disk's torus mesh; built once, like the gas giant's ring
+static ACCRETION_DISK: LazyLock<Vec<Vertex>> =
+    LazyLock::new(|| rings::generate_torus_ring(DISK_MAJOR_RADIUS, DISK_MINOR_RADIUS, DISK_MAJOR_SEGMENTS, DISK_MINOR_SEGMENTS));
+
+// Resolution of the sphere that stands in for a model that fails to load
+const FALLBACK_SPHERE_STACKS: usize = 24;
+const FALLBACK_SPHERE_SLICES: usize = 48;
+
+// Shader gallery (--shader-gallery): sphere resolution and radius as a fraction of the smaller cell side
+const GALLERY_SPHERE_STACKS: usize = 32;
+const GALLERY_SPHERE_SLICES: usize = 64;
+const GALLERY_SPHERE_FRACTION: f32 = 0.32;
+
+// Length of each gizmo axis in world units
+const AXES_LENGTH: f32 = 80.0;
+
+// Reference grid (F8): line color at the center; lines fade to nothing at the grid's edge
+const GRID_COLOR: Vector3 = Vector3 { x: 0.25, y: 0.35, z: 0.55 };
+
+// Orbit paths (0): points sampled along one period, color and opacity of the line
+const ORBIT_PATH_SAMPLES: usize = 128;
+const ORBIT_PATH_COLOR: Vector3 = Vector3 { x: 0.6, y: 0.6, z: 0.7 };
+const ORBIT_PATH_ALPHA: f32 = 0.35;
+
+// Orbital geometry (SHIFT+0): opacity of the ellipse and axes, colors of the occupied focus (the
+// sun or barycenter), the empty focus and the axes
+const ORBIT_GEOMETRY_ALPHA: f32 = 0.6;
+const OCCUPIED_FOCUS_COLOR: Vector3 = Vector3 { x: 1.0, y: 0.85, z: 0.3 };
+const EMPTY_FOCUS_COLOR: Vector3 = Vector3 { x: 1.0, y: 0.4, z: 0.8 };
+const ORBIT_AXES_COLOR: Vector3 = Vector3 { x: 0.4, y: 0.6, z: 1.0 };
+// Eccentricity change per press of keypad * and /, and its cap (the orbit must stay an ellipse)
+const ECCENTRICITY_STEP: f32 = 0.05;
+const ECCENTRICITY_MAX: f32 = 0.9;
+
+// Point cloud view (F11): dot radius in pixels, colors of the nearest and farthest vertices
+const POINT_CLOUD_RADIUS: f32 = 1.5;
+const POINT_CLOUD_NEAR_COLOR: Vector3 = Vector3 { x: 1.0, y: 0.8, z: 0.3 };
+const POINT_CLOUD_FAR_COLOR: Vector3 = Vector3 { x: 0.25, y: 0.45, z: 1.0 };
+
+// Velocity arrows (F10): time step of the central difference, arrowhead size in pixels and color
+const VELOCITY_TIME_STEP: f32 = 0.01;
+const VELOCITY_ARROW_HEAD: f32 = 8.0;
+const VELOCITY_ARROW_COLOR: Vector3 = Vector3 { x: 0.35, y: 1.0, z: 0.55 };
+
+// Fraction of the smaller screen dimension the isolated body (plus satellites) spans
+const ISOLATION_FRAME_FRACTION: f32 = 0.35;
+
+/// How far a body's satellites reach, measured in multiples of the body's own radius
+fn satellite_reach(body: &CelestialBody) -> f32 {
+    let ring_reach = match body.planet_type {
+        PlanetType::GasGiant => RING_SCALE * RING_OUTER_RADIUS,
+        PlanetType::AccretionDisk => DISK_MAJOR_RADIUS + DISK_MINOR_RADIUS,
+        _ => 1.0,
+    };
+    body.moons
+        .iter()
+        .map(|moon| (moon.orbit_radius + moon.scale * BODY_SIZE) / (body.scale * BODY_SIZE))
+        .fold(ring_reach, f32::max)
+}
+
+/// Light of every star, given as (position, light color) with the sun first, on a body at
+/// `body_position`: the direction towards each star in world space (where normals are lit). A star
+/// doesn't light itself; `manual_light` replaces the sun's direction. Without a star lighting the
+/// body, falls back to `DEFAULT_STAR_LIGHT`.
+fn star_lights_at(stars: &[(Vector3, Vector3)], body_position: Vector3, manual_light: Option<Vector3>) -> Vec<StarLight> {
+    let mut lights: Vec<StarLight> = stars
+        .iter()
+        .enumerate()
+        .filter_map(|(index, &(position, color))| {
+            let to_star = position - body_position;
+            let direction = match manual_light {
+                Some(direction) if index == 0 => direction,
+                _ if to_star.length() > 1e-3 => to_star.normalized(),
+                _ => return None,
+            };
+            Some(StarLight { direction, color })
+        })
+        .collect();
+    if lights.is_empty() {
+        lights.push(DEFAULT_STAR_LIGHT);
+    }
+    lights
+}
+
+/// Position of a body on its orbit around `orbit_center` (the center itself while orbits are paused)
+fn orbit_position(body: &CelestialBody, orbit_center: Vector3, time: f32, state: &AppState) -> Vector3 {
+    if !state.auto_orbit {
+        return orbit_center;
+    }
+
+    // Mean anomaly: grows at a constant rate, the position along the ellipse doesn't
+    let mean_anomaly = time * body.orbit_speed * state.orbit_speed_mult + body.orbit_phase;
+
+    // Ellipse in the X-Y plane with the orbit center at the focus (faster near the perihelion),
+    // then tilted into the body's own orbital plane
+    let in_plane = kepler_position(body.orbit_radius, body.eccentricity, mean_anomaly);
+    orbit_center + incline(in_plane, body.inclination, body.ascending_node)
+}
+
+/// Mass used to place stars around their barycenter (proportional to volume)
+fn star_mass(body: &CelestialBody) -> f32 {
+    body.scale.powi(3)
+}
+
+/// Adds `companions` as stars after the sun (`bodies[0]`), spread evenly around it at
+/// `BINARY_SEPARATION`, and sets every star circling the barycenter of them all at `BINARY_ORBIT_SPEED`
+fn add_companion_stars(bodies: &mut Vec<CelestialBody>, companions: &[CompanionStar]) {
+    if companions.is_empty() {
+        return;
+    }
+
+    let stars: Vec<CelestialBody> = companions
+        .iter()
+        .map(|companion| CelestialBody {
+            name: companion.name.clone(),
+            planet_type: PlanetType::Sun,
+            scale: companion.scale,
+            orbit_radius: 0.0,
+            orbit_speed: BINARY_ORBIT_SPEED,
+            eccentricity: 0.0,
+            inclination: 0.0,
+            ascending_node: 0.0,
+            rotation_speed: 0.03,
+            model_path: "assets/models/13913_Sun_v2_l3.obj".to_string(),
+            subdivision: 0,
+            visible: true,
+            satellites_visible: true,
+            animation: AnimationSpeeds::default(),
+            material: SurfaceMaterial::default(),
+            spin_axis: SPIN_AXIS_UP,
+            orbit_phase: 0.0,
+            star: true,
+            light_color: companion.light_color,
+            lod: LodFade::default(),
+            moons: Vec::new(),
+        })
+        .collect();
+
+    // Offsets from the sun, then the sun's own offset from the barycenter: the one that leaves
+    // the mass-weighted sum of all star positions at zero
+    let offsets: Vec<Vector2> = (0..stars.len())
+        .map(|index| {
+            let angle = PI + 2.0 * PI * index as f32 / stars.len() as f32;
+            Vector2::new(angle.cos(), angle.sin()) * BINARY_SEPARATION
+        })
+        .collect();
+    let total_mass = star_mass(&bodies[0]) + stars.iter().map(star_mass).sum::<f32>();
+    let sun = stars
+        .iter()
+        .zip(&offsets)
+        .fold(Vector2::zero(), |sum, (star, &offset)| sum - offset * (star_mass(star) / total_mass));
+
+    // Circular orbits at the same rate keep the stars in formation around the barycenter
+    let place = |body: &mut CelestialBody, position: Vector2| {
+        body.orbit_radius = position.length();
+        body.orbit_phase = position.y.atan2(position.x);
+        body.orbit_speed = BINARY_ORBIT_SPEED;
+    };
+    place(&mut bodies[0], sun);
+    for (index, (mut star, offset)) in stars.into_iter().zip(offsets).enumerate() {
+        place(&mut star, sun + offset);
+        bodies.insert(1 + index, star);
+    }
+}
+
+/// Mass-weighted center of the stars orbiting `center`; `center` itself when there are none
+fn star_barycenter(bodies: &[CelestialBody], center: Vector3, time: f32, state: &AppState) -> Vector3 {
+    let mut weighted = Vector3::zero();
+    let mut total_mass = 0.0;
+    for body in bodies.iter().filter(|body| body.star) {
+        let mass = star_mass(body);
+        weighted = weighted + orbit_position(body, center, time, state) * mass;
+        total_mass += mass;
+    }
+    if total_mass > 0.0 { weighted * (1.0 / total_mass) } else { center }
+}
+
+/// Where the camera looks and from how far: the selected body, framed, while it is isolated;
+/// the manual camera otherwise
+fn camera_framing(bodies: &[CelestialBody], state: &AppState, time: f32, viewport: (u32, u32)) -> (Vector3, f32) {
+    if state.isolate_body {
+        let body = &bodies[state.selected_body];
+        (system_position(body, bodies, time, state), isolation_distance(body, state, viewport))
+    } else {
+        (state.camera_target, state.camera_distance(viewport))
+    }
+}
+
+/// Picks every body's level of detail from its radius on screen (through the camera without
+/// shake or stereo) and advances the cross-fades by `dt` seconds
+fn update_lod(bodies: &mut [CelestialBody], state: &AppState, config: &Config, time: f32, viewport: (u32, u32), dt: f32) {
+    let (target, distance) = camera_framing(bodies, state, time, viewport);
+    let camera = Camera::orbit(target, state.camera_rotation, distance, Vector2::zero(), viewport, state.near_plane, state.far_plane);
+    let screen_radii: Vec<f32> = bodies
+        .iter()
+        .map(|body| {
+            let position = system_position(body, bodies, time, state);
+            body.scale * BODY_SIZE * camera.pixels_per_unit(position)
+        })
+        .collect();
+    for (body, screen_radius) in bodies.iter_mut().zip(screen_radii) {
+        body.lod.update(screen_radius, config.lod_radius, dt, config.lod_fade);
+    }
+}
+
+/// Camera distance that frames an isolated body (and its satellites) in the middle of the
+/// screen; the zoom still dollies in and out, but never into the body
+fn isolation_distance(body: &CelestialBody, state: &AppState, viewport: (u32, u32)) -> f32 {
+    let reach = if state.isolate_satellites { satellite_reach(body) } else { 1.0 };
+    let frame_radius = viewport.0.min(viewport.1) as f32 * ISOLATION_FRAME_FRACTION;
+    let radius = body.scale * BODY_SIZE;
+    // At pixel_distance one world unit spans one pixel
+    let distance = Camera::pixel_distance(viewport) * radius * reach / frame_radius;
+    (distance * DEFAULT_CAMERA_ZOOM / state.camera_zoom).max(radius + state.near_plane)
 }
 
 fn create_model_matrix(translation: Vector3, scale: f32, rotation: Vector3) -> Matrix {
@@ -92,83 +743,385 @@ fn create_model_matrix(translation: Vector3, scale: f32, rotation: Vector3) -> M
     scale_matrix * rotation_matrix * translation_matrix
 }
 
-fn render(framebuffer: &mut Framebuffer, uniforms: &Uniforms, vertex_array: &[Vertex]) {
+/// Renders a non-indexed triangle list (every 3 consecutive vertices form a triangle).
+/// Used for procedural geometry such as the rings; OBJ models go through `render_indexed`.
+/// With `deferred` the draw may only be rasterized now and shaded later (see `rasterize_and_shade`).
+fn render<'u>(
+    framebuffer: &mut Framebuffer,
+    uniforms: &Uniforms<'u>,
+    vertex_array: &[Vertex],
+    stats: &mut RenderStats,
+    deferred: Option<&mut DeferredDraws<'u>>,
+) {
     // Vertex Shader Stage
+    let stage_start = Instant::now();
     let mut transformed_vertices = Vec::with_capacity(vertex_array.len());
     for vertex in vertex_array {
         let transformed = vertex_shader(vertex, uniforms);
         transformed_vertices.push(transformed);
     }
 
+    stats.timings.vertex += stage_start.elapsed();
+
+    // Primitive Assembly Stage
+    let triangles = transformed_vertices
+        .chunks_exact(3)
+        .map(|tri| [&tri[0], &tri[1], &tri[2]]);
+
+    rasterize_and_shade(framebuffer, uniforms, triangles, stats, deferred);
+}
+
+/// Renders an indexed mesh: the vertex shader runs once per unique vertex and
+/// triangles are assembled from the index buffer afterwards.
+fn render_indexed<'u>(
+    framebuffer: &mut Framebuffer,
+    uniforms: &Uniforms<'u>,
+    vertices: &[Vertex],
+    indices: &[u32],
+    stats: &mut RenderStats,
+    deferred: Option<&mut DeferredDraws<'u>>,
+) {
+    // Vertex Shader Stage (once per unique vertex)
+    let stage_start = Instant::now();
+    let mut transformed_vertices = Vec::with_capacity(vertices.len());
+    for vertex in vertices {
+        let transformed = vertex_shader(vertex, uniforms);
+        transformed_vertices.push(transformed);
+    }
+
+    stats.timings.vertex += stage_start.elapsed();
+
     // Primitive Assembly Stage
-    let mut triangles = Vec::new();
-    for i in (0..transformed_vertices.len()).step_by(3) {
-        if i + 2 < transformed_vertices.len() {
-            triangles.push([
-                transformed_vertices[i].clone(),
-                transformed_vertices[i + 1].clone(),
-                transformed_vertices[i + 2].clone(),
-            ]);
+    let triangles = indices.chunks_exact(3).map(|face| {
+        [
+            &transformed_vertices[face[0] as usize],
+            &transformed_vertices[face[1] as usize],
+            &transformed_vertices[face[2] as usize],
+        ]
+    });
+
+    rasterize_and_shade(framebuffer, uniforms, triangles, stats, deferred);
+}
+
+/// Cheap occlusion reject before rasterizing: the triangle's nearest corner is behind what is
+/// already stored across its whole screen bounding box, so none of its fragments could show.
+/// Only earlier draws count, since a draw's own fragments are written after all of its
+/// triangles are rasterized.
+fn occluded(framebuffer: &Framebuffer, [a, b, c]: [&Vertex; 3], viewport: (u32, u32)) -> bool {
+    let (p1, p2, p3) = (a.transformed_position, b.transformed_position, c.transformed_position);
+    pixel_bounds(p1, p2, p3, viewport).is_some_and(|bounds| framebuffer.depth_hides(bounds, p1.z.min(p2.z).min(p3.z)))
+}
+
+/// A draw rasterized but not shaded yet, waiting for `DeferredDraws::finish`
+struct DeferredDraw<'a> {
+    uniforms: Uniforms<'a>,
+    fragments: Vec<Fragment>,
+}
+
+/// Draws of a frame held back until all of it is rasterized, for the depth prepass and depth
+/// peeling. Draws that neither needs are shaded as soon as they are rasterized.
+struct DeferredDraws<'a> {
+    depth_prepass: bool, // Opaque draws write only depth now and are shaded where they stayed the nearest
+    depth_peels: usize,  // Translucent layers composited by depth peeling; 0 blends them in draw order
+    draws: Vec<DeferredDraw<'a>>,
+}
+
+impl<'a> DeferredDraws<'a> {
+    fn new(depth_prepass: bool, depth_peels: usize) -> Self {
+        DeferredDraws { depth_prepass, depth_peels, draws: Vec::new() }
+    }
+
+    /// Whether a draw with this blend is held back. Translucent draws must wait whenever opaque
+    /// ones do, or the opaque colors shaded later would paint over them.
+    fn defers(&self, blend: Option<(BlendMode, f32)>) -> bool {
+        self.depth_prepass || (blend.is_some() && self.depth_peels > 0)
+    }
+
+    /// Shades everything held back: opaque fragments only where they are the nearest surface,
+    /// then the translucent draws over the finished surfaces, peeled or in the order they came
+    fn finish(self, framebuffer: &mut Framebuffer, stats: &mut RenderStats) {
+        let (opaque, translucent): (Vec<_>, Vec<_>) = self.draws.into_iter().partition(|draw| draw.uniforms.blend.is_none());
+        for draw in opaque {
+            shade(framebuffer, &draw.uniforms, draw.fragments, stats, true);
+        }
+        if self.depth_peels > 0 {
+            shade_peeled(framebuffer, &translucent, self.depth_peels, stats);
+        } else {
+            for draw in translucent {
+                shade(framebuffer, &draw.uniforms, draw.fragments, stats, true);
+            }
+        }
+    }
+}
+
+/// Rasterizes a draw, then shades it right away or, when `deferred` holds it back, queues it
+/// for `DeferredDraws::finish`. With the depth prepass, opaque draws write their depth now.
+fn rasterize_and_shade<'a, 'u>(
+    framebuffer: &mut Framebuffer,
+    uniforms: &Uniforms<'u>,
+    triangles: impl Iterator<Item = [&'a Vertex; 3]>,
+    stats: &mut RenderStats,
+    deferred: Option<&mut DeferredDraws<'u>>,
+) {
+    let mut fragments = rasterize(framebuffer, uniforms, triangles, stats);
+    if let Some(coverage) = uniforms.screen_door {
+        fragments.retain(|fragment| screen_door_keeps(fragment.position.x as i32, fragment.position.y as i32, coverage));
+    }
+    match deferred {
+        Some(deferred) if deferred.defers(uniforms.blend) => {
+            // Translucent draws don't hide anything, so they leave the depth alone
+            if uniforms.blend.is_none() {
+                let (near, far) = uniforms.clip_planes;
+                for fragment in fragments.iter().filter(|fragment| (near..=far).contains(&fragment.depth)) {
+                    framebuffer.write_depth(fragment.position.x as i32, fragment.position.y as i32, fragment.depth);
+                }
+            }
+            deferred.draws.push(DeferredDraw { uniforms: uniforms.clone(), fragments });
+        }
+        _ => shade(framebuffer, uniforms, fragments, stats, false),
+    }
+}
+
+/// Depth peeling (`depth_peels`): the translucent fragments in front of the opaque surfaces are
+/// sorted into up to `peels` layers per pixel, nearest first (see `peeling::peel_layers`), and
+/// blended farthest layer first, so they composite the same whatever order they were drawn in.
+/// Each fragment that makes it into a layer is shaded once; deeper ones are dropped unshaded.
+fn shade_peeled(framebuffer: &mut Framebuffer, draws: &[DeferredDraw], peels: usize, stats: &mut RenderStats) {
+    let (width, height) = (framebuffer.width as i32, framebuffer.height as i32);
+    // (pixel, depth) of every fragment that can show, and the (draw, fragment) it comes from
+    let mut candidates = Vec::new();
+    let mut sources = Vec::new();
+    for (draw_index, draw) in draws.iter().enumerate() {
+        let (near, far) = draw.uniforms.clip_planes;
+        for (fragment_index, fragment) in draw.fragments.iter().enumerate() {
+            stats.record_depth(fragment.depth);
+            if fragment.depth < near || fragment.depth > far {
+                stats.clipped_fragments += 1;
+                continue;
+            }
+            let (x, y) = (fragment.position.x as i32, fragment.position.y as i32);
+            if x >= 0 && y >= 0 && x < width && y < height {
+                candidates.push((y as usize * width as usize + x as usize, fragment.depth));
+                sources.push((draw_index, fragment_index));
+            }
+        }
+    }
+
+    let stage_start = Instant::now();
+    let layers = peel_layers(&candidates, framebuffer.depth_buffer(), peels);
+    let shaders = shader_registry();
+    for layer in layers.iter().rev() {
+        for &candidate in layer {
+            let (draw_index, fragment_index) = sources[candidate];
+            let uniforms = &draws[draw_index].uniforms;
+            let fragment = &draws[draw_index].fragments[fragment_index];
+            let Some((mode, alpha)) = uniforms.blend else { continue };
+            let shader_params = ShaderParams { animation: uniforms.animation, disk: uniforms.disk };
+            let color = fragment_color(fragment, uniforms, shaders.get(uniforms.planet_type), &shader_params);
+            stats.shaded_fragments += 1;
+            framebuffer.with_blend_mode(mode, |framebuffer| {
+                framebuffer.point_blended(fragment.position.x as i32, fragment.position.y as i32, color, alpha)
+            });
         }
     }
+    stats.timings.fragment += stage_start.elapsed();
+}
 
+fn rasterize<'a>(
+    framebuffer: &Framebuffer,
+    uniforms: &Uniforms,
+    triangles: impl Iterator<Item = [&'a Vertex; 3]>,
+    stats: &mut RenderStats,
+) -> Vec<Fragment> {
     // Rasterization Stage
+    let stage_start = Instant::now();
     let mut fragments = Vec::new();
-    for tri in &triangles {
-        fragments.extend(triangle(&tri[0], &tri[1], &tri[2]));
+    for tri in triangles {
+        if !tri.iter().any(|vertex| vertex.behind_camera) {
+            if occluded(framebuffer, tri, uniforms.viewport) {
+                stats.occluded_triangles += 1;
+                continue;
+            }
+            stats.triangles += 1;
+            fragments.extend(triangle(tri[0], tri[1], tri[2], uniforms.cull_backfaces, uniforms.viewport));
+            continue;
+        }
+
+        // Primitive assembly: cut off the part behind the camera before it is rasterized
+        let mut clipped = clip_near(tri, uniforms.viewport);
+        // The new vertices on the near plane are projected with the projection's depth
+        if let Some(far) = uniforms.log_depth_far {
+            for vertex in clipped.iter_mut().flatten() {
+                vertex.transformed_position.z = log_depth(vertex.clip_position.w, far);
+            }
+        }
+        if clipped.is_empty() {
+            stats.behind_camera_triangles += 1;
+        }
+        for [a, b, c] in &clipped {
+            if occluded(framebuffer, [a, b, c], uniforms.viewport) {
+                stats.occluded_triangles += 1;
+                continue;
+            }
+            stats.triangles += 1;
+            fragments.extend(triangle(a, b, c, uniforms.cull_backfaces, uniforms.viewport));
+        }
     }
+    stats.fragments += fragments.len();
+    stats.timings.raster += stage_start.elapsed();
+    fragments
+}
 
+/// Runs the fragment shader on `fragments` and writes them to the framebuffer. With
+/// `depth_prepassed` the depth buffer already holds the nearest depth of every pixel, so only
+/// the opaque fragments matching it are shaded, and translucent ones only where nothing nearer
+/// hides them; the others never reach the shader.
+fn shade(framebuffer: &mut Framebuffer, uniforms: &Uniforms, fragments: Vec<Fragment>, stats: &mut RenderStats, depth_prepassed: bool) {
     // Fragment Processing Stage
-    for fragment in fragments {
-        // Create a temporary vertex at the fragment position for shader evaluation
-        let temp_vertex = Vertex {
-            position: Vector3::new(fragment.position.x, fragment.position.y, 0.0),
-            normal: Vector3::new(0.0, 1.0, 0.0),
-            tex_coords: Vector2::zero(),
-            color: fragment.color, // Use material color from the vertex
-            transformed_position: Vector3::new(fragment.position.x, fragment.position.y, fragment.depth),
-            transformed_normal: Vector3::new(0.0, 1.0, 0.0),
-        };
-        
-        // Apply shader to get color based on planet type
-        let color = get_planet_color(&fragment, &temp_vertex, uniforms.time, uniforms.planet_type);
-        
-        framebuffer.point(
-            fragment.position.x as i32,
-            fragment.position.y as i32,
-            color
-        );
+    let stage_start = Instant::now();
+    // The body's shader is looked up once for the whole draw
+    let shaders = shader_registry();
+    let shader = shaders.get(uniforms.planet_type);
+    let shader_params = ShaderParams { animation: uniforms.animation, disk: uniforms.disk };
+    // Opaque draws replace the stored pixels; translucent ones blend with their mode and opacity
+    let (mode, alpha) = uniforms.blend.unwrap_or((BlendMode::Replace, 1.0));
+    framebuffer.with_blend_mode(mode, |framebuffer| {
+        for fragment in fragments {
+            stats.record_depth(fragment.depth);
+            let (near, far) = uniforms.clip_planes;
+            if fragment.depth < near || fragment.depth > far {
+                stats.clipped_fragments += 1;
+                continue;
+            }
+            let (x, y) = (fragment.position.x as i32, fragment.position.y as i32);
+            if depth_prepassed {
+                let hidden = match uniforms.blend {
+                    Some(_) => framebuffer.depth_hides((x, y, x, y), fragment.depth),
+                    None => !framebuffer.depth_matches(x, y, fragment.depth),
+                };
+                if hidden {
+                    continue;
+                }
+            }
+            stats.shaded_fragments += 1;
+
+            let color = fragment_color(&fragment, uniforms, shader, &shader_params);
+
+            match uniforms.blend {
+                // Translucent surfaces are hidden behind opaque ones but don't hide anything themselves
+                Some(_) => framebuffer.point_blended_with_depth(x, y, fragment.depth, color, alpha),
+                // The prepass already stored this fragment's depth; it only needs its color
+                None if depth_prepassed => framebuffer.point(x, y, color),
+                // Opaque surfaces are depth-tested, so nearer bodies hide farther ones whatever the draw order
+                None => framebuffer.point_with_depth(x, y, fragment.depth, color),
+            }
+        }
+    });
+    stats.timings.fragment += stage_start.elapsed();
+}
+
+/// Fragment shader of a draw: the lit color of one fragment with the draw's maps, dynamic
+/// lights and debug tint applied
+fn fragment_color(fragment: &Fragment, uniforms: &Uniforms, shader: &dyn FragmentShader, shader_params: &ShaderParams) -> Vector3 {
+    // Create a temporary vertex at the fragment position for shader evaluation, carrying the
+    // interpolated normal (lit by get_planet_color), UVs and object-space position (which the
+    // shaders map their patterns from, so they stay on the surface)
+    let mut temp_vertex = Vertex {
+        position: fragment.object_position,
+        normal: fragment.transformed_normal,
+        tangent: fragment.tangent,
+        tex_coords: fragment.tex_coords,
+        color: fragment.color, // Use material color from the vertex
+        transformed_position: Vector3::new(fragment.position.x, fragment.position.y, fragment.depth),
+        transformed_normal: fragment.transformed_normal,
+        clip_position: Vector4::new(fragment.position.x, fragment.position.y, fragment.depth, 1.0),
+        behind_camera: false,
+        inv_w: 1.0,
+    };
+    
+    // With a normal map, light the mapped surface normal instead of the interpolated one
+    if let Some((normal_texture, material, strength)) = uniforms.normal_map {
+        let uv = material.transform_uv(fragment.tex_coords);
+        let sample = normal_texture.sample_bilinear(uv.x, uv.y);
+        // The map perturbs the object-space normal; light it in the same space as the interpolated one
+        let mapped = perturb_normal(fragment.normal, fragment.tangent, sample, strength);
+        temp_vertex.normal = transform_direction(&uniforms.normal_transform, mapped).normalized();
+    }
+
+    // The rings shade each star's light on its own: on a sphere the world-space normal points at
+    // the surface point, in the light's space
+    let shadowed_lights: Vec<StarLight>;
+    let star_lights = match &uniforms.ring_shadow {
+        Some(shadow) => {
+            shadowed_lights = uniforms
+                .star_lights
+                .iter()
+                .map(|light| StarLight {
+                    color: light.color * ring_shadow_factor(fragment.transformed_normal, light.direction, shadow),
+                    ..*light
+                })
+                .collect();
+            &shadowed_lights
+        }
+        None => &uniforms.star_lights,
+    };
+
+    // Apply shader to get color based on planet type
+    let mut color = get_planet_color(fragment, &temp_vertex, uniforms.time, shader, shader_params, star_lights, uniforms.view_dir, uniforms.ambient, uniforms.material);
+
+    // The sun and the accretion disk are emissive, everything else picks up nearby dynamic lights
+    if !matches!(uniforms.planet_type, PlanetType::Sun | PlanetType::AccretionDisk) {
+        let world_position = transform_point(&uniforms.model_matrix, fragment.object_position);
+        color = apply_point_lights(color, world_position, temp_vertex.normal.normalized(), &uniforms.point_lights);
+    }
+
+    // Emissive maps glow on top of the lit color, so they still show on the night side
+    if let Some((emissive_texture, material)) = uniforms.emissive_map {
+        let uv = material.transform_uv(fragment.tex_coords);
+        color = color + emissive_texture.sample_bilinear(uv.x, uv.y);
     }
+
+    if let (Some(tint), false) = (uniforms.backface_tint, fragment.front_facing) {
+        color = color * (1.0 - BACKFACE_TINT_AMOUNT) + tint * BACKFACE_TINT_AMOUNT;
+    }
+
+    color
 }
 
 fn main() {
-    let window_width = 800;
-    let window_height = 600;
+    let args: Vec<String> = std::env::args().collect();
+    let mut config = Config::from_args(&args);
+    println!("✓ Scene seed: {}", config.scene_seed);
 
-    let (mut window, thread) = raylib::init()
-        .size(window_width, window_height)
-        .title("Rust Graphics - Renderer Example")
-        .log_level(TraceLogLevel::LOG_WARNING) // Suppress INFO messages
-        .build();
+    let batch = BatchRender::from_args(&args);
+    let gallery = ShaderGallery::from_args(&args);
+    let panel_view = args.iter().any(|arg| arg == "--panel");
+    let depth_test_scene = args.iter().any(|arg| arg == "--depth-test");
+
+    // Batch renders use their own frame size; the window is always 800x600
+    let (window_width, window_height) = match &batch {
+        Some(batch) => (batch.width as i32, batch.height as i32),
+        None => (800, 600),
+    };
 
     let mut framebuffer = Framebuffer::new(window_width as u32, window_height as u32);
     framebuffer.set_background_color(Vector3::new(0.01, 0.01, 0.05)); // Deep space black with slight blue tint
-
-    // Initialize the texture inside the framebuffer
-    framebuffer.init_texture(&mut window, &thread);
+    framebuffer.set_star_seed(derive_seed(config.scene_seed, "stars"));
+    framebuffer.set_star_antialiasing(config.star_antialiasing);
 
     // Animation parameters
     let mut time = 0.0f32;
-    let mut auto_rotate = true;
-    let mut auto_orbit = true;
-    
-    // Camera/viewport control
-    let mut camera_offset = Vector3::new(0.0, 0.0, 0.0);
-    let mut camera_zoom = 0.6f32;  // Start more zoomed out to see all planets
-    let mut system_rotation = Vector3::new(0.0, 0.0, 0.0);
 
-    // Load all unique models into a cache (with textures)
-    let mut model_cache: std::collections::HashMap<String, (Vec<Vertex>, Option<Texture>)> = std::collections::HashMap::new();
+    // Camera/viewport control and toggles
+    let mut state = AppState::new();
+    state.near_plane = config.near_plane;
+    state.far_plane = config.far_plane;
+    state.log_depth = config.log_depth;
+    state.cinematic = config.cinematic_camera;
+    state.time_scale = config.time_scale.clamp(-TIME_SCALE_MAX, TIME_SCALE_MAX);
+
+    // Load all unique models into a cache (indexed meshes with textures)
+    let mut model_cache: HashMap<String, Obj> = HashMap::new();
     
     // Pre-load unique models
     let unique_models = vec![
@@ -179,296 +1132,2243 @@ fn main() {
         "assets/models/10464_Asteroid_v1_Iterations-2.obj",
     ];
     
+    // Winding correction is a heuristic, so it is opt-in (config or --fix-winding)
+    let load_options = ObjLoadOptions {
+        fix_winding: config.fix_winding,
+        smooth_normals: config.smooth_normals,
+        missing_texture_checker: config.missing_texture_checker,
+        flip_texcoord_v: false,
+        ..ObjLoadOptions::default()
+    };
+    
     for model_path in unique_models {
-        match Obj::load(model_path) {
+        // UV V flip and axes are per model: they depend on the program that exported the OBJ
+        let options = ObjLoadOptions {
+            flip_texcoord_v: config.flips_texcoord_v(model_path),
+            up_axis: config.up_axis(model_path),
+            handedness: config.handedness(model_path),
+            ..load_options.clone()
+        };
+        match Obj::load_with_options(model_path, &options) {
             Ok(obj) => {
-                let vertex_array = obj.get_vertex_array();
-                let texture = obj.get_texture().clone();
-                model_cache.insert(model_path.to_string(), (vertex_array, texture));
                 println!("✓ Loaded model: {}", model_path);
+                println!("  {}", obj.validate());
+                model_cache.insert(model_path.to_string(), obj);
             }
             Err(e) => {
-                eprintln!("✗ Failed to load {}: {:?}", model_path, e);
+                // Bodies using the model still show up, as a plain sphere
+                eprintln!("✗ Failed to load {}: {} (using a procedural sphere instead)", model_path, e);
+                let (vertices, indices) = sphere::generate_uv_sphere(1.0, FALLBACK_SPHERE_STACKS, FALLBACK_SPHERE_SLICES);
+                model_cache.insert(model_path.to_string(), Obj::from_mesh(vertices, indices));
             }
         }
     }
 
     // Define celestial bodies with their respective OBJ models
-    let bodies = vec![
+    let mut bodies = vec![
         CelestialBody {
             name: "Sol".to_string(),
-            planet_type: 0,
+            planet_type: PlanetType::Sun,
             scale: 50.0,  // Slightly larger sun
             orbit_radius: 0.0,
             orbit_speed: 0.0,
+            eccentricity: 0.0,
+            inclination: 0.0,
+            ascending_node: 0.0,
             rotation_speed: 0.02,
             model_path: "assets/models/13913_Sun_v2_l3.obj".to_string(),
+            subdivision: 0,
+            visible: true,
+            satellites_visible: true,
+            animation: AnimationSpeeds::default(),
+            material: SurfaceMaterial::default(),
+            spin_axis: SPIN_AXIS_UP,
+            orbit_phase: 0.0,
+            star: true,
+            light_color: config.sun_light_color,
+            lod: LodFade::default(),
+            moons: Vec::new(),
         },
         CelestialBody {
             name: "Tierra".to_string(),
-            planet_type: 1,
+            planet_type: PlanetType::EarthLike,
             scale: 20.0,  // Smaller to emphasize distance
             orbit_radius: 180.0,  // Much farther from sun
             orbit_speed: 0.15,
+            eccentricity: 0.1,
+            inclination: 0.15,
+            ascending_node: 0.0,
             rotation_speed: 0.03,
             model_path: "assets/models/13902_Earth_v1_l3.obj".to_string(),
+            subdivision: 0,
+            visible: true,
+            satellites_visible: true,
+            animation: AnimationSpeeds::default(),
+            material: SurfaceMaterial::default(),
+            spin_axis: SPIN_AXIS_UP,
+            orbit_phase: 0.0,
+            star: false,
+            light_color: Vector3::zero(),
+            lod: LodFade::default(),
+            moons: vec![Moon::new(MOON_SCALE, MOON_DISTANCE, 0.08, 0.05, PlanetType::Moon)], // Luna
         },
         CelestialBody {
             name: "Gigante Gaseoso".to_string(),
-            planet_type: 2,
+            planet_type: PlanetType::GasGiant,
             scale: 28.0,  // Smaller but still large
             orbit_radius: 280.0,  // Very far orbit
             orbit_speed: 0.08,
+            eccentricity: 0.1,
+            inclination: 0.25,
+            ascending_node: 1.2,
             rotation_speed: 0.02,
             model_path: "assets/models/13905_Jupiter_V1_l3.obj".to_string(),
+            subdivision: 0,
+            visible: true,
+            satellites_visible: true,
+            animation: AnimationSpeeds::default(),
+            material: SurfaceMaterial::default(),
+            spin_axis: GAS_GIANT_SPIN_AXIS, // The rings lie in its equator
+            orbit_phase: 0.0,
+            star: false,
+            light_color: Vector3::zero(),
+            lod: LodFade::default(),
+            moons: Vec::new(),
         },
         CelestialBody {
             name: "Venus".to_string(),
-            planet_type: 7,
+            planet_type: PlanetType::Venus,
             scale: 18.0,  // Smaller, closer to sun
             orbit_radius: 120.0,  // Between sun and Earth
             orbit_speed: 0.20,
-            rotation_speed: 0.008,
+            eccentricity: 0.05,
+            inclination: 0.1,
+            ascending_node: 2.5,
+            rotation_speed: -0.008, // Retrograde spin
             model_path: "assets/models/13902_Earth_v1_l3.obj".to_string(), // Reuse Earth model
+            subdivision: 0,
+            visible: true,
+            satellites_visible: true,
+            animation: AnimationSpeeds::default(),
+            material: SurfaceMaterial::default(),
+            spin_axis: SPIN_AXIS_UP,
+            orbit_phase: 0.0,
+            star: false,
+            light_color: Vector3::zero(),
+            lod: LodFade::default(),
+            moons: Vec::new(),
         },
         CelestialBody {
             name: "Neptuno".to_string(),
-            planet_type: 5,
+            planet_type: PlanetType::Neptune,
             scale: 25.0,  // Medium size
             orbit_radius: 380.0,  // Very far orbit
             orbit_speed: 0.05,
+            eccentricity: 0.04,
+            inclination: 0.35,
+            ascending_node: 4.0,
             rotation_speed: 0.025,
             model_path: "assets/models/13907_Uranus_v2_l3.obj".to_string(), // Reuse Uranus model
+            subdivision: 0,
+            visible: true,
+            satellites_visible: true,
+            animation: AnimationSpeeds::default(),
+            material: ICE_GIANT_MATERIAL,
+            spin_axis: SPIN_AXIS_UP,
+            orbit_phase: 0.0,
+            star: false,
+            light_color: Vector3::zero(),
+            lod: LodFade::default(),
+            moons: Vec::new(),
         },
         CelestialBody {
             name: "Urano".to_string(),
-            planet_type: 6,
+            planet_type: PlanetType::Uranus,
             scale: 24.0,  // Similar to Neptune
             orbit_radius: 330.0,  // Between Jupiter and Neptune
             orbit_speed: 0.07,
+            eccentricity: 0.06,
+            inclination: 0.3,
+            ascending_node: 5.2,
             rotation_speed: 0.035,
             model_path: "assets/models/13907_Uranus_v2_l3.obj".to_string(),
+            subdivision: 0,
+            visible: true,
+            satellites_visible: true,
+            animation: AnimationSpeeds::default(),
+            material: ICE_GIANT_MATERIAL,
+            spin_axis: URANUS_SPIN_AXIS, // Spins on its side
+            orbit_phase: 0.0,
+            star: false,
+            light_color: Vector3::zero(),
+            lod: LodFade::default(),
+            moons: Vec::new(),
         },
     ];
 
-    while !window.window_should_close() {
-        handle_input(&mut window, &mut camera_offset, &mut camera_zoom, &mut system_rotation, &mut auto_rotate, &mut auto_orbit);
+    // Companion stars join the sun and every star circles their barycenter
+    add_companion_stars(&mut bodies, &config.companion_stars);
 
-        // Update time
-        time += 0.016; // Approximately 60 FPS
+    // Visibility saved in the config
+    for body in bodies.iter_mut() {
+        body.visible = !config.hidden_bodies.contains(&body.name);
+        body.satellites_visible = !config.hidden_satellites.contains(&body.name);
+        if let Some((_, material)) = config.body_materials.iter().find(|(name, _)| *name == body.name) {
+            body.material = *material;
+        }
+    }
 
-        framebuffer.clear();
+    let mut legend = build_legend(&bodies);
 
-        // Center point for the solar system (affected by camera offset)
-        let center = Vector3::new(400.0 + camera_offset.x, 300.0 + camera_offset.y, 0.0 + camera_offset.z);
+    // Subdivided copies of the models that ask for it, keyed by (model path, level)
+    let mut subdivided_meshes: HashMap<(String, u32), (Vec<Vertex>, Vec<u32>)> = HashMap::new();
+    let subdivision_requests = bodies
+        .iter()
+        .map(|body| (body.model_path.as_str(), body.subdivision))
+        .chain(std::iter::once((MOON_MODEL_PATH, MOON_SUBDIVISION)));
+    for (model_path, level) in subdivision_requests {
+        if level == 0 || subdivided_meshes.contains_key(&(model_path.to_string(), level)) {
+            continue;
+        }
+        if let Some(model) = model_cache.get(model_path) {
+            let mesh = subdivision::subdivide(&model.vertices, &model.indices, level, true);
+            println!("✓ Subdivided {} x{}: {} triangles", model_path, level, mesh.1.len() / 3);
+            subdivided_meshes.insert((model_path.to_string(), level), mesh);
+        }
+    }
 
-        // Render all celestial bodies
-        for body in bodies.iter() {
-            // Calculate position
-            let body_rotation = if auto_rotate {
-                Vector3::new(0.0, time * body.rotation_speed, 0.0)
-            } else {
-                Vector3::new(0.0, 0.0, 0.0)
-            };
+    // Post-process effects, in the order listed in the config
+    let post_chain = PostProcessChain::from_names(&config.post_effects);
+    if !config.post_effects.is_empty() {
+        println!("✓ Post effects: {}", post_chain.names().join(" -> "));
+    }
 
-            let body_translation = if auto_orbit {
-                let orbit_angle = time * body.orbit_speed;
-                // Create a proper 3D elliptical orbit with inclination
-                // Each planet has different orbital characteristics
-                let inclination = body.planet_type as f32 * 0.4; // Stronger inclination per planet
-                
-                // Primary orbit in X-Y plane
-                let orbit_x = orbit_angle.cos() * body.orbit_radius;
-                let orbit_y = orbit_angle.sin() * body.orbit_radius;
-                
-                // Z component (vertical oscillation due to orbit inclination)
-                // The Z position changes as the planet orbits
-                let orbit_z = (orbit_angle * inclination).sin() * body.orbit_radius * 0.5;
-                
-                Vector3::new(
-                    center.x + orbit_x,
-                    center.y + orbit_y,
-                    center.z + orbit_z,
-                )
-            } else {
-                center
-            };
+    // Scripted presentation, if one was given
+    let mut timeline = config.timeline.as_deref().and_then(|path| match Timeline::load(path) {
+        Ok(timeline) => {
+            println!("✓ Loaded timeline: {}", path);
+            Some(timeline)
+        }
+        Err(e) => {
+            eprintln!("✗ Failed to load timeline {}: {}", path, e);
+            None
+        }
+    });
 
-            // Apply system-wide rotation around center
-            let rotated_translation = rotate_point_around_center(body_translation, center, system_rotation);
+    // --profile-csv logs every rendered frame's stats, in batch mode or in the window
+    let mut profile_csv = config.profile_csv.as_deref().and_then(|path| match ProfileCsv::create(path) {
+        Ok(profile) => {
+            println!("✓ Logging render stats to {}", path);
+            Some(profile)
+        }
+        Err(e) => {
+            eprintln!("✗ Failed to create {}: {}", path, e);
+            None
+        }
+    });
 
-            let model_matrix = create_model_matrix(rotated_translation, body.scale * camera_zoom, body_rotation);
-            let uniforms = Uniforms {
-                model_matrix,
-                time,
-                planet_type: body.planet_type,
-            };
+    // Headless batch render: write the frames as PNGs and exit without opening a window
+    if let Some(batch) = batch {
+        if let Err(e) = std::fs::create_dir_all(&batch.out_dir) {
+            eprintln!("✗ Failed to create {}: {}", batch.out_dir, e);
+            return;
+        }
+        println!("Rendering {} frames ({}x{}, {}s per frame) to {}", batch.frames, batch.width, batch.height, batch.step, batch.out_dir);
 
-            // Get the vertex array for this body's model
-            if let Some((vertex_array, _texture)) = model_cache.get(&body.model_path) {
-                render(&mut framebuffer, &uniforms, vertex_array);
-            }
-
-            // Renderizar lunas y anillos específicos para algunos planetas
-            match body.planet_type {
-                1 => {
-                    // Tierra - Renderizar luna (la Luna)
-                    let moon_orbit_angle = time * 0.08; // Faster moon orbit
-                    let moon_distance = 50.0;
-                    let moon_pos = Vector3::new(
-                        rotated_translation.x + moon_orbit_angle.cos() * moon_distance,
-                        rotated_translation.y + moon_orbit_angle.sin() * moon_distance,
-                        rotated_translation.z,
-                    );
-                    
-                    let moon_rotation = if auto_rotate {
-                        Vector3::new(0.0, time * 0.05, 0.0)
-                    } else {
-                        Vector3::new(0.0, 0.0, 0.0)
-                    };
-                    
-                    let moon_matrix = create_model_matrix(moon_pos, 8.0 * camera_zoom, moon_rotation);
-                    let moon_uniforms = Uniforms {
-                        model_matrix: moon_matrix,
-                        time,
-                        planet_type: 3, // Moon shader
-                    };
-                    
-                    // Use asteroid model for moon
-                    if let Some((moon_vertex_array, _moon_texture)) = model_cache.get("assets/models/10464_Asteroid_v1_Iterations-2.obj") {
-                        render(&mut framebuffer, &moon_uniforms, moon_vertex_array);
-                    }
-                },
-                2 => {
-                    // Gigante Gaseoso - Renderizar anillos
-                    let ring_scale = 1.8;
-                    let ring_matrix = create_model_matrix(rotated_translation, body.scale * ring_scale * camera_zoom, Vector3::new(0.2, 0.0, 0.0));
-                    let ring_uniforms = Uniforms {
-                        model_matrix: ring_matrix,
-                        time,
-                        planet_type: 4, // Ring shader
-                    };
-                    
-                    // Generate and render ring geometry
-                    let ring_vertices = rings::generate_flat_ring(1.0, 1.5, 128);
-                    render(&mut framebuffer, &ring_uniforms, &ring_vertices);
-                },
-                _ => {}
+        for frame in 0..batch.frames {
+            time = frame as f32 * batch.step;
+            state.update_shake(batch.step);
+            state.update_cinematic(batch.step, &config.cinematic);
+            update_lod(&mut bodies, &state, &config, time, (framebuffer.width, framebuffer.height), batch.step);
+            if let Some(timeline) = timeline.as_mut() {
+                let body_names: Vec<&str> = bodies.iter().map(|body| body.name.as_str()).collect();
+                timeline.update(time, &mut state, &body_names);
             }
-        }
 
-        // Display framebuffer and text overlay
-        framebuffer.update_texture();
-        
-        let mut draw_handle = window.begin_drawing(&thread);
-        draw_handle.clear_background(Color::BLACK);
-        framebuffer.draw(&mut draw_handle);
-        
-        // Draw HUD - Top info
-        draw_handle.draw_text(&format!("FPS: {}", draw_handle.get_fps()), 10, 10, 20, Color::GREEN);
-        draw_handle.draw_text("Sistema Solar - 6 Cuerpos Celestes", 10, 40, 20, Color::WHITE);
-        draw_handle.draw_text(&format!("Time: {:.1}s", time), 10, 70, 15, Color::GRAY);
-        
-        // Show status with clear indicators
-        let status_rotate = if auto_rotate { "▶ ACTIVA" } else { "⏸ PAUSADA" };
-        let status_orbit = if auto_orbit { "▶ ACTIVA" } else { "⏸ PAUSADA" };
-        draw_handle.draw_text(&format!("Rotación: {} | Órbita: {}", status_rotate, status_orbit), 10, 100, 14, Color::YELLOW);
-        
-        // Draw HUD - Bottom controls
-        let y_offset = window_height as i32 - 150;
-        draw_handle.draw_text("CONTROLES:", 10, y_offset, 18, Color::YELLOW);
-        draw_handle.draw_text("SPACE: Pausar/Reanudar rotacion", 10, y_offset + 25, 14, Color::LIGHTGRAY);
-        draw_handle.draw_text("O: Pausar/Reanudar orbita", 10, y_offset + 45, 14, Color::LIGHTGRAY);
-        draw_handle.draw_text("Flechas: Mover camara | S/A: Zoom", 10, y_offset + 65, 14, Color::LIGHTGRAY);
-        draw_handle.draw_text("Q/W: Rot X | E/R: Rot Y | T/Y: Rot Z", 10, y_offset + 85, 14, Color::LIGHTGRAY);
-        draw_handle.draw_text(&format!("Zoom: {:.2}x", camera_zoom), 10, y_offset + 110, 14, Color::LIGHTGRAY);
+            let frame_start = Instant::now();
+            let (mut render_stats, _) =
+                render_scene(&mut framebuffer, &model_cache, &subdivided_meshes, &bodies, &state, &config, time, None);
+            let post_start = Instant::now();
+            post_chain.apply(&mut framebuffer);
+            render_stats.timings.post = post_start.elapsed();
+            render_stats.timings.frame = frame_start.elapsed();
+            if let Some(profile) = profile_csv.as_mut() {
+                let frame_time = render_stats.timings.frame.as_secs_f32();
+                if let Err(e) = profile.write_frame(frame as u64, time, frame_time, &render_stats) {
+                    eprintln!("✗ Failed to write render stats: {}", e);
+                    profile_csv = None;
+                }
+            }
 
-        thread::sleep(Duration::from_millis(16));
+            let path = Path::new(&batch.out_dir).join(format!("frame_{:05}.png", frame));
+            let path = path.to_string_lossy();
+            if let Err(e) = framebuffer.save_png(&path) {
+                eprintln!("✗ {}", e);
+                return;
+            }
+            if batch.depth {
+                let depth_path = Path::new(&batch.out_dir).join(format!("depth_{:05}.png", frame));
+                if let Err(e) = framebuffer.save_depth_png(&depth_path.to_string_lossy()) {
+                    eprintln!("✗ {}", e);
+                    return;
+                }
+            }
+            println!("[{}/{}] {}", frame + 1, batch.frames, path);
+        }
+        println!("✓ Rendered {} frames to {}", batch.frames, batch.out_dir);
+        finish_profile(profile_csv);
+        return;
     }
-}
-
-// Helper function to rotate a point around a center point
-fn rotate_point_around_center(point: Vector3, center: Vector3, rotation: Vector3) -> Vector3 {
-    // Translate to origin
-    let p = Vector3::new(point.x - center.x, point.y - center.y, point.z - center.z);
-    
-    // Apply rotation matrices
-    let (sin_x, cos_x) = rotation.x.sin_cos();
-    let (sin_y, cos_y) = rotation.y.sin_cos();
-    let (sin_z, cos_z) = rotation.z.sin_cos();
-    
-    // Rotate around X
-    let p = Vector3::new(p.x, p.y * cos_x - p.z * sin_x, p.y * sin_x + p.z * cos_x);
-    
-    // Rotate around Y
-    let p = Vector3::new(p.x * cos_y + p.z * sin_y, p.y, -p.x * sin_y + p.z * cos_y);
-    
-    // Rotate around Z
-    let p = Vector3::new(p.x * cos_z - p.y * sin_z, p.x * sin_z + p.y * cos_z, p.z);
-    
-    // Translate back
-    Vector3::new(p.x + center.x, p.y + center.y, p.z + center.z)
-}
 
-fn handle_input(
-    window: &mut RaylibHandle,
-    camera_offset: &mut Vector3,
-    camera_zoom: &mut f32,
-    system_rotation: &mut Vector3,
-    auto_rotate: &mut bool,
-    auto_orbit: &mut bool,
+    let (mut window, thread) = raylib::init()
+        .size(window_width, window_height)
+        .title("Rust Graphics - Renderer Example")
+        .log_level(TraceLogLevel::LOG_WARNING) // Suppress INFO messages
+        .build();
+    // Caps the frame rate; the simulation follows the real frame time either way
+    window.set_target_fps(config.target_fps);
+
+    // Initialize the texture inside the framebuffer
+    framebuffer
+        .init_texture(&mut window, &thread)
+        .expect("Failed to initialize the framebuffer texture");
+
+    // Shader gallery replaces the scene with every shader side by side, drawn once.
+    // It needs the window: the labels use raylib's default font, loaded with it
+    if let Some(gallery) = gallery {
+        render_shader_gallery(&mut framebuffer, gallery.time);
+        if let Some(out) = &gallery.out {
+            match framebuffer.save_png(out) {
+                Ok(()) => println!("✓ Saved shader gallery to {}", out),
+                Err(e) => eprintln!("✗ {}", e),
+            }
+            return;
+        }
+
+        show_still_frame(&mut window, &thread, &mut framebuffer);
+        return;
+    }
+
+    if depth_test_scene {
+        render_depth_test_scene(&mut framebuffer, time);
+        show_still_frame(&mut window, &thread, &mut framebuffer);
+        return;
+    }
+
+    // With --panel the frame is composed into a render texture drawn inside a UI panel
+    let mut panel_target = if panel_view {
+        Some(
+            window
+                .load_render_texture(&thread, framebuffer.width, framebuffer.height)
+                .expect("Failed to create the panel render texture"),
+        )
+    } else {
+        None
+    };
+
+    let mut frame_times = FrameTimeHistory::new(FRAME_TIME_SAMPLES);
+    let mut scrubber = TimeScrubber::default();
+    let scrubber_bar = scrubber_rect(window_width, window_height);
+    let mut frame_index: u64 = 0;
+
+    while !window.window_should_close() {
+        let camera_before = state.manual_camera();
+        handle_input(&mut window, &mut state, bodies.len(), (framebuffer.width, framebuffer.height));
+        if state.manual_camera() != camera_before {
+            state.cinematic_idle = 0.0;
+        }
+        frame_times.push(window.get_frame_time());
+
+        // Save the scene configuration (including the seed) with F5
+        if window.is_key_pressed(KeyboardKey::KEY_F5) {
+            config.hidden_bodies = bodies.iter().filter(|body| !body.visible).map(|body| body.name.clone()).collect();
+            config.hidden_satellites = bodies.iter().filter(|body| !body.satellites_visible).map(|body| body.name.clone()).collect();
+            config.near_plane = state.near_plane;
+            config.far_plane = state.far_plane;
+            config.log_depth = state.log_depth;
+            config.body_materials = bodies.iter().map(|body| (body.name.clone(), body.material)).collect();
+            match config.save() {
+                Ok(()) => println!("✓ Saved config: {}", config.path),
+                Err(e) => eprintln!("✗ Failed to save config {}: {}", config.path, e),
+            }
+        }
+
+        // Cycle the selected body through every shader with P (SHIFT+P goes backwards)
+        if window.is_key_pressed(KeyboardKey::KEY_P) {
+            let body = &mut bodies[state.selected_body];
+            let count = PlanetType::ALL.len();
+            let current = PlanetType::ALL
+                .iter()
+                .position(|planet_type| *planet_type == body.planet_type)
+                .unwrap_or(0);
+            let next = if window.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) {
+                (current + count - 1) % count
+            } else {
+                (current + 1) % count
+            };
+            body.planet_type = PlanetType::ALL[next];
+            legend = build_legend(&bodies);
+        }
+
+        // Tweak the selected body's material: CTRL+= / CTRL+- change the specular weight (SHIFT: diffuse),
+        // F9 toggles metallic. Without CTRL, = / - scale the simulation time
+        {
+            let material = &mut bodies[state.selected_body].material;
+            let weight = if window.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) { &mut material.diffuse } else { &mut material.specular };
+            let control = window.is_key_down(KeyboardKey::KEY_LEFT_CONTROL);
+            if control && window.is_key_pressed(KeyboardKey::KEY_EQUAL) {
+                *weight = (*weight + MATERIAL_STEP).min(MATERIAL_MAX);
+            }
+            if control && window.is_key_pressed(KeyboardKey::KEY_MINUS) {
+                *weight = (*weight - MATERIAL_STEP).max(0.0);
+            }
+            if window.is_key_pressed(KeyboardKey::KEY_F9) {
+                material.metallic = !material.metallic;
+            }
+        }
+
+        // Stretch or round the selected body's orbit with keypad * and /
+        {
+            let body = &mut bodies[state.selected_body];
+            if window.is_key_pressed(KeyboardKey::KEY_KP_MULTIPLY) {
+                body.eccentricity = (body.eccentricity + ECCENTRICITY_STEP).min(ECCENTRICITY_MAX);
+            }
+            if window.is_key_pressed(KeyboardKey::KEY_KP_DIVIDE) {
+                body.eccentricity = (body.eccentricity - ECCENTRICITY_STEP).max(0.0);
+            }
+        }
+
+        // Toggle body visibility with 1-9 (SHIFT+number toggles its moon/rings)
+        let number_keys = [
+            KeyboardKey::KEY_ONE, KeyboardKey::KEY_TWO, KeyboardKey::KEY_THREE,
+            KeyboardKey::KEY_FOUR, KeyboardKey::KEY_FIVE, KeyboardKey::KEY_SIX,
+            KeyboardKey::KEY_SEVEN, KeyboardKey::KEY_EIGHT, KeyboardKey::KEY_NINE,
+        ];
+        for (body, key) in bodies.iter_mut().zip(number_keys) {
+            if window.is_key_pressed(key) {
+                if window.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) {
+                    body.satellites_visible = !body.satellites_visible;
+                } else {
+                    body.visible = !body.visible;
+                }
+            }
+        }
+
+        // Update time; while the scrubber is dragged the mouse sets it directly instead
+        let mouse = window.get_mouse_position();
+        if window.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) && rect_contains(scrubber_bar, mouse) {
+            scrubber.dragging = true;
+        }
+        if !window.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT) {
+            scrubber.dragging = false;
+        }
+        let frame_delta = window.get_frame_time().min(MAX_FRAME_DELTA);
+        if scrubber.dragging {
+            time = scrubber.time_at((mouse.x - scrubber_bar.x) / scrubber_bar.width);
+        } else {
+            // Running backwards stops at the start of the run, where the scrubber bar begins
+            time = (time + frame_delta * state.current_time_scale()).max(0.0);
+        }
+        scrubber.track(time);
+        // The camera moves in real time, whatever the simulation speed
+        state.update_shake(frame_delta);
+        state.update_cinematic(frame_delta, &config.cinematic);
+        update_lod(&mut bodies, &state, &config, time, (framebuffer.width, framebuffer.height), frame_delta);
+
+        if let Some(timeline) = timeline.as_mut() {
+            let body_names: Vec<&str> = bodies.iter().map(|body| body.name.as_str()).collect();
+            timeline.update(time, &mut state, &body_names);
+        }
+
+        // F6 dumps the selected body's transformed mesh to an OBJ file
+        let mut exported_mesh = window.is_key_pressed(KeyboardKey::KEY_F6).then(Vec::new);
+
+        let frame_start = Instant::now();
+        let (mut render_stats, selected_label) =
+            render_scene(&mut framebuffer, &model_cache, &subdivided_meshes, &bodies, &state, &config, time, exported_mesh.as_mut());
+
+        if let Some(mesh) = exported_mesh {
+            let body = &bodies[state.selected_body];
+            let path = format!("export_{}.obj", body.name.to_lowercase().replace(' ', "_"));
+            if mesh.is_empty() {
+                eprintln!("✗ {} was not rendered this frame, nothing to export", body.name);
+            } else {
+                match obj::write_obj(&path, &mesh) {
+                    Ok(()) => println!("✓ Exported {} ({} triangles) to {}", body.name, mesh.len() / 3, path),
+                    Err(e) => eprintln!("✗ Failed to write {}: {}", path, e),
+                }
+            }
+        }
+
+        let post_start = Instant::now();
+        post_chain.apply(&mut framebuffer);
+        render_stats.timings.post = post_start.elapsed();
+
+        // Display framebuffer and text overlay
+        framebuffer
+            .update_texture()
+            .expect("Failed to upload the framebuffer texture");
+        if let Some(target) = panel_target.as_mut() {
+            framebuffer
+                .render_to_texture(&mut window, &thread, target)
+                .expect("Failed to render into the panel texture");
+        }
+        render_stats.timings.frame = frame_start.elapsed();
+        if let Some(profile) = profile_csv.as_mut() {
+            if let Err(e) = profile.write_frame(frame_index, time, window.get_frame_time(), &render_stats) {
+                eprintln!("✗ Failed to write render stats: {}", e);
+                profile_csv = None;
+            }
+        }
+        frame_index += 1;
+        
+        let mut draw_handle = window.begin_drawing(&thread);
+        match &panel_target {
+            Some(target) => {
+                draw_handle.clear_background(Color::new(30, 30, 36, 255));
+                draw_handle.draw_text("Vista 3D", PANEL_RECT.x as i32, PANEL_RECT.y as i32 - 22, 18, Color::LIGHTGRAY);
+                draw_render_texture(&mut draw_handle, target, PANEL_RECT);
+                draw_handle.draw_rectangle_lines_ex(PANEL_RECT, 2.0, Color::GRAY);
+            }
+            None => {
+                draw_handle.clear_background(Color::BLACK);
+                framebuffer.draw(&mut draw_handle);
+            }
+        }
+        
+        // Draw HUD - Top info
+        draw_handle.draw_text(&format!("FPS: {}", draw_handle.get_fps()), 10, 10, 20, Color::GREEN);
+        draw_handle.draw_text(&format!("Sistema Solar - {} Cuerpos Celestes", bodies.len()), 10, 40, 20, Color::WHITE);
+        let light_mode = if state.light_override { "manual" } else { "Sol" };
+        draw_handle.draw_text(
+            &format!(
+                "Time: {:.1}s | Seed: {} | Luz ({}): az {:.0}° el {:.0}°",
+                time,
+                config.scene_seed,
+                light_mode,
+                state.light_azimuth.to_degrees(),
+                state.light_elevation.to_degrees(),
+            ),
+            10,
+            70,
+            15,
+            Color::GRAY,
+        );
+        
+        // Show status with clear indicators
+        let status_rotate = if state.auto_rotate { "▶ ACTIVA" } else { "⏸ PAUSADA" };
+        let status_orbit = if state.auto_orbit { "▶ ACTIVA" } else { "⏸ PAUSADA" };
+        let status_relief = if state.normal_mapping { format!("{:.2}", state.normal_map_strength) } else { "OFF".to_string() };
+        draw_handle.draw_text(&format!("Rotación: {} | Órbita: {} | Relieve: {} | Puntos: {}", status_rotate, status_orbit, status_relief, state.point_cloud.name()), 10, 100, 14, Color::YELLOW);
+        let time_status = if state.time_paused {
+            "en pausa".to_string()
+        } else if state.time_scale < 0.0 {
+            format!("{:.2}x (reversa)", state.time_scale)
+        } else {
+            format!("{:.2}x", state.time_scale)
+        };
+        draw_handle.draw_text(&format!("Tiempo: {} | Vel. rotación: {:.2}x | Vel. órbita: {:.2}x | Anillos: {} | Ambiente: {:.2}", time_status, state.rotation_speed_mult, state.orbit_speed_mult, state.ring_blend.name(), state.ambient_level), 10, 140, 14, Color::YELLOW);
+
+        let selected_material = bodies[state.selected_body].material;
+        let selected_name = if bodies[state.selected_body].visible {
+            bodies[state.selected_body].name.clone()
+        } else {
+            format!("{} (oculto)", bodies[state.selected_body].name)
+        };
+        if state.isolate_body {
+            let satellites = if state.isolate_satellites { "con satélites" } else { "sin satélites" };
+            draw_handle.draw_text(&format!("Aislado: {} ({})", selected_name, satellites), 10, 120, 14, Color::ORANGE);
+        } else {
+            draw_handle.draw_text(
+                &format!(
+                    "Seleccionado: {} | Material: difusa {:.2}, especular {:.2}, {}",
+                    selected_name,
+                    selected_material.diffuse,
+                    selected_material.specular,
+                    if selected_material.metallic { "metálico" } else { "dieléctrico" },
+                ),
+                10,
+                120,
+                14,
+                Color::LIGHTGRAY,
+            );
+        }
+        
+        if state.map_view {
+            let planet_type = bodies[state.selected_body].planet_type;
+            draw_handle.draw_text(&format!("Mapa UV: {} (u 0-1 →, v 0-1 ↓)", planet_type.name()), 10, 160, 14, Color::SKYBLUE);
+        }
+
+        if state.stereo {
+            draw_handle.draw_text(
+                &format!("ESTÉREO lado a lado | Separación: {:.2} rad | Convergencia: {:.0} px", state.stereo_separation, state.stereo_convergence),
+                10,
+                180,
+                14,
+                Color::MAGENTA,
+            );
+        }
+
+        if state.cinematic {
+            let cinematic_status = if state.cinematic_idle < CINEMATIC_RESUME_DELAY { "en pausa (control manual)" } else { "activa" };
+            draw_handle.draw_text(&format!("Cámara cinemática: {}", cinematic_status), 10, 200, 14, Color::SKYBLUE);
+        }
+
+        // Current shader of the selected body, next to it
+        if let Some((position, radius)) = selected_label {
+            draw_handle.draw_text(
+                &format!("Shader: {}", bodies[state.selected_body].planet_type.name()),
+                (position.x + radius) as i32 + 4,
+                (position.y - radius) as i32,
+                14,
+                Color::SKYBLUE,
+            );
+        }
+
+        // Marker labels and distances (labels only without stereo, like the shader label)
+        if !state.isolate_body && !state.map_view {
+            if !state.stereo {
+                let camera = state.camera((framebuffer.width, framebuffer.height));
+                for (i, &marker) in state.markers.iter().enumerate() {
+                    if let Some(position) = camera.screen_position(marker) {
+                        draw_handle.draw_text(&format!("M{}", i + 1), position.x as i32 + 8, position.y as i32 - 14, 12, Color::SKYBLUE);
+                    }
+                }
+            }
+            draw_marker_panel(&mut draw_handle, &state.markers, &bodies, &state, time, 10, 222);
+        }
+
+        if state.show_frame_times {
+            draw_frame_time_graph(&mut draw_handle, &frame_times, window_width - 250, window_height - 110);
+        }
+
+        if state.show_stage_timings {
+            draw_stage_timings(&mut draw_handle, &render_stats.timings, window_width - 190, 200);
+        }
+
+        if state.show_legend {
+            draw_legend(&mut draw_handle, &legend, window_width - 190, 10);
+        }
+        
+        let scrubber_hovered = rect_contains(scrubber_bar, draw_handle.get_mouse_position());
+        draw_scrubber(&mut draw_handle, &scrubber, scrubber_bar, time, scrubber_hovered);
+
+        // Draw HUD - Bottom controls and render stats, wrapped to the window width and stacked up
+        // above the scrubber
+        let depth_range = match render_stats.depth_range {
+            Some((min, max)) => format!("{:.3} a {:.3}", min, max),
+            None => "-".to_string(),
+        };
+        let stats = format!(
+            "Zoom: {:.2}x | Recorte: near {:.0} far {:.0} | Profundidad{}: {} | {} frag. recortados | {} tri. antes del plano near | {} tri. ocultos | Sombreados: {} de {} frag.",
+            state.camera_zoom,
+            state.near_plane,
+            state.far_plane,
+            if state.log_depth { " log" } else { "" },
+            depth_range,
+            render_stats.clipped_fragments,
+            render_stats.behind_camera_triangles,
+            render_stats.occluded_triangles,
+            render_stats.shaded_fragments,
+            render_stats.fragments,
+        );
+        let hud_width = window_width - 2 * HUD_MARGIN;
+        let measure = |text: &str| draw_handle.measure_text(text, HUD_FONT_SIZE);
+        let control_lines: Vec<String> = CONTROLS_HELP.iter().flat_map(|line| wrap_hud_line(line, hud_width, measure)).collect();
+        let stats_lines = wrap_hud_line(&stats, hud_width, measure);
+
+        let stats_y = window_height - 40 - (stats_lines.len() as i32 - 1) * HUD_LINE_HEIGHT;
+        let controls_y = stats_y - 5 - control_lines.len() as i32 * HUD_LINE_HEIGHT;
+        draw_handle.draw_text("CONTROLES:", HUD_MARGIN, controls_y - 25, 18, Color::YELLOW);
+        for (i, line) in control_lines.iter().enumerate() {
+            draw_handle.draw_text(line, HUD_MARGIN, controls_y + i as i32 * HUD_LINE_HEIGHT, HUD_FONT_SIZE, Color::LIGHTGRAY);
+        }
+        for (i, line) in stats_lines.iter().enumerate() {
+            draw_handle.draw_text(line, HUD_MARGIN, stats_y + i as i32 * HUD_LINE_HEIGHT, HUD_FONT_SIZE, Color::LIGHTGRAY);
+        }
+    }
+
+    finish_profile(profile_csv);
+}
+
+/// Splits a HUD line into lines at most `max_width` pixels wide, as `measure` sizes them, breaking
+/// only between its " | "-separated items. An item wider than `max_width` gets a line of its own.
+fn wrap_hud_line(text: &str, max_width: i32, measure: impl Fn(&str) -> i32) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for item in text.split(" | ") {
+        match lines.last_mut() {
+            Some(line) if measure(&format!("{} | {}", line, item)) <= max_width => {
+                line.push_str(" | ");
+                line.push_str(item);
+            }
+            _ => lines.push(item.to_string()),
+        }
+    }
+    lines
+}
+
+/// Flushes the `--profile-csv` log, if one is open
+fn finish_profile(profile_csv: Option<ProfileCsv>) {
+    if let Some(profile) = profile_csv {
+        match profile.finish() {
+            Ok(()) => println!("✓ Render stats saved"),
+            Err(e) => eprintln!("✗ Failed to write render stats: {}", e),
+        }
+    }
+}
+
+/// Renders the 3D scene (background, bodies, satellites, gizmo) into the framebuffer, for one
+/// or both stereo eyes. Returns the frame's render stats and the screen position and radius of
+/// the selected body, for its shader label. When `mesh_capture` is given it receives the
+/// selected body's transformed triangle list (first eye only), for OBJ export.
+#[allow(clippy::too_many_arguments)]
+fn render_scene(
+    framebuffer: &mut Framebuffer,
+    model_cache: &HashMap<String, Obj>,
+    subdivided_meshes: &HashMap<(String, u32), (Vec<Vertex>, Vec<u32>)>,
+    bodies: &[CelestialBody],
+    state: &AppState,
+    config: &Config,
+    time: f32,
+    mut mesh_capture: Option<&mut Vec<Vertex>>,
+) -> (RenderStats, Option<(Vector3, f32)>) {
+    // Isolation mode renders against a plain background
+    framebuffer.set_stars_visible(!state.isolate_body && !state.map_view);
+    let mut render_stats = RenderStats::default();
+    let shake = state.shake_offset(time);
+    let backface_tint = if state.show_backfaces { Some(config.backface_color) } else { None };
+    // The back-face debug view needs the back faces, so it turns culling off
+    let cull_backfaces = config.cull_backfaces && !state.show_backfaces;
+    let ambient = Vector3::new(state.ambient_level, state.ambient_level, state.ambient_level);
+    // Bodies are lit from the main sun unless the manual light (Z) is on
+    let manual_light = state.light_override.then(|| light_direction(state.light_azimuth, state.light_elevation));
+    // The near and far planes sit at NDC depth -1 and 1
+    let clip_planes = (-1.0, 1.0);
+    let log_depth_far = state.log_depth.then_some(state.far_plane);
+
+    // Screen position and radius of the selected body, for its shader label
+    let mut selected_label: Option<(Vector3, f32)> = None;
+
+    // Stereo renders the scene once per eye (-1 left, +1 right) and packs both side by side
+    let eyes: &[f32] = if state.stereo { &[-1.0, 1.0] } else { &[0.0] };
+    let mut left_eye_frame = None;
+    // Transform hierarchy, rebuilt for every eye
+    let mut scene = SceneGraph::new();
+    let viewport = (framebuffer.width, framebuffer.height);
+    // The system center is the world origin
+    let center = Vector3::zero();
+    // An isolated body is framed by pointing the camera at it from a distance it fits at
+    let (target, distance) = if state.isolate_body {
+        let body = &bodies[state.selected_body];
+        (system_position(body, bodies, time, state), isolation_distance(body, state, viewport))
+    } else {
+        (state.camera_target, state.camera_distance(viewport))
+    };
+    for &eye in eyes {
+        framebuffer.clear();
+
+        // Each eye orbits the target by half the separation angle; shifting the images apart
+        // moves the convergence (zero-parallax) point
+        let eye_rotation = state.camera_rotation + Vector3::new(0.0, eye * state.stereo_separation / 2.0, 0.0);
+        let shift = shake + Vector2::new(-eye * state.stereo_convergence / 2.0, 0.0);
+        let camera = Camera::orbit(target, eye_rotation, distance, shift, viewport, state.near_plane, state.far_plane);
+        let (view_matrix, projection_matrix) = (camera.view, camera.projection);
+
+        // Dynamic lights collected while rendering (affect bodies drawn afterwards)
+        let mut point_lights: Vec<PointLight> = Vec::new();
+        // With the depth prepass or depth peeling, draws are rasterized in the loop and shaded after it
+        let mut deferred = (config.depth_prepass || config.depth_peels > 0)
+            .then(|| DeferredDraws::new(config.depth_prepass, config.depth_peels as usize));
+        scene.clear();
+
+        // Shader map view replaces the scene with the selected body's unwrapped shader
+        if state.map_view {
+            draw_shader_map(framebuffer, bodies[state.selected_body].planet_type, time);
+        }
+
+        // Orbit paths go under the bodies, so a planet is never crossed by its own orbit
+        if state.show_orbits && !state.isolate_body && !state.map_view {
+            for body in bodies.iter().filter(|body| body.visible) {
+                draw_orbit_path(framebuffer, bodies, body, &camera, state, config, time);
+            }
+        }
+
+        // Planets orbit the stars' barycenter (the system center with a single sun)
+        let barycenter = star_barycenter(bodies, center, time, state);
+        // Every star's position and light color, the sun first
+        let stars: Vec<(Vector3, Vector3)> = bodies
+            .iter()
+            .filter(|body| body.star)
+            .map(|body| (orbit_position(body, center, time, state), body.light_color))
+            .collect();
+
+        // Vertices of the bodies shown as a point cloud, drawn once every body is through the vertex shader
+        let mut cloud_points: Vec<Vector3> = Vec::new();
+        // Sun halos as (screen center and depth, radius in pixels), drawn after the opaque bodies
+        let mut sun_halos: Vec<(Vector3, f32)> = Vec::new();
+
+        // Render all celestial bodies
+        for (index, body) in bodies.iter().enumerate() {
+            if state.map_view || !body.visible || (state.isolate_body && index != state.selected_body) {
+                continue;
+            }
+
+            // Calculate position
+            let spin_angle = if state.auto_rotate {
+                time * body.rotation_speed * state.rotation_speed_mult
+            } else {
+                0.0
+            };
+
+            let satellites_shown = body.satellites_visible && !(state.isolate_body && !state.isolate_satellites);
+            let as_points = state.point_cloud.applies_to(index, state.selected_body);
+            let orbit_center = if body.star { center } else { barycenter };
+            let body_translation = orbit_position(body, orbit_center, time, state);
+
+            let radius = body.scale * BODY_SIZE;
+
+            // Light comes from where the stars are, in world space like the normals it lights
+            let star_lights = star_lights_at(&stars, body_translation, manual_light);
+            let to_camera = camera.eye - body_translation;
+            let view_dir = if to_camera.length() > 1e-3 { to_camera.normalized() } else { DEFAULT_VIEW_DIR };
+
+            // The halo and the label live on screen: the body's projected center and radius in
+            // pixels (nothing when it is behind the camera)
+            let on_screen = camera
+                .screen_position(body_translation)
+                .map(|position| (position, radius * camera.pixels_per_unit(body_translation)));
+
+            if let (Some(label), true) = (on_screen, index == state.selected_body && !state.stereo) {
+                selected_label = Some(label);
+            }
+
+            // Gas giant lightning strikes flash as a brief point light, in world space like the
+            // surfaces it lights (its rings and the giant itself)
+            if state.lightning_light && body.planet_type == PlanetType::GasGiant {
+                point_lights.extend(gas_giant_lightning_flash(body_translation, radius, time));
+            }
+
+            // The halo is drawn once the bodies are, at the sun's center depth, so the sun and
+            // the planets in front of it hide it wherever they cover it
+            if let (Some((sun_center, screen_radius)), true) = (on_screen, state.sun_halo && body.planet_type == PlanetType::Sun) {
+                let depth = match log_depth_far {
+                    Some(far) => log_depth(camera.clip(body_translation).w, far),
+                    None => sun_center.z,
+                };
+                sun_halos.push((Vector3::new(sun_center.x, sun_center.y, depth), screen_radius * state.sun_halo_size));
+            }
+
+            // The body's frame carries its position and its orbit's inclination, so moons orbit in
+            // the planet's own orbital plane; the axis frame tilts it onto the spin axis, and the
+            // body mesh and its rings hang from that, so the rings stay in its equator
+            let body_frame = scene.add(
+                orbital_plane(body.inclination, body.ascending_node) * create_model_matrix(body_translation, 1.0, Vector3::zero()),
+                None,
+            );
+            let axis_frame = scene.add(rotation_between(SPIN_AXIS_UP, body.spin_axis), Some(body_frame));
+            let body_node = scene.add(
+                create_model_matrix(Vector3::zero(), radius, Vector3::zero()) * axis_angle_matrix(SPIN_AXIS_UP, spin_angle),
+                Some(axis_frame),
+            );
+            let model_matrix = scene.world_matrix(body_node);
+            let uniforms = Uniforms {
+                model_matrix,
+                normal_transform: normal_matrix(&model_matrix),
+                view_matrix,
+                projection_matrix,
+                viewport,
+                time,
+                planet_type: body.planet_type,
+                point_lights: point_lights.clone(),
+                blend: None,
+                backface_tint,
+                animation: body.animation,
+                star_lights: star_lights.clone(),
+                view_dir,
+                clip_planes,
+                log_depth_far,
+                ambient,
+                normal_map: normal_map(model_cache, &body.model_path, state),
+                emissive_map: emissive_map(model_cache, &body.model_path),
+                disk: config.accretion_disk,
+                material: body.material,
+                ring_shadow: ring_shadow(body, &scene.world_matrix(axis_frame), satellites_shown, config),
+                screen_door: None,
+                cull_backfaces,
+            };
+
+            // The accretion disk replaces the model with a torus whose glow adds onto the scene
+            if body.planet_type == PlanetType::AccretionDisk {
+                let disk_node = scene.add(
+                    create_model_matrix(Vector3::zero(), radius, Vector3::new(DISK_TILT, 0.0, 0.0)),
+                    Some(body_frame),
+                );
+                let model_matrix = scene.world_matrix(disk_node);
+                let disk_uniforms = Uniforms {
+                    model_matrix,
+                    normal_transform: normal_matrix(&model_matrix),
+                    blend: Some((BlendMode::Additive, 1.0)),
+                    normal_map: None,
+                    emissive_map: None,
+                    ring_shadow: None,
+                    ..uniforms
+                };
+                if as_points {
+                    collect_points(&disk_uniforms, &ACCRETION_DISK, &mut cloud_points, &mut render_stats);
+                } else {
+                    render(framebuffer, &disk_uniforms, &ACCRETION_DISK, &mut render_stats, deferred.as_mut());
+                }
+            } else if let Some((vertices, indices)) = model_mesh(model_cache, subdivided_meshes, &body.model_path, body.subdivision) {
+                if as_points {
+                    collect_points(&uniforms, vertices, &mut cloud_points, &mut render_stats);
+                } else {
+                    // Small bodies use the low-detail sphere, dithering between it and the model while they switch
+                    for (lod, screen_door) in body.lod.layers() {
+                        let (vertices, indices) = match lod {
+                            Lod::Full => (vertices, indices),
+                            Lod::Low => (LOW_DETAIL_MESH.0.as_slice(), LOW_DETAIL_MESH.1.as_slice()),
+                        };
+                        let uniforms = Uniforms { screen_door, ..uniforms.clone() };
+                        render_indexed(framebuffer, &uniforms, vertices, indices, &mut render_stats, deferred.as_mut());
+                    }
+                }
+
+                if index == state.selected_body {
+                    if let Some(capture) = mesh_capture.take() {
+                        capture.extend(indices.iter().map(|&i| vertex_shader(&vertices[i as usize], &uniforms)));
+                    }
+                }
+            }
+
+            if !satellites_shown {
+                continue;
+            }
+
+            // Renderizar las lunas del planeta (la Luna de la Tierra)
+            for moon in &body.moons {
+                let moon_orbit_angle = time * moon.orbit_speed * state.orbit_speed_mult;
+                let moon_distance = moon.orbit_radius;
+                let moon_offset = Vector3::new(
+                    moon_orbit_angle.cos() * moon_distance,
+                    moon_orbit_angle.sin() * moon_distance,
+                    0.0,
+                );
+
+                let moon_rotation = if state.auto_rotate {
+                    Vector3::new(0.0, time * moon.rotation_speed * state.rotation_speed_mult, 0.0)
+                } else {
+                    Vector3::new(0.0, 0.0, 0.0)
+                };
+
+                let moon_orbit = scene.add(create_model_matrix(moon_offset, 1.0, Vector3::zero()), Some(body_frame));
+                let moon_node = scene.add(create_model_matrix(Vector3::zero(), moon.scale * BODY_SIZE, moon_rotation), Some(moon_orbit));
+                let model_matrix = scene.world_matrix(moon_node);
+                let moon_uniforms = Uniforms {
+                    model_matrix,
+                    normal_transform: normal_matrix(&model_matrix),
+                    view_matrix,
+                    projection_matrix,
+                    viewport,
+                    time,
+                    planet_type: moon.planet_type,
+                    point_lights: point_lights.clone(),
+                    blend: None,
+                    backface_tint,
+                    animation: AnimationSpeeds::default(),
+                    star_lights: star_lights.clone(),
+                    view_dir,
+                    clip_planes,
+                    log_depth_far,
+                    ambient,
+                    normal_map: normal_map(model_cache, MOON_MODEL_PATH, state),
+                    emissive_map: emissive_map(model_cache, MOON_MODEL_PATH),
+                    disk: config.accretion_disk,
+                    material: MOON_MATERIAL,
+                    ring_shadow: None,
+                    screen_door: None,
+                    cull_backfaces,
+                };
+
+                // Use asteroid model for moons
+                if let Some((vertices, indices)) = model_mesh(model_cache, subdivided_meshes, MOON_MODEL_PATH, MOON_SUBDIVISION) {
+                    if as_points {
+                        collect_points(&moon_uniforms, vertices, &mut cloud_points, &mut render_stats);
+                    } else {
+                        render_indexed(framebuffer, &moon_uniforms, vertices, indices, &mut render_stats, deferred.as_mut());
+                    }
+                }
+            }
+
+            // Renderizar anillos específicos para algunos planetas
+            if body.planet_type == PlanetType::GasGiant {
+                // Gigante Gaseoso - Renderizar anillos
+                let ring_node = scene.add(
+                    create_model_matrix(Vector3::zero(), radius * RING_SCALE, Vector3::zero()),
+                    Some(axis_frame),
+                );
+                let model_matrix = scene.world_matrix(ring_node);
+                let ring_uniforms = Uniforms {
+                    model_matrix,
+                    normal_transform: normal_matrix(&model_matrix),
+                    view_matrix,
+                    projection_matrix,
+                    viewport,
+                    time,
+                    planet_type: PlanetType::Ring,
+                    point_lights: point_lights.clone(),
+                    blend: Some((state.ring_blend, RING_ALPHA)),
+                    backface_tint,
+                    animation: AnimationSpeeds::default(),
+                    star_lights: star_lights.clone(),
+                    view_dir,
+                    clip_planes,
+                    log_depth_far,
+                    ambient,
+                    normal_map: None,
+                    emissive_map: None,
+                    disk: config.accretion_disk,
+                    material: SurfaceMaterial::default(),
+                    ring_shadow: None,
+                    screen_door: None,
+                    cull_backfaces: false, // Flat and seen from both sides
+                };
+            
+                if as_points {
+                    collect_points(&ring_uniforms, &GAS_GIANT_RING, &mut cloud_points, &mut render_stats);
+                } else {
+                    render(framebuffer, &ring_uniforms, &GAS_GIANT_RING, &mut render_stats, deferred.as_mut());
+                }
+            }
+        }
+
+        if let Some(deferred) = deferred {
+            deferred.finish(framebuffer, &mut render_stats);
+        }
+        draw_point_cloud(framebuffer, &cloud_points);
+
+        for (center, radius) in sun_halos {
+            framebuffer.soft_point_with_depth(center.x, center.y, center.z, radius, state.sun_halo_color);
+        }
+
+        // The grid is tested against the depth the bodies left, so those in front of the plane
+        // hide it and those behind it show through between its lines
+        if state.show_grid && !state.isolate_body && !state.map_view {
+            draw_grid(framebuffer, &camera, config, log_depth_far, time);
+        }
+
+        let project = |point: Vector3| project_point(point, &camera, time);
+
+        // The orbital geometry is depth-tested like the grid
+        if state.orbit_geometry != OrbitGeometryMode::Off && !state.isolate_body && !state.map_view {
+            for body in bodies.iter().filter(|body| body.visible && body.orbit_radius > 0.0) {
+                let orbit_center = if body.star { center } else { barycenter };
+                draw_orbit_geometry(framebuffer, body, orbit_center, &project, state.orbit_geometry, config, log_depth_far);
+            }
+        }
+
+        // Velocity arrows go over the bodies, like the gizmo
+        if state.show_velocities && !state.isolate_body && !state.map_view {
+            for body in bodies.iter().filter(|body| body.visible) {
+                let orbit_center = if body.star { center } else { barycenter };
+                let position = orbit_position(body, orbit_center, time, state);
+                let velocity = body_velocity(bodies, body, center, time, state);
+                draw_velocity_arrow(framebuffer, position, velocity, &project, config);
+            }
+        }
+
+        // Orientation gizmo (meaningless while a single body is isolated)
+        if state.show_axes && !state.isolate_body && !state.map_view {
+            draw_axes_gizmo(framebuffer, &camera, config.line_thickness, time);
+        }
+
+        // Probe markers live in system space, so they are hidden while a body is isolated
+        if !state.isolate_body && !state.map_view {
+            for &marker in &state.markers {
+                if let Some(position) = camera.screen_position(marker) {
+                    draw_marker(framebuffer, position);
+                }
+            }
+        }
+
+        if eye < 0.0 {
+            left_eye_frame = Some(framebuffer.snapshot());
+        }
+    }
+    if let Some(left_eye_frame) = left_eye_frame {
+        framebuffer.compose_side_by_side(&left_eye_frame);
+    }
+
+    (render_stats, selected_label)
+}
+
+/// Shadow of a gas giant's rings on the planet, while the rings are drawn. The rings lie flat in
+/// the axis frame the planet spins in, so their plane's normal is that frame's up, in world space.
+fn ring_shadow(body: &CelestialBody, axis_frame: &Matrix, satellites_shown: bool, config: &Config) -> Option<RingShadow> {
+    if body.planet_type != PlanetType::GasGiant || !satellites_shown {
+        return None;
+    }
+    Some(RingShadow {
+        plane_normal: transform_direction(axis_frame, SPIN_AXIS_UP).normalized(),
+        inner_radius: RING_SCALE * RING_INNER_RADIUS,
+        outer_radius: RING_SCALE * RING_OUTER_RADIUS,
+        softness: config.ring_shadow_softness,
+        darkness: config.ring_shadow_darkness,
+    })
+}
+
+/// Vertex shader stage only: appends the screen position (and depth) of every vertex that can be
+/// projected, for the point cloud view. Triangles are never assembled.
+fn collect_points(uniforms: &Uniforms, vertices: &[Vertex], points: &mut Vec<Vector3>, stats: &mut RenderStats) {
+    let stage_start = Instant::now();
+    points.extend(
+        vertices
+            .iter()
+            .map(|vertex| vertex_shader(vertex, uniforms))
+            .filter(|vertex| !vertex.behind_camera)
+            .map(|vertex| vertex.transformed_position),
+    );
+    stats.timings.vertex += stage_start.elapsed();
+}
+
+/// Draws each point as a small soft dot colored by depth across the whole cloud, from
+/// `POINT_CLOUD_NEAR_COLOR` (smallest depth, nearest) to `POINT_CLOUD_FAR_COLOR`.
+/// Dots add up, so duplicated vertices show up brighter.
+fn draw_point_cloud(framebuffer: &mut Framebuffer, points: &[Vector3]) {
+    let (min, max) = points
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), point| (min.min(point.z), max.max(point.z)));
+    let span = (max - min).max(f32::EPSILON);
+    for point in points {
+        let t = (point.z - min) / span;
+        let color = POINT_CLOUD_NEAR_COLOR * (1.0 - t) + POINT_CLOUD_FAR_COLOR * t;
+        framebuffer.soft_point(point.x, point.y, POINT_CLOUD_RADIUS, color);
+    }
+}
+
+/// Vertices and indices of a model at the given subdivision level, falling back to the
+/// original mesh when no subdivided copy was built
+fn model_mesh<'a>(
+    model_cache: &'a HashMap<String, Obj>,
+    subdivided_meshes: &'a HashMap<(String, u32), (Vec<Vertex>, Vec<u32>)>,
+    model_path: &str,
+    level: u32,
+) -> Option<(&'a [Vertex], &'a [u32])> {
+    if let Some((vertices, indices)) = subdivided_meshes.get(&(model_path.to_string(), level)) {
+        return Some((vertices, indices));
+    }
+    model_cache
+        .get(model_path)
+        .map(|model| (model.vertices.as_slice(), model.indices.as_slice()))
+}
+
+/// Normal map of a cached model with the current strength, if normal mapping is on and the
+/// model's material has one
+fn normal_map<'a>(model_cache: &'a HashMap<String, Obj>, model_path: &str, state: &AppState) -> Option<(&'a Texture, &'a Material, f32)> {
+    if !state.normal_mapping {
+        return None;
+    }
+    let model = model_cache.get(model_path)?;
+    Some((model.get_normal_texture().as_ref()?, model.get_normal_material()?, state.normal_map_strength))
+}
+
+/// Emissive map of a cached model, if its material has one
+fn emissive_map<'a>(model_cache: &'a HashMap<String, Obj>, model_path: &str) -> Option<(&'a Texture, &'a Material)> {
+    let model = model_cache.get(model_path)?;
+    Some((model.get_emissive_texture().as_ref()?, model.get_emissive_material()?))
+}
+
+/// Legend entries: every shader in use (bodies plus the moon/ring satellites), in enum order
+fn build_legend(bodies: &[CelestialBody]) -> Vec<(PlanetType, Vector3)> {
+    PlanetType::ALL
+        .iter()
+        .copied()
+        .filter(|planet_type| {
+            bodies.iter().any(|body| {
+                body.planet_type == *planet_type
+                    || body.moons.iter().any(|moon| moon.planet_type == *planet_type)
+                    || (body.planet_type == PlanetType::GasGiant && *planet_type == PlanetType::Ring)
+            })
+        })
+        .map(|planet_type| (planet_type, representative_color(planet_type)))
+        .collect()
+}
+
+/// Draws a bar per recent frame (oldest on the left) plus min/max/avg in milliseconds.
+/// Bars are capped at `FRAME_TIME_GRAPH_MAX`; frames over the cap are drawn in red.
+fn draw_frame_time_graph(draw_handle: &mut RaylibDrawHandle, history: &FrameTimeHistory, x: i32, y: i32) {
+    let width = history.capacity() as i32;
+    let height = 60;
+    draw_handle.draw_rectangle(x, y, width + 110, height + 40, Color::new(0, 0, 0, 170));
+
+    let graph_top = y + 30;
+    let graph_bottom = graph_top + height;
+    for (i, frame_time) in history.iter().enumerate() {
+        let bar = ((frame_time / FRAME_TIME_GRAPH_MAX).min(1.0) * height as f32) as i32;
+        let color = if frame_time > FRAME_TIME_GRAPH_MAX {
+            Color::RED
+        } else if frame_time > 1.0 / 30.0 {
+            Color::ORANGE
+        } else {
+            Color::GREEN
+        };
+        let bar_x = x + 5 + i as i32;
+        draw_handle.draw_line(bar_x, graph_bottom, bar_x, graph_bottom - bar, color);
+    }
+
+    // 60 FPS reference line
+    let target_y = graph_bottom - ((1.0 / 60.0) / FRAME_TIME_GRAPH_MAX * height as f32) as i32;
+    draw_handle.draw_line(x + 5, target_y, x + 5 + width, target_y, Color::GRAY);
+
+    if let Some((min, max, avg)) = history.stats() {
+        draw_handle.draw_text("FRAME TIME (ms)", x + 5, y + 8, 14, Color::YELLOW);
+        draw_handle.draw_text(&format!("min {:.1}", min * 1000.0), x + width + 12, graph_top, 12, Color::LIGHTGRAY);
+        draw_handle.draw_text(&format!("max {:.1}", max * 1000.0), x + width + 12, graph_top + 18, 12, Color::LIGHTGRAY);
+        draw_handle.draw_text(&format!("avg {:.1}", avg * 1000.0), x + width + 12, graph_top + 36, 12, Color::LIGHTGRAY);
+    }
+}
+
+/// Rectangle of the timeline scrubber bar in window coordinates
+fn scrubber_rect(window_width: i32, window_height: i32) -> Rectangle {
+    Rectangle::new(
+        SCRUBBER_MARGIN as f32,
+        (window_height - SCRUBBER_MARGIN - SCRUBBER_HEIGHT) as f32,
+        (window_width - 2 * SCRUBBER_MARGIN) as f32,
+        SCRUBBER_HEIGHT as f32,
+    )
+}
+
+fn rect_contains(rect: Rectangle, point: Vector2) -> bool {
+    point.x >= rect.x && point.x <= rect.x + rect.width && point.y >= rect.y && point.y <= rect.y + rect.height
+}
+
+/// Draws the timeline scrubber: the elapsed part of the bar filled, a handle at the current
+/// time and the time range at the right end. Highlighted while hovered or dragged.
+fn draw_scrubber(draw_handle: &mut RaylibDrawHandle, scrubber: &TimeScrubber, bar: Rectangle, time: f32, hovered: bool) {
+    let active = hovered || scrubber.dragging;
+    let (x, y, width, height) = (bar.x as i32, bar.y as i32, bar.width as i32, bar.height as i32);
+    draw_handle.draw_rectangle(x, y, width, height, Color::new(0, 0, 0, 170));
+
+    let handle_x = x + (scrubber.fraction(time) * bar.width) as i32;
+    let fill = if active { Color::SKYBLUE } else { Color::new(80, 120, 160, 200) };
+    draw_handle.draw_rectangle(x, y, handle_x - x, height, fill);
+    draw_handle.draw_rectangle(handle_x - 2, y - 3, 4, height + 6, if active { Color::WHITE } else { Color::LIGHTGRAY });
+    draw_handle.draw_rectangle_lines(x, y, width, height, Color::GRAY);
+
+    if active {
+        let label = format!("{:.1}s / {:.0}s", time, scrubber.range());
+        draw_handle.draw_rectangle(x + width - 96, y - 18, 96, 16, Color::new(0, 0, 0, 200));
+        draw_handle.draw_text(&label, x + width - 90, y - 16, 12, Color::SKYBLUE);
+    }
+}
+
+/// Draws the per-stage breakdown of the last frame's software render, in milliseconds
+fn draw_stage_timings(draw_handle: &mut RaylibDrawHandle, timings: &StageTimings, x: i32, y: i32) {
+    let rows = timings.rows();
+    let row_height = 18;
+    draw_handle.draw_rectangle(x, y, 180, 50 + rows.len() as i32 * row_height, Color::new(0, 0, 0, 170));
+    draw_handle.draw_text("ETAPAS (ms)", x + 10, y + 8, 14, Color::YELLOW);
+
+    for (i, (label, duration)) in rows.iter().enumerate() {
+        let row_y = y + 28 + i as i32 * row_height;
+        draw_handle.draw_text(label, x + 10, row_y, 12, Color::LIGHTGRAY);
+        draw_handle.draw_text(&format!("{:.2}", duration.as_secs_f32() * 1000.0), x + 120, row_y, 12, Color::LIGHTGRAY);
+    }
+
+    let total_y = y + 32 + rows.len() as i32 * row_height;
+    draw_handle.draw_text("Total", x + 10, total_y, 12, Color::WHITE);
+    draw_handle.draw_text(&format!("{:.2}", timings.frame.as_secs_f32() * 1000.0), x + 120, total_y, 12, Color::WHITE);
+}
+
+/// Draws the legend panel: one color swatch and shader name per entry
+fn draw_legend(draw_handle: &mut RaylibDrawHandle, entries: &[(PlanetType, Vector3)], x: i32, y: i32) {
+    let row_height = 20;
+    let height = 30 + entries.len() as i32 * row_height;
+    draw_handle.draw_rectangle(x, y, 180, height, Color::new(0, 0, 0, 170));
+    draw_handle.draw_text("LEYENDA", x + 10, y + 8, 14, Color::YELLOW);
+
+    for (i, (planet_type, color)) in entries.iter().enumerate() {
+        let row_y = y + 28 + i as i32 * row_height;
+        let swatch = Color::new(
+            (color.x.clamp(0.0, 1.0) * 255.0) as u8,
+            (color.y.clamp(0.0, 1.0) * 255.0) as u8,
+            (color.z.clamp(0.0, 1.0) * 255.0) as u8,
+            255,
+        );
+        draw_handle.draw_rectangle(x + 10, row_y, 14, 14, swatch);
+        draw_handle.draw_text(&format!("{} ({})", planet_type.name(), planet_type.id()), x + 32, row_y, 14, Color::LIGHTGRAY);
+    }
+}
+
+/// Renders a shader unlit over a flat 2:1 quad whose UVs span 0-1 (equirectangular),
+/// so seams and poles can be inspected directly. Shaded in 2x2 pixel blocks to stay interactive.
+fn draw_shader_map(framebuffer: &mut Framebuffer, planet_type: PlanetType, time: f32) {
+    const BLOCK: i32 = 2;
+
+    let width = (framebuffer.width as i32 - 40).min((framebuffer.height as i32 - 80) * 2);
+    let height = width / 2;
+    let left = (framebuffer.width as i32 - width) / 2;
+    let top = (framebuffer.height as i32 - height) / 2;
+
+    for by in (0..height).step_by(BLOCK as usize) {
+        for bx in (0..width).step_by(BLOCK as usize) {
+            let uv = Vector2::new(
+                (bx as f32 + BLOCK as f32 * 0.5) / width as f32,
+                (by as f32 + BLOCK as f32 * 0.5) / height as f32,
+            );
+            let color = shade_unlit(planet_type, uv, time);
+            for y in by..(by + BLOCK).min(height) {
+                for x in bx..(bx + BLOCK).min(width) {
+                    framebuffer.point(left + x, top + y, color);
+                }
+            }
+        }
+    }
+}
+
+/// Presents a frame rendered once (gallery, test scenes) until the window is closed
+fn show_still_frame(window: &mut RaylibHandle, thread: &RaylibThread, framebuffer: &mut Framebuffer) {
+    framebuffer
+        .update_texture()
+        .expect("Failed to upload the framebuffer texture");
+    while !window.window_should_close() {
+        let mut draw_handle = window.begin_drawing(thread);
+        draw_handle.clear_background(Color::BLACK);
+        framebuffer.draw(&mut draw_handle);
+    }
+}
+
+/// Depth test check (`--depth-test`): a small moon between the camera and a large gas giant,
+/// drawn nearest first. With the depth test the moon stays whole in front of the giant;
+/// without it the giant, drawn last, would cover it.
+fn render_depth_test_scene(framebuffer: &mut Framebuffer, time: f32) {
+    framebuffer.set_stars_visible(false);
+    framebuffer.clear();
+    let spheres = depth_test_spheres(framebuffer);
+    render_spheres(framebuffer, &spheres, time);
+}
+
+/// Bodies of the depth test scene as (shader, center, radius), in drawing order
+fn depth_test_spheres(framebuffer: &Framebuffer) -> [(PlanetType, Vector3, f32); 2] {
+    let center = Vector3::new(framebuffer.width as f32 / 2.0, framebuffer.height as f32 / 2.0, 0.0);
+    [
+        (PlanetType::Moon, center + Vector3::new(70.0, 30.0, -300.0), 70.0), // Near (smaller depth)
+        (PlanetType::GasGiant, center, 200.0),                               // Far, drawn over it
+    ]
+}
+
+/// Lit spheres laid out in pixels (see `Camera::screen`), depth tested, in the given order
+fn render_spheres(framebuffer: &mut Framebuffer, spheres: &[(PlanetType, Vector3, f32)], time: f32) {
+    let (vertices, indices) = sphere::generate_uv_sphere(1.0, GALLERY_SPHERE_STACKS, GALLERY_SPHERE_SLICES);
+    let camera = Camera::screen((framebuffer.width, framebuffer.height), DEFAULT_NEAR_PLANE, DEFAULT_FAR_PLANE);
+    let mut render_stats = RenderStats::default();
+    for &(planet_type, position, radius) in spheres {
+        let model_matrix = create_model_matrix(position, radius, Vector3::zero());
+        let uniforms = Uniforms {
+            model_matrix,
+            normal_transform: normal_matrix(&model_matrix),
+            view_matrix: camera.view,
+            projection_matrix: camera.projection,
+            viewport: (framebuffer.width, framebuffer.height),
+            time,
+            planet_type,
+            point_lights: Vec::new(),
+            blend: None,
+            backface_tint: None,
+            animation: AnimationSpeeds::default(),
+            star_lights: vec![DEFAULT_STAR_LIGHT],
+            view_dir: DEFAULT_VIEW_DIR,
+            clip_planes: (f32::NEG_INFINITY, f32::INFINITY),
+            log_depth_far: None,
+            ambient: Vector3::new(DEFAULT_AMBIENT_LEVEL, DEFAULT_AMBIENT_LEVEL, DEFAULT_AMBIENT_LEVEL),
+            normal_map: None,
+            emissive_map: None,
+            disk: AccretionDisk::default(),
+            material: SurfaceMaterial::default(),
+            ring_shadow: None,
+            screen_door: None,
+            cull_backfaces: true,
+        };
+        render_indexed(framebuffer, &uniforms, &vertices, &indices, &mut render_stats, None);
+    }
+}
+
+/// Shader gallery: every `PlanetType` on its own lit sphere, in a grid of cells in enum order,
+/// labeled with its name and id. All shaders are evaluated at `time` with default settings.
+/// The labels are written into the framebuffer (kept in a saved PNG), so the window must be open.
+fn render_shader_gallery(framebuffer: &mut Framebuffer, time: f32) {
+    framebuffer.set_stars_visible(false);
+    framebuffer.clear();
+
+    let (vertices, indices) = sphere::generate_uv_sphere(1.0, GALLERY_SPHERE_STACKS, GALLERY_SPHERE_SLICES);
+    let count = PlanetType::ALL.len();
+    let columns = (count as f32).sqrt().ceil() as usize;
+    let rows = count.div_ceil(columns);
+    let cell_width = framebuffer.width as f32 / columns as f32;
+    let cell_height = framebuffer.height as f32 / rows as f32;
+    let radius = cell_width.min(cell_height) * GALLERY_SPHERE_FRACTION;
+    // The cells are laid out in pixels on the z = 0 plane
+    let camera = Camera::screen((framebuffer.width, framebuffer.height), DEFAULT_NEAR_PLANE, DEFAULT_FAR_PLANE);
+    let mut render_stats = RenderStats::default();
+
+    for (i, planet_type) in PlanetType::ALL.iter().enumerate() {
+        let (column, row) = ((i % columns) as f32, (i / columns) as f32);
+        // Spheres sit a little above the cell center to leave room for the label
+        let center = Vector3::new((column + 0.5) * cell_width, (row + 0.45) * cell_height, 0.0);
+        let model_matrix = create_model_matrix(center, radius, Vector3::zero());
+        let uniforms = Uniforms {
+            model_matrix,
+            normal_transform: normal_matrix(&model_matrix),
+            view_matrix: camera.view,
+            projection_matrix: camera.projection,
+            viewport: (framebuffer.width, framebuffer.height),
+            time,
+            planet_type: *planet_type,
+            point_lights: Vec::new(),
+            blend: None,
+            backface_tint: None,
+            animation: AnimationSpeeds::default(),
+            star_lights: vec![DEFAULT_STAR_LIGHT],
+            view_dir: DEFAULT_VIEW_DIR,
+            clip_planes: (f32::NEG_INFINITY, f32::INFINITY),
+            log_depth_far: None,
+            ambient: Vector3::new(DEFAULT_AMBIENT_LEVEL, DEFAULT_AMBIENT_LEVEL, DEFAULT_AMBIENT_LEVEL),
+            normal_map: None,
+            emissive_map: None,
+            disk: AccretionDisk::default(),
+            material: SurfaceMaterial::default(),
+            ring_shadow: None,
+            screen_door: None,
+            cull_backfaces: true,
+        };
+        render_indexed(framebuffer, &uniforms, &vertices, &indices, &mut render_stats, None);
+
+        let label = format!("{} ({})", planet_type.name(), planet_type.id());
+        framebuffer.draw_text(&label, (column * cell_width) as i32 + 10, (center.y + radius) as i32 + 12, 16, Color::RAYWHITE);
+    }
+}
+
+/// Projects a world-space point to the screen the same way the vertex shader projects geometry
+fn project_point(point: Vector3, camera: &Camera, time: f32) -> Vertex {
+    let model_matrix = create_model_matrix(Vector3::zero(), 1.0, Vector3::zero());
+    let uniforms = Uniforms {
+        model_matrix,
+        normal_transform: normal_matrix(&model_matrix),
+        view_matrix: camera.view,
+        projection_matrix: camera.projection,
+        viewport: camera.viewport,
+        time,
+        planet_type: PlanetType::Sun,
+        point_lights: Vec::new(),
+        blend: None,
+        backface_tint: None,
+        animation: AnimationSpeeds::default(),
+        star_lights: vec![DEFAULT_STAR_LIGHT],
+        view_dir: DEFAULT_VIEW_DIR,
+        clip_planes: (f32::NEG_INFINITY, f32::INFINITY),
+        log_depth_far: None,
+        ambient: Vector3::zero(),
+        normal_map: None,
+        emissive_map: None,
+        disk: AccretionDisk::default(),
+        material: SurfaceMaterial::default(),
+        ring_shadow: None,
+        screen_door: None,
+        cull_backfaces: false,
+    };
+    vertex_shader(&Vertex::new_with_color(point, Vector3::one()), &uniforms)
+}
+
+/// Draws red/green/blue X/Y/Z axes from the system center plus a marker dot at the center.
+/// The axes are world-space segments projected like any other geometry.
+fn draw_axes_gizmo(framebuffer: &mut Framebuffer, camera: &Camera, thickness: f32, time: f32) {
+    let project = |point: Vector3| project_point(point, camera, time);
+
+    let axes = [
+        (Vector3::new(AXES_LENGTH, 0.0, 0.0), Vector3::new(1.0, 0.2, 0.2)), // X - red
+        (Vector3::new(0.0, AXES_LENGTH, 0.0), Vector3::new(0.2, 1.0, 0.2)), // Y - green
+        (Vector3::new(0.0, 0.0, AXES_LENGTH), Vector3::new(0.3, 0.5, 1.0)), // Z - blue
+    ];
+
+    let origin = project(Vector3::zero());
+    for (tip, color) in axes {
+        let Some((from, to)) = clip_segment_near(&origin, &project(tip), camera.viewport) else {
+            continue;
+        };
+        for fragment in line(&from, &to, thickness, camera.viewport) {
+            framebuffer.point(fragment.position.x as i32, fragment.position.y as i32, color);
+        }
+    }
+
+    // Center marker
+    if origin.behind_camera {
+        return;
+    }
+    let cx = origin.transformed_position.x as i32;
+    let cy = origin.transformed_position.y as i32;
+    for dy in -2..=2 {
+        for dx in -2..=2 {
+            if dx * dx + dy * dy <= 4 {
+                framebuffer.point(cx + dx, cy + dy, Vector3::new(1.0, 1.0, 1.0));
+            }
+        }
+    }
+}
+
+/// Orbital velocity of a body in world units per time unit, from a central difference of its
+/// position (barycenter motion included). Zero while orbits are paused.
+fn body_velocity(bodies: &[CelestialBody], body: &CelestialBody, center: Vector3, time: f32, state: &AppState) -> Vector3 {
+    let position_at = |t: f32| {
+        let orbit_center = if body.star { center } else { star_barycenter(bodies, center, t, state) };
+        orbit_position(body, orbit_center, t, state)
+    };
+    (position_at(time + VELOCITY_TIME_STEP) - position_at(time - VELOCITY_TIME_STEP)) * (0.5 / VELOCITY_TIME_STEP)
+}
+
+/// Arrow from a body along its orbital velocity, `velocity_arrow_scale` world units long per unit
+/// of speed, so faster (inner) planets get longer arrows. Bodies at rest get none. `position` and
+/// `velocity` are in system space and `project` takes system-space points to the screen.
+fn draw_velocity_arrow(
+    framebuffer: &mut Framebuffer,
+    position: Vector3,
+    velocity: Vector3,
+    project: &impl Fn(Vector3) -> Vertex,
+    config: &Config,
 ) {
-    // Camera movement (arrow keys)
+    // The shaft is cut at the near plane; the head only goes on a tip that is still visible
+    let viewport = (framebuffer.width, framebuffer.height);
+    let tip = project(position + velocity * config.velocity_arrow_scale);
+    let Some((base, end)) = clip_segment_near(&project(position), &tip, viewport) else {
+        return;
+    };
+
+    // The head is built on screen, so it keeps its size and shape from any angle
+    let (base_xy, end_xy) = (base.transformed_position, end.transformed_position);
+    let (dx, dy) = (end_xy.x - base_xy.x, end_xy.y - base_xy.y);
+    let length = (dx * dx + dy * dy).sqrt();
+    if length < 1.0 {
+        return;
+    }
+    let head = VELOCITY_ARROW_HEAD.min(length * 0.4);
+    let (back_x, back_y) = (-dx / length * head, -dy / length * head);
+    let barb = |side: f32| {
+        let mut vertex = end.clone();
+        // Back from the tip, turned ±30° off the shaft
+        let (sin, cos) = (side * PI / 6.0).sin_cos();
+        vertex.transformed_position.x += back_x * cos - back_y * sin;
+        vertex.transformed_position.y += back_x * sin + back_y * cos;
+        vertex
+    };
+
+    let mut segments = vec![(base.clone(), end.clone())];
+    if !tip.behind_camera {
+        segments.extend([1.0, -1.0].map(|side| (end.clone(), barb(side))));
+    }
+    for (from, to) in &segments {
+        for fragment in line(from, to, config.line_thickness, viewport) {
+            framebuffer.point(fragment.position.x as i32, fragment.position.y as i32, VELOCITY_ARROW_COLOR);
+        }
+    }
+}
+
+/// Traces a body's orbit as a faint closed polyline: `ORBIT_PATH_SAMPLES` points through
+/// `orbit_position` over one period from now, around where the orbit is centered now, projected
+/// like the bodies. Bodies that don't orbit (or while orbits are paused) get none.
+fn draw_orbit_path(
+    framebuffer: &mut Framebuffer,
+    bodies: &[CelestialBody],
+    body: &CelestialBody,
+    camera: &Camera,
+    state: &AppState,
+    config: &Config,
+    time: f32,
+) {
+    let angular_speed = body.orbit_speed * state.orbit_speed_mult;
+    if !state.auto_orbit || body.orbit_radius <= 0.0 || angular_speed == 0.0 {
+        return;
+    }
+    let period = 2.0 * PI / angular_speed.abs();
+    let center = Vector3::zero();
+    let orbit_center = if body.star { center } else { star_barycenter(bodies, center, time, state) };
+
+    let points: Vec<Vertex> = (0..ORBIT_PATH_SAMPLES)
+        .map(|i| {
+            let t = time + period * i as f32 / ORBIT_PATH_SAMPLES as f32;
+            project_point(orbit_position(body, orbit_center, t, state), camera, time)
+        })
+        .collect();
+
+    for (i, a) in points.iter().enumerate() {
+        let b = &points[(i + 1) % points.len()];
+        // Only the part beyond the near plane is drawn
+        let Some((a, b)) = clip_segment_near(a, b, camera.viewport) else {
+            continue;
+        };
+        framebuffer.with_blend_mode(BlendMode::Additive, |framebuffer| {
+            for fragment in line(&a, &b, config.line_thickness, camera.viewport) {
+                framebuffer.point_blended(fragment.position.x as i32, fragment.position.y as i32, ORBIT_PATH_COLOR, ORBIT_PATH_ALPHA);
+            }
+        });
+    }
+}
+
+/// Draws a square grid on the orbital plane (world z = 0) around the system center: lines
+/// every `grid_spacing` world units out to `grid_extent`, fading with their distance from the
+/// center. Its pixels are skipped where the depth buffer already holds a nearer surface, with the
+/// same depth mapping (`log_depth_far`) as the bodies.
+fn draw_grid(framebuffer: &mut Framebuffer, camera: &Camera, config: &Config, log_depth_far: Option<f32>, time: f32) {
+    let (extent, spacing) = (config.grid_extent, config.grid_spacing);
+    if spacing <= 0.0 || extent <= 0.0 {
+        return;
+    }
+    let project = |offset: Vector3| project_point(offset, camera, time);
+    let fade = |offset: Vector3| (1.0 - offset.length() / extent).max(0.0);
+
+    let half_lines = (extent / spacing) as i32;
+    let directions = [
+        (Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0)), // Lines along X, stacked in Y
+        (Vector3::new(0.0, 1.0, 0.0), Vector3::new(1.0, 0.0, 0.0)), // Lines along Y, stacked in X
+    ];
+    for (along, across) in directions {
+        for i in -half_lines..=half_lines {
+            // Each line is split per cell so the fade follows the distance from the center
+            for cell in -half_lines..half_lines {
+                let start = across * (i as f32 * spacing) + along * (cell as f32 * spacing);
+                let end = start + along * spacing;
+                if fade(start) <= 0.0 && fade(end) <= 0.0 {
+                    continue;
+                }
+                let Some((a, b)) = clip_segment_near(&project(start), &project(end), camera.viewport) else {
+                    continue;
+                };
+                // A clipped end keeps its world position, so it fades for where it really is
+                let (start_fade, end_fade) = (fade(a.position), fade(b.position));
+                draw_overlay_segment(framebuffer, &a, &b, GRID_COLOR, (start_fade, end_fade), config.line_thickness, log_depth_far);
+            }
+        }
+    }
+}
+
+/// Draws a line segment over the finished bodies, blended additively, with its opacity going
+/// from `alphas.0` to `alphas.1` along it. Each fragment is tested against the depth the bodies
+/// left, so bodies in front hide it and bodies behind it show through.
+fn draw_overlay_segment(
+    framebuffer: &mut Framebuffer,
+    a: &Vertex,
+    b: &Vertex,
+    color: Vector3,
+    (start_alpha, end_alpha): (f32, f32),
+    thickness: f32,
+    log_depth_far: Option<f32>,
+) {
+    let depth = |vertex: &Vertex| match log_depth_far {
+        Some(far) => log_depth(vertex.clip_position.w, far),
+        None => vertex.transformed_position.z,
+    };
+    let (start_depth, end_depth) = (depth(a), depth(b));
+
+    // Opacity and depth at each fragment from where it falls along the projected segment
+    let (screen_a, screen_b) = (a.transformed_position, b.transformed_position);
+    let (dx, dy) = (screen_b.x - screen_a.x, screen_b.y - screen_a.y);
+    let length_sq = (dx * dx + dy * dy).max(f32::EPSILON);
+    let viewport = (framebuffer.width, framebuffer.height);
+    framebuffer.with_blend_mode(BlendMode::Additive, |framebuffer| {
+        for fragment in line(a, b, thickness, viewport) {
+            let t = (((fragment.position.x - screen_a.x) * dx + (fragment.position.y - screen_a.y) * dy) / length_sq).clamp(0.0, 1.0);
+            let (x, y) = (fragment.position.x as i32, fragment.position.y as i32);
+            if framebuffer.depth_hides((x, y, x, y), start_depth + (end_depth - start_depth) * t) {
+                continue;
+            }
+            let alpha = start_alpha + (end_alpha - start_alpha) * t;
+            framebuffer.point_blended(x, y, color, alpha);
+        }
+    });
+}
+
+/// Draws a body's orbit ellipse from its current elements, so it follows eccentricity changes,
+/// with a cross on both foci: the occupied one at the orbit center (the sun or the stars'
+/// barycenter) and the empty one. A circle's foci coincide at the center.
+/// `OrbitGeometryMode::Axes` adds the major axis (perihelion to aphelion) and the minor axis.
+/// The ellipse is tilted into the orbit's inclined plane, like the body.
+fn draw_orbit_geometry(
+    framebuffer: &mut Framebuffer,
+    body: &CelestialBody,
+    orbit_center: Vector3,
+    project: &impl Fn(Vector3) -> Vertex,
+    mode: OrbitGeometryMode,
+    config: &Config,
+    log_depth_far: Option<f32>,
+) {
+    let (a, e) = (body.orbit_radius, body.eccentricity);
+    // Points on the orbital plane, tilted the way `orbit_position` tilts the body
+    let project = |point: Vector2| project(orbit_center + incline(point, body.inclination, body.ascending_node));
+
+    let ellipse: Vec<Vertex> = (0..ORBIT_PATH_SAMPLES)
+        .map(|i| project(ellipse_point(a, e, 2.0 * PI * i as f32 / ORBIT_PATH_SAMPLES as f32)))
+        .collect();
+    let mut segments: Vec<(&Vertex, &Vertex, Vector3)> = ellipse
+        .iter()
+        .enumerate()
+        .map(|(i, from)| (from, &ellipse[(i + 1) % ellipse.len()], ORBIT_PATH_COLOR))
+        .collect();
+    let axes: Vec<(Vertex, Vertex)> = match mode {
+        OrbitGeometryMode::Axes => axis_ends(a, e).iter().map(|&(from, to)| (project(from), project(to))).collect(),
+        _ => Vec::new(),
+    };
+    segments.extend(axes.iter().map(|(from, to)| (from, to, ORBIT_AXES_COLOR)));
+    let viewport = (framebuffer.width, framebuffer.height);
+    for (from, to, color) in segments {
+        let Some((from, to)) = clip_segment_near(from, to, viewport) else {
+            continue;
+        };
+        let alphas = (ORBIT_GEOMETRY_ALPHA, ORBIT_GEOMETRY_ALPHA);
+        draw_overlay_segment(framebuffer, &from, &to, color, alphas, config.line_thickness, log_depth_far);
+    }
+
+    for (focus, color) in [(empty_focus(a, e), EMPTY_FOCUS_COLOR), (Vector2::zero(), OCCUPIED_FOCUS_COLOR)] {
+        let focus = project(focus);
+        if !focus.behind_camera {
+            draw_cross(framebuffer, focus.transformed_position, color);
+        }
+    }
+}
+
+/// Screen-aligned cross, so a marker reads the same from any angle
+fn draw_marker(framebuffer: &mut Framebuffer, position: Vector3) {
+    draw_cross(framebuffer, position, MARKER_COLOR);
+}
+
+/// Cross `MARKER_SIZE` pixels across each way at a screen position
+fn draw_cross(framebuffer: &mut Framebuffer, position: Vector3, color: Vector3) {
+    let (x, y) = (position.x as i32, position.y as i32);
+    framebuffer.line(x - MARKER_SIZE, y, x + MARKER_SIZE, y, color);
+    framebuffer.line(x, y - MARKER_SIZE, x, y + MARKER_SIZE, color);
+}
+
+/// World position of a body (its offset from the system center), like the markers
+fn system_position(body: &CelestialBody, bodies: &[CelestialBody], time: f32, state: &AppState) -> Vector3 {
+    let orbit_center = if body.star { Vector3::zero() } else { star_barycenter(bodies, Vector3::zero(), time, state) };
+    orbit_position(body, orbit_center, time, state)
+}
+
+/// Marker coordinates plus the distance from the newest marker to every body
+fn draw_marker_panel(
+    draw_handle: &mut RaylibDrawHandle,
+    markers: &[Vector3],
+    bodies: &[CelestialBody],
+    state: &AppState,
+    time: f32,
+    x: i32,
+    y: i32,
+) {
+    let Some(&newest) = markers.last() else {
+        return;
+    };
+    let row_height = 14;
+    let body_rows = bodies.len().div_ceil(2) as i32;
+    let height = 44 + (markers.len() as i32 + body_rows) * row_height;
+    draw_handle.draw_rectangle(x, y, 330, height, Color::new(0, 0, 0, 170));
+    draw_handle.draw_text("MARCADORES", x + 8, y + 6, 14, Color::YELLOW);
+
+    let mut row_y = y + 24;
+    for (i, marker) in markers.iter().enumerate() {
+        draw_handle.draw_text(
+            &format!("M{}: ({:.0}, {:.0}, {:.0})", i + 1, marker.x, marker.y, marker.z),
+            x + 8,
+            row_y,
+            12,
+            Color::SKYBLUE,
+        );
+        row_y += row_height;
+    }
+
+    draw_handle.draw_text(&format!("Distancia desde M{}:", markers.len()), x + 8, row_y + 2, 12, Color::LIGHTGRAY);
+    row_y += row_height + 4;
+    for (i, body) in bodies.iter().enumerate() {
+        let distance = (system_position(body, bodies, time, state) - newest).length();
+        let column_x = x + 8 + (i as i32 % 2) * 160;
+        let column_y = row_y + (i as i32 / 2) * row_height;
+        draw_handle.draw_text(&format!("{}: {:.0}", body.name, distance), column_x, column_y, 12, Color::WHITE);
+    }
+}
+
+fn handle_input(window: &mut RaylibHandle, state: &mut AppState, body_count: usize, viewport: (u32, u32)) {
+    // Camera panning (arrow keys): the target slides across the screen plane, so the scene
+    // moves CAMERA_PAN_STEP pixels the way the arrow points
+    let camera = state.camera(viewport);
+    let (right, up) = camera.screen_axes();
+    let pan = CAMERA_PAN_STEP / camera.pixels_per_unit(state.camera_target);
     if window.is_key_down(KeyboardKey::KEY_RIGHT) {
-        camera_offset.x += 10.0;
+        state.camera_target -= right * pan;
     }
     if window.is_key_down(KeyboardKey::KEY_LEFT) {
-        camera_offset.x -= 10.0;
+        state.camera_target += right * pan;
     }
     if window.is_key_down(KeyboardKey::KEY_UP) {
-        camera_offset.y -= 10.0;
+        state.camera_target -= up * pan;
     }
     if window.is_key_down(KeyboardKey::KEY_DOWN) {
-        camera_offset.y += 10.0;
+        state.camera_target += up * pan;
     }
     
     // Zoom (S/A keys)
     if window.is_key_down(KeyboardKey::KEY_S) {
-        *camera_zoom += 0.05;
-        if *camera_zoom > 3.0 { *camera_zoom = 3.0; }
+        state.camera_zoom += 0.05;
+        if state.camera_zoom > 3.0 { state.camera_zoom = 3.0; }
     }
     if window.is_key_down(KeyboardKey::KEY_A) {
-        *camera_zoom -= 0.05;
-        if *camera_zoom < 0.3 { *camera_zoom = 0.3; }
+        state.camera_zoom -= 0.05;
+        if state.camera_zoom < 0.3 { state.camera_zoom = 0.3; }
     }
     
-    // System rotation (Q/W/E/R/T/Y keys)
+    // Camera orbit around the target (Q/W/E/R/T/Y keys)
     if window.is_key_down(KeyboardKey::KEY_Q) {
-        system_rotation.x -= PI / 30.0;
+        state.camera_rotation.x -= PI / 30.0;
     }
     if window.is_key_down(KeyboardKey::KEY_W) {
-        system_rotation.x += PI / 30.0;
+        state.camera_rotation.x += PI / 30.0;
     }
     if window.is_key_down(KeyboardKey::KEY_E) {
-        system_rotation.y -= PI / 30.0;
+        state.camera_rotation.y -= PI / 30.0;
     }
     if window.is_key_down(KeyboardKey::KEY_R) {
-        system_rotation.y += PI / 30.0;
+        state.camera_rotation.y += PI / 30.0;
     }
     if window.is_key_down(KeyboardKey::KEY_T) {
-        system_rotation.z -= PI / 30.0;
+        state.camera_rotation.z -= PI / 30.0;
     }
     if window.is_key_down(KeyboardKey::KEY_Y) {
-        system_rotation.z += PI / 30.0;
+        state.camera_rotation.z += PI / 30.0;
     }
     
     // Toggle auto-rotation with SPACE
     if window.is_key_pressed(KeyboardKey::KEY_SPACE) {
-        *auto_rotate = !*auto_rotate;
+        state.auto_rotate = !state.auto_rotate;
     }
     
     // Toggle auto-orbit with O
     if window.is_key_pressed(KeyboardKey::KEY_O) {
-        *auto_orbit = !*auto_orbit;
+        state.auto_orbit = !state.auto_orbit;
+    }
+
+    // Rotation speed multiplier (,/. keys)
+    if window.is_key_pressed(KeyboardKey::KEY_PERIOD) {
+        state.rotation_speed_mult = (state.rotation_speed_mult * SPEED_MULT_STEP).min(SPEED_MULT_MAX);
+    }
+    if window.is_key_pressed(KeyboardKey::KEY_COMMA) {
+        state.rotation_speed_mult = (state.rotation_speed_mult / SPEED_MULT_STEP).max(SPEED_MULT_MIN);
+    }
+
+    // Orbit speed multiplier ([/] keys)
+    if window.is_key_pressed(KeyboardKey::KEY_RIGHT_BRACKET) {
+        state.orbit_speed_mult = (state.orbit_speed_mult * SPEED_MULT_STEP).min(SPEED_MULT_MAX);
+    }
+    if window.is_key_pressed(KeyboardKey::KEY_LEFT_BRACKET) {
+        state.orbit_speed_mult = (state.orbit_speed_mult / SPEED_MULT_STEP).max(SPEED_MULT_MIN);
+    }
+
+    // Simulation time scale: + / - (keypad or main row; CTRL+= / CTRL+- tweak the material instead)
+    // speed it up/slow it down, Backspace reverses it, Enter pauses
+    let control = window.is_key_down(KeyboardKey::KEY_LEFT_CONTROL);
+    if window.is_key_pressed(KeyboardKey::KEY_KP_ADD) || (window.is_key_pressed(KeyboardKey::KEY_EQUAL) && !control) {
+        state.time_scale = (state.time_scale * SPEED_MULT_STEP).clamp(-TIME_SCALE_MAX, TIME_SCALE_MAX);
+    }
+    if window.is_key_pressed(KeyboardKey::KEY_KP_SUBTRACT) || (window.is_key_pressed(KeyboardKey::KEY_MINUS) && !control) {
+        let magnitude = (state.time_scale.abs() / SPEED_MULT_STEP).max(TIME_SCALE_MIN);
+        state.time_scale = magnitude.copysign(state.time_scale);
+    }
+    if window.is_key_pressed(KeyboardKey::KEY_BACKSPACE) {
+        state.time_scale = -state.time_scale;
+    }
+    if window.is_key_pressed(KeyboardKey::KEY_ENTER) {
+        state.time_paused = !state.time_paused;
+    }
+
+    // Cycle the selected body with TAB (SHIFT+TAB goes backwards)
+    if window.is_key_pressed(KeyboardKey::KEY_TAB) && body_count > 0 {
+        if window.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) {
+            state.selected_body = (state.selected_body + body_count - 1) % body_count;
+        } else {
+            state.selected_body = (state.selected_body + 1) % body_count;
+        }
+    }
+
+    // Toggle single-body isolation with I
+    if window.is_key_pressed(KeyboardKey::KEY_I) {
+        state.isolate_body = !state.isolate_body;
+    }
+
+    // Toggle the axes gizmo with G
+    if window.is_key_pressed(KeyboardKey::KEY_G) {
+        state.show_axes = !state.show_axes;
+    }
+
+    // Toggle the reference grid with F8
+    if window.is_key_pressed(KeyboardKey::KEY_F8) {
+        state.show_grid = !state.show_grid;
+    }
+
+    // Toggle the orbit paths with 0; SHIFT+0 cycles the orbital geometry view (foci, then axes)
+    if window.is_key_pressed(KeyboardKey::KEY_ZERO) {
+        if window.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) {
+            state.orbit_geometry = state.orbit_geometry.next();
+        } else {
+            state.show_orbits = !state.show_orbits;
+        }
+    }
+
+    // Toggle the velocity arrows with F10
+    if window.is_key_pressed(KeyboardKey::KEY_F10) {
+        state.show_velocities = !state.show_velocities;
+    }
+
+    // Cycle the point cloud view (off, every body, selected body) with F11
+    if window.is_key_pressed(KeyboardKey::KEY_F11) {
+        state.point_cloud = state.point_cloud.next();
+    }
+
+    // Toggle the lightning flash light with L
+    if window.is_key_pressed(KeyboardKey::KEY_L) {
+        state.lightning_light = !state.lightning_light;
+    }
+
+    // Toggle the sun halo with H
+    if window.is_key_pressed(KeyboardKey::KEY_H) {
+        state.sun_halo = !state.sun_halo;
+    }
+
+    // Ambient light floor with ; (darker) and ' (brighter)
+    if window.is_key_pressed(KeyboardKey::KEY_SEMICOLON) {
+        state.ambient_level = (state.ambient_level - AMBIENT_STEP).max(0.0);
+    }
+    if window.is_key_pressed(KeyboardKey::KEY_APOSTROPHE) {
+        state.ambient_level = (state.ambient_level + AMBIENT_STEP).min(1.0);
+    }
+
+    // Side-by-side stereo with F2; \ and / adjust separation and convergence (SHIFT lowers them)
+    if window.is_key_pressed(KeyboardKey::KEY_F2) {
+        state.stereo = !state.stereo;
+    }
+    let lower = window.is_key_down(KeyboardKey::KEY_LEFT_SHIFT);
+    if window.is_key_pressed(KeyboardKey::KEY_BACKSLASH) {
+        state.stereo_separation = if lower {
+            (state.stereo_separation - STEREO_SEPARATION_STEP).max(0.0)
+        } else {
+            (state.stereo_separation + STEREO_SEPARATION_STEP).min(STEREO_SEPARATION_MAX)
+        };
+    }
+    if window.is_key_pressed(KeyboardKey::KEY_SLASH) {
+        state.stereo_convergence += if lower { -STEREO_CONVERGENCE_STEP } else { STEREO_CONVERGENCE_STEP };
+    }
+
+    // Near/far planes: Home/End move the near plane, PageUp/PageDown the far plane
+    if window.is_key_down(KeyboardKey::KEY_HOME) {
+        state.near_plane = (state.near_plane + CLIP_PLANE_STEP).min(state.far_plane - MIN_CLIP_RANGE);
+    }
+    if window.is_key_down(KeyboardKey::KEY_END) {
+        state.near_plane = (state.near_plane - CLIP_PLANE_STEP).max(MIN_NEAR_PLANE);
+    }
+    if window.is_key_down(KeyboardKey::KEY_PAGE_UP) {
+        state.far_plane += CLIP_PLANE_STEP;
+    }
+    if window.is_key_down(KeyboardKey::KEY_PAGE_DOWN) {
+        state.far_plane = (state.far_plane - CLIP_PLANE_STEP).max(state.near_plane + MIN_CLIP_RANGE);
+    }
+    // ` switches the depth between linear and logarithmic
+    if window.is_key_pressed(KeyboardKey::KEY_GRAVE) {
+        state.log_depth = !state.log_depth;
+    }
+
+    // Manual light direction: Z toggles it, D/F turn the azimuth, C/V change the elevation
+    if window.is_key_pressed(KeyboardKey::KEY_Z) {
+        state.light_override = !state.light_override;
+    }
+    if state.light_override {
+        if window.is_key_down(KeyboardKey::KEY_D) {
+            state.light_azimuth = (state.light_azimuth - LIGHT_ANGLE_SPEED).rem_euclid(2.0 * PI);
+        }
+        if window.is_key_down(KeyboardKey::KEY_F) {
+            state.light_azimuth = (state.light_azimuth + LIGHT_ANGLE_SPEED).rem_euclid(2.0 * PI);
+        }
+        if window.is_key_down(KeyboardKey::KEY_C) {
+            state.light_elevation = (state.light_elevation - LIGHT_ANGLE_SPEED).max(-LIGHT_ELEVATION_LIMIT);
+        }
+        if window.is_key_down(KeyboardKey::KEY_V) {
+            state.light_elevation = (state.light_elevation + LIGHT_ANGLE_SPEED).min(LIGHT_ELEVATION_LIMIT);
+        }
+    }
+
+    // Toggle normal mapping with N; J raises its strength (SHIFT+J lowers it)
+    if window.is_key_pressed(KeyboardKey::KEY_N) {
+        state.normal_mapping = !state.normal_mapping;
+    }
+    if window.is_key_pressed(KeyboardKey::KEY_J) {
+        state.normal_map_strength = if window.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) {
+            (state.normal_map_strength - NORMAL_MAP_STRENGTH_STEP).max(0.0)
+        } else {
+            (state.normal_map_strength + NORMAL_MAP_STRENGTH_STEP).min(NORMAL_MAP_STRENGTH_MAX)
+        };
+    }
+
+    // Toggle back-face tinting with X
+    if window.is_key_pressed(KeyboardKey::KEY_X) {
+        state.show_backfaces = !state.show_backfaces;
+    }
+
+    // Toggle the frame-time overlay with F3
+    if window.is_key_pressed(KeyboardKey::KEY_F3) {
+        state.show_frame_times = !state.show_frame_times;
+    }
+
+    // Toggle the per-stage timing overlay with F4
+    if window.is_key_pressed(KeyboardKey::KEY_F4) {
+        state.show_stage_timings = !state.show_stage_timings;
+    }
+
+    // Drop a probe marker at the point under the screen center with Insert (the oldest goes
+    // once MAX_MARKERS are placed); Delete clears them all
+    if window.is_key_pressed(KeyboardKey::KEY_INSERT) {
+        if state.markers.len() == MAX_MARKERS {
+            state.markers.remove(0);
+        }
+        // Unprojected at the target's depth, that is the camera target itself
+        let center = Vector2::new(viewport.0 as f32 / 2.0, viewport.1 as f32 / 2.0);
+        let marker = camera.unproject(center, state.camera_target).unwrap_or(state.camera_target);
+        state.markers.push(marker);
+    }
+    if window.is_key_pressed(KeyboardKey::KEY_DELETE) {
+        state.markers.clear();
+    }
+
+    // Toggle the cinematic camera with F7; it starts moving right away
+    if window.is_key_pressed(KeyboardKey::KEY_F7) {
+        state.cinematic = !state.cinematic;
+        state.cinematic_idle = CINEMATIC_RESUME_DELAY;
+    }
+
+    // Toggle the flat shader map view with M
+    if window.is_key_pressed(KeyboardKey::KEY_M) {
+        state.map_view = !state.map_view;
+    }
+
+    // Trigger a test camera shake with K
+    if window.is_key_pressed(KeyboardKey::KEY_K) {
+        state.trigger_shake(TEST_SHAKE_INTENSITY, TEST_SHAKE_DURATION);
+    }
+
+    // Cycle the ring blend mode with B
+    if window.is_key_pressed(KeyboardKey::KEY_B) {
+        state.ring_blend = state.ring_blend.next();
+    }
+
+    // Toggle the shader legend with F1
+    if window.is_key_pressed(KeyboardKey::KEY_F1) {
+        state.show_legend = !state.show_legend;
+    }
+
+    // Toggle the isolated body's moon/rings with U
+    if window.is_key_pressed(KeyboardKey::KEY_U) {
+        state.isolate_satellites = !state.isolate_satellites;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shaders::{register_shader, MarsShader};
+
+    #[test]
+    fn depth_test_keeps_the_near_moon_in_front_of_the_giant() {
+        let blank = |framebuffer: &mut Framebuffer| {
+            framebuffer.set_stars_visible(false);
+            framebuffer.clear();
+        };
+        let pixels = |framebuffer: &Framebuffer| -> Vec<(u8, u8, u8)> {
+            framebuffer.snapshot().iter().map(|color| (color.r, color.g, color.b)).collect()
+        };
+
+        let mut scene = Framebuffer::new(800, 600);
+        render_depth_test_scene(&mut scene, 0.0);
+        let [moon, giant] = depth_test_spheres(&scene);
+
+        let (mut moon_only, mut giant_only, mut empty) = (Framebuffer::new(800, 600), Framebuffer::new(800, 600), Framebuffer::new(800, 600));
+        for framebuffer in [&mut moon_only, &mut giant_only, &mut empty] {
+            blank(framebuffer);
+        }
+        render_spheres(&mut moon_only, &[moon], 0.0);
+        render_spheres(&mut giant_only, &[giant], 0.0);
+        let (scene, moon_only, giant_only, empty) = (pixels(&scene), pixels(&moon_only), pixels(&giant_only), pixels(&empty));
+
+        let moon_pixels: Vec<usize> = (0..empty.len()).filter(|&i| moon_only[i] != empty[i]).collect();
+        assert!(!moon_pixels.is_empty());
+        // The giant, drawn after the moon, covers a good part of it on its own...
+        let overlapped = moon_pixels.iter().filter(|&&i| giant_only[i] != empty[i]).count();
+        assert!(overlapped > moon_pixels.len() / 4, "only {} of {} moon pixels overlap the giant", overlapped, moon_pixels.len());
+        // ...but none of it shows through the moon in the scene
+        let bleeding = moon_pixels.iter().filter(|&&i| scene[i] != moon_only[i]).count();
+        assert_eq!(bleeding, 0);
+    }
+
+    /// Unlit flat magenta, a color no built-in shader produces
+    struct FlatShader;
+
+    impl FragmentShader for FlatShader {
+        fn shade(&self, _fragment: &Fragment, _vertex: &Vertex, _time: f32, _params: &ShaderParams) -> Vector3 {
+            Vector3::new(1.0, 0.0, 1.0)
+        }
+
+        fn lit(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn a_registered_shader_replaces_the_builtin_one() {
+        let mut framebuffer = Framebuffer::new(200, 200);
+        framebuffer.set_stars_visible(false);
+        framebuffer.clear();
+
+        // Only Mars is swapped, and only for the length of the draw: other tests use the registry too
+        register_shader(PlanetType::Mars, Box::new(FlatShader));
+        render_spheres(&mut framebuffer, &[(PlanetType::Mars, Vector3::new(100.0, 100.0, 0.0), 60.0)], 0.0);
+        register_shader(PlanetType::Mars, Box::new(MarsShader));
+
+        let center = framebuffer.snapshot()[100 * 200 + 100];
+        assert_eq!((center.r, center.g, center.b), (255, 0, 255));
+    }
+
+    #[test]
+    fn camera_target_projects_to_the_screen_center() {
+        let viewport = (800, 600);
+        let mut state = AppState::new();
+        for (target, rotation) in [
+            (Vector3::zero(), Vector3::zero()),
+            (Vector3::new(250.0, -80.0, 30.0), Vector3::new(0.6, -1.1, 0.2)),
+        ] {
+            state.camera_target = target;
+            state.camera_rotation = rotation;
+            let projected = project_point(target, &state.camera(viewport), 0.0);
+            assert!(!projected.behind_camera);
+            let screen = projected.transformed_position;
+            assert!((screen.x - 400.0).abs() < 1e-2 && (screen.y - 300.0).abs() < 1e-2, "{:?}", screen);
+        }
+    }
+
+    #[test]
+    fn vertices_nearer_than_the_near_plane_are_flagged() {
+        let state = AppState::new();
+        let camera = state.camera((800, 600));
+        let forward = (state.camera_target - camera.eye).normalized();
+        // Still in front of the eye (w > 0), but nearer than the near plane
+        let too_near = project_point(camera.eye + forward * (state.near_plane * 0.5), &camera, 0.0);
+        assert!(too_near.clip_position.w > 0.0 && too_near.behind_camera);
+        let visible = project_point(camera.eye + forward * (state.near_plane * 2.0), &camera, 0.0);
+        assert!(!visible.behind_camera);
+    }
+
+    #[test]
+    fn points_behind_the_eye_are_flagged_by_the_projection() {
+        let mut state = AppState::new();
+        // Looking along the orbital plane from close in, part of the system is behind the eye
+        state.camera_rotation = Vector3::new(PI / 2.0, 0.0, 0.0);
+        state.camera_zoom = 3.0;
+        let camera = state.camera((800, 600));
+        let behind = camera.eye + (camera.eye - state.camera_target);
+        assert!(project_point(behind, &camera, 0.0).behind_camera);
+        assert!(!project_point(state.camera_target, &camera, 0.0).behind_camera);
+    }
+
+    #[test]
+    fn default_camera_shows_one_pixel_per_world_unit_at_the_target() {
+        let state = AppState::new();
+        let camera = state.camera((800, 600));
+        assert!((camera.pixels_per_unit(state.camera_target) - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn every_star_lights_the_bodies_but_itself() {
+        let sun = (Vector3::zero(), Vector3::one());
+        let companion = (Vector3::new(100.0, 0.0, 0.0), Vector3::new(0.5, 0.4, 0.3));
+        let stars = [sun, companion];
+        let planet = Vector3::new(40.0, 0.0, 0.0);
+
+        // A planet between the stars is lit from both sides, each star in its own color
+        let lights = star_lights_at(&stars, planet, None);
+        assert_eq!(lights.len(), 2);
+        assert_eq!((lights[0].direction, lights[0].color), (Vector3::new(-1.0, 0.0, 0.0), sun.1));
+        assert_eq!((lights[1].direction, lights[1].color), (Vector3::new(1.0, 0.0, 0.0), companion.1));
+        // A star is lit only by the others
+        let lights = star_lights_at(&stars, companion.0, None);
+        assert_eq!(lights.len(), 1);
+        assert_eq!(lights[0].color, sun.1);
+        // The manual light steers the sun's light and leaves the companion's where it is
+        let manual = Vector3::new(0.0, 1.0, 0.0);
+        let lights = star_lights_at(&stars, planet, Some(manual));
+        assert_eq!((lights[0].direction, lights[1].direction), (manual, Vector3::new(1.0, 0.0, 0.0)));
+        // Without stars, the default light
+        assert_eq!(star_lights_at(&[], planet, None)[0].direction, DEFAULT_LIGHT_DIR);
+    }
+
+    #[test]
+    fn companion_stars_circle_the_barycenter_with_the_sun() {
+        let state = AppState::new();
+        for scales in [&[30.0][..], &[30.0, 12.0], &[40.0, 25.0, 10.0]] {
+            let mut bodies = vec![CelestialBody {
+                name: "Sol".to_string(),
+                planet_type: PlanetType::Sun,
+                scale: 50.0,
+                orbit_radius: 0.0,
+                orbit_speed: 0.0,
+                eccentricity: 0.0,
+                inclination: 0.0,
+                ascending_node: 0.0,
+                rotation_speed: 0.0,
+                spin_axis: SPIN_AXIS_UP,
+                orbit_phase: 0.0,
+                star: true,
+                light_color: Vector3::one(),
+                moons: Vec::new(),
+                model_path: String::new(),
+                subdivision: 0,
+                visible: true,
+                satellites_visible: true,
+                animation: AnimationSpeeds::default(),
+                material: SurfaceMaterial::default(),
+                lod: LodFade::default(),
+            }];
+            let companions: Vec<CompanionStar> = scales
+                .iter()
+                .enumerate()
+                .map(|(index, &scale)| CompanionStar { name: format!("Sol {}", index), scale, ..CompanionStar::default() })
+                .collect();
+            add_companion_stars(&mut bodies, &companions);
+            assert_eq!(bodies.len(), 1 + scales.len());
+            assert!(bodies.iter().all(|body| body.star));
+
+            for time in [0.0, 2.5, 9.0] {
+                // The barycenter stays at the system center, and every companion keeps its distance to the sun
+                assert!(star_barycenter(&bodies, Vector3::zero(), time, &state).length() < 1e-3);
+                let sun = orbit_position(&bodies[0], Vector3::zero(), time, &state);
+                for companion in &bodies[1..] {
+                    let distance = (orbit_position(companion, Vector3::zero(), time, &state) - sun).length();
+                    assert!((distance - BINARY_SEPARATION).abs() < 1e-3, "{} at {}", companion.name, distance);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hud_lines_wrap_between_items_to_the_window_width() {
+        // 8 pixels per character, roughly the default font at the HUD size
+        let measure = |text: &str| 8 * text.chars().count() as i32;
+        let width = 800 - 2 * HUD_MARGIN;
+        for help in CONTROLS_HELP {
+            let lines = wrap_hud_line(help, width, measure);
+            assert!(lines.iter().all(|line| measure(line) <= width), "{:?}", lines);
+            // Nothing is lost or reordered, and lines only break between items
+            assert_eq!(lines.join(" | "), help);
+        }
+        assert_eq!(wrap_hud_line("ab | cd | ef", 8 * 7, measure), vec!["ab | cd", "ef"]);
+        // An item too wide for any line still shows, alone
+        assert_eq!(wrap_hud_line("abcdefgh | ab", 8 * 4, measure), vec!["abcdefgh", "ab"]);
     }
 }
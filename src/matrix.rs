@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 
-use raylib::prelude::Matrix;
+use raylib::prelude::{Matrix, Vector3, Vector4};
 
 /// Creates a 4x4 matrix from 16 float values, specified in traditional row-major order.
 pub fn new_matrix4(
@@ -37,4 +37,365 @@ pub fn new_matrix3(
         r2c0, r2c1, r2c2, 0.0,
         0.0,  0.0,  0.0,  1.0,
     )
-}
\ No newline at end of file
+}
+
+// This function manually multiplies a 4x4 matrix with a 4D vector (in homogeneous coordinates)
+pub fn multiply_matrix_vector4(matrix: &Matrix, vector: &Vector4) -> Vector4 {
+    Vector4::new(
+        matrix.m0 * vector.x + matrix.m4 * vector.y + matrix.m8 * vector.z + matrix.m12 * vector.w,
+        matrix.m1 * vector.x + matrix.m5 * vector.y + matrix.m9 * vector.z + matrix.m13 * vector.w,
+        matrix.m2 * vector.x + matrix.m6 * vector.y + matrix.m10 * vector.z + matrix.m14 * vector.w,
+        matrix.m3 * vector.x + matrix.m7 * vector.y + matrix.m11 * vector.z + matrix.m15 * vector.w,
+    )
+}
+
+/// Applies `matrix` to the point `p` (w = 1), translation included
+pub fn transform_point(matrix: &Matrix, p: Vector3) -> Vector3 {
+    let transformed = multiply_matrix_vector4(matrix, &Vector4::new(p.x, p.y, p.z, 1.0));
+    Vector3::new(transformed.x, transformed.y, transformed.z)
+}
+
+/// Applies only the rotation/scale part of `matrix` to `v` (directions, normals of rigid transforms)
+pub fn transform_direction(matrix: &Matrix, v: Vector3) -> Vector3 {
+    Vector3::new(
+        matrix.m0 * v.x + matrix.m4 * v.y + matrix.m8 * v.z,
+        matrix.m1 * v.x + matrix.m5 * v.y + matrix.m9 * v.z,
+        matrix.m2 * v.x + matrix.m6 * v.y + matrix.m10 * v.z,
+    )
+}
+
+/// Inverse-transpose of the upper-left 3x3 of `m`, which carries normals through `m` so they
+/// stay perpendicular to the surface under scaling. Renormalize the result after applying it.
+/// A singular matrix (a zero scale) yields its cofactor matrix, which still points the right way.
+pub fn normal_matrix(m: &Matrix) -> Matrix {
+    let (a, b, c) = (m.m0, m.m4, m.m8);
+    let (d, e, f) = (m.m1, m.m5, m.m9);
+    let (g, h, i) = (m.m2, m.m6, m.m10);
+
+    // Cofactors; (M^-1)^T = cofactor(M) / det(M)
+    let cofactors = [
+        [e * i - f * h, f * g - d * i, d * h - e * g],
+        [c * h - b * i, a * i - c * g, b * g - a * h],
+        [b * f - c * e, c * d - a * f, a * e - b * d],
+    ];
+    let det = a * cofactors[0][0] + b * cofactors[0][1] + c * cofactors[0][2];
+    let scale = if det.abs() > 1e-12 { 1.0 / det } else { 1.0 };
+    let r = |row: usize, column: usize| cofactors[row][column] * scale;
+
+    new_matrix3(
+        r(0, 0), r(0, 1), r(0, 2),
+        r(1, 0), r(1, 1), r(1, 2),
+        r(2, 0), r(2, 1), r(2, 2),
+    )
+}
+
+/// Rotation of `angle` radians around `axis` (Rodrigues' formula), right-handed like the
+/// per-axis rotations in `create_model_matrix`. A zero axis falls back to +Y.
+pub fn axis_angle_matrix(axis: Vector3, angle: f32) -> Matrix {
+    let axis = if axis.length() > 0.0001 { axis.normalized() } else { Vector3::new(0.0, 1.0, 0.0) };
+    let (x, y, z) = (axis.x, axis.y, axis.z);
+    let (sin, cos) = angle.sin_cos();
+    let t = 1.0 - cos;
+
+    new_matrix3(
+        t * x * x + cos,     t * x * y - sin * z, t * x * z + sin * y,
+        t * x * y + sin * z, t * y * y + cos,     t * y * z - sin * x,
+        t * x * z - sin * y, t * y * z + sin * x, t * z * z + cos,
+    )
+}
+
+/// Shortest rotation taking the direction `from` onto `to`. Opposite directions are turned half
+/// a circle around an axis perpendicular to `from`.
+pub fn rotation_between(from: Vector3, to: Vector3) -> Matrix {
+    let (from, to) = (from.normalized(), to.normalized());
+    let cos = from.dot(to).clamp(-1.0, 1.0);
+    let axis = from.cross(to);
+    if axis.length() > 1e-6 {
+        return axis_angle_matrix(axis, cos.acos());
+    }
+    if cos > 0.0 {
+        return axis_angle_matrix(from, 0.0);
+    }
+    let helper = if from.x.abs() < 0.9 { Vector3::new(1.0, 0.0, 0.0) } else { Vector3::new(0.0, 1.0, 0.0) };
+    axis_angle_matrix(from.cross(helper), std::f32::consts::PI)
+}
+
+/// Perspective projection in the OpenGL convention, from a right-handed view space where the
+/// camera sits at the origin looking down -Z with +Y up.
+/// - `fov_y_rad` is the full vertical field of view and `aspect` the viewport's width / height.
+/// - `near` / `far` are positive distances in front of the camera, with `0 < near < far`.
+///
+/// Clip space: `w = -z_view`, so points in front of the camera get a positive w and the
+/// perspective divide by w gives the foreshortening. After the divide (NDC) the visible volume
+/// is the cube [-1, 1] on every axis: x to the right, y up, and depth from z = -1 at the near
+/// plane to z = +1 at the far plane, spaced non-linearly (most precision close to `near`).
+pub fn perspective(fov_y_rad: f32, aspect: f32, near: f32, far: f32) -> Matrix {
+    let f = 1.0 / (fov_y_rad / 2.0).tan();
+    let depth = near - far;
+
+    new_matrix4(
+        f / aspect, 0.0, 0.0,                 0.0,
+        0.0,        f,   0.0,                 0.0,
+        0.0,        0.0, (far + near) / depth, 2.0 * far * near / depth,
+        0.0,        0.0, -1.0,                0.0,
+    )
+}
+
+/// View matrix of a camera at `eye` looking at `target`, in the right-handed convention
+/// `perspective` expects: the camera ends up at the origin looking down -Z, with `up` (projected
+/// perpendicular to the view direction) along +Y. An `up` parallel to the view direction is
+/// replaced by another axis; `eye == target` looks down -Z.
+pub fn look_at(eye: Vector3, target: Vector3, up: Vector3) -> Matrix {
+    let to_target = target - eye;
+    let forward = if to_target.length() > 0.0001 { to_target.normalized() } else { Vector3::new(0.0, 0.0, -1.0) };
+    let side = forward.cross(up);
+    let side = if side.length() > 0.0001 {
+        side.normalized()
+    } else {
+        let fallback_up = if forward.x.abs() < 0.9 { Vector3::new(1.0, 0.0, 0.0) } else { Vector3::new(0.0, 1.0, 0.0) };
+        forward.cross(fallback_up).normalized()
+    };
+    let camera_up = side.cross(forward);
+
+    new_matrix4(
+        side.x,       side.y,       side.z,       -side.dot(eye),
+        camera_up.x,  camera_up.y,  camera_up.z,  -camera_up.dot(eye),
+        -forward.x,   -forward.y,   -forward.z,   forward.dot(eye),
+        0.0,          0.0,          0.0,          1.0,
+    )
+}
+
+/// Maps NDC (after the perspective divide) to framebuffer pixels: x from -1..1 to 0..width and
+/// y from -1..1 to height..0, since NDC +Y is up and pixel rows grow downwards. z is kept as the depth.
+pub fn viewport_transform(ndc: Vector3, width: u32, height: u32) -> Vector3 {
+    Vector3::new(
+        (ndc.x + 1.0) * 0.5 * width as f32,
+        (1.0 - ndc.y) * 0.5 * height as f32,
+        ndc.z,
+    )
+}
+
+/// Moves everything by (`x`, `y`) in NDC after the projection: x += x·w, y += y·w, so the
+/// offset survives the perspective divide unchanged. Applied after `perspective` it shifts the
+/// image on screen without changing the perspective (camera shake, stereo convergence).
+pub fn ndc_translation(x: f32, y: f32) -> Matrix {
+    new_matrix4(
+        1.0, 0.0, 0.0, x,
+        0.0, 1.0, 0.0, y,
+        0.0, 0.0, 1.0, 0.0,
+        0.0, 0.0, 0.0, 1.0,
+    )
+}
+
+/// Pivots smaller than this make `invert` treat the matrix as singular
+const SINGULAR_PIVOT_EPSILON: f32 = 1e-10;
+
+/// Entries of `m` as rows of the usual column-vector notation (`rows[row][column]`)
+fn to_rows(m: &Matrix) -> [[f32; 4]; 4] {
+    [
+        [m.m0, m.m4, m.m8, m.m12],
+        [m.m1, m.m5, m.m9, m.m13],
+        [m.m2, m.m6, m.m10, m.m14],
+        [m.m3, m.m7, m.m11, m.m15],
+    ]
+}
+
+/// Inverse of `m` by Gauss-Jordan elimination with partial pivoting, or `None` when `m` is
+/// singular (a zero scale, a projection onto a plane...)
+pub fn invert(m: &Matrix) -> Option<Matrix> {
+    let mut a = to_rows(m);
+    let mut inverse = [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+
+    for column in 0..4 {
+        // The largest remaining entry of the column keeps the elimination stable
+        let pivot_row = (column..4).max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))?;
+        if a[pivot_row][column].abs() < SINGULAR_PIVOT_EPSILON {
+            return None;
+        }
+        a.swap(column, pivot_row);
+        inverse.swap(column, pivot_row);
+
+        let pivot = a[column][column];
+        for k in 0..4 {
+            a[column][k] /= pivot;
+            inverse[column][k] /= pivot;
+        }
+
+        for row in 0..4 {
+            if row != column {
+                let factor = a[row][column];
+                for k in 0..4 {
+                    a[row][k] -= factor * a[column][k];
+                    inverse[row][k] -= factor * inverse[column][k];
+                }
+            }
+        }
+    }
+
+    let r = inverse;
+    Some(new_matrix4(
+        r[0][0], r[0][1], r[0][2], r[0][3],
+        r[1][0], r[1][1], r[1][2], r[1][3],
+        r[2][0], r[2][1], r[2][2], r[2][3],
+        r[3][0], r[3][1], r[3][2], r[3][3],
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::{FRAC_PI_3, FRAC_PI_4};
+
+    fn assert_close(actual: Vector3, expected: Vector3) {
+        assert!((actual - expected).length() < 1e-5, "expected {:?}, got {:?}", expected, actual);
+    }
+
+    #[test]
+    fn normal_matrix_rotates_normals_with_the_model() {
+        let rotation = axis_angle_matrix(Vector3::new(0.0, 0.0, 1.0), FRAC_PI_4);
+        let normal = transform_direction(&normal_matrix(&rotation), Vector3::new(1.0, 0.0, 0.0)).normalized();
+        let half = FRAC_PI_4.cos();
+        assert_close(normal, Vector3::new(half, half, 0.0));
+    }
+
+    #[test]
+    fn rotation_between_takes_one_direction_onto_the_other() {
+        let up = Vector3::new(0.0, 1.0, 0.0);
+        for to in [Vector3::new(0.990, -0.139, 0.0), Vector3::new(0.0, 0.0, 2.0), up, -up] {
+            assert_close(transform_direction(&rotation_between(up, to), up), to.normalized());
+        }
+        // Half a turn still keeps lengths and right angles
+        let flip = rotation_between(up, -up);
+        assert_close(transform_direction(&flip, Vector3::new(1.0, 0.0, 0.0)).cross(transform_direction(&flip, up)), Vector3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn normal_matrix_keeps_normals_perpendicular_under_non_uniform_scale() {
+        let scale = new_matrix3(
+            2.0, 0.0, 0.0,
+            0.0, 0.5, 0.0,
+            0.0, 0.0, 1.0,
+        );
+        // Surface x + y = 1: tangent (1, -1, 0), normal (1, 1, 0)
+        let tangent = transform_direction(&scale, Vector3::new(1.0, -1.0, 0.0));
+        let normal = transform_direction(&normal_matrix(&scale), Vector3::new(1.0, 1.0, 0.0));
+        assert!(tangent.dot(normal).abs() < 1e-5);
+        // Transforming it like a direction would tilt it off the surface
+        assert!(tangent.dot(transform_direction(&scale, Vector3::new(1.0, 1.0, 0.0))).abs() > 1.0);
+    }
+
+    /// Clip-space position of a point under `projection`: (x, y, z) and w
+    fn clip(projection: &Matrix, p: Vector3) -> (Vector3, f32) {
+        let m = projection;
+        let xyz = Vector3::new(
+            m.m0 * p.x + m.m4 * p.y + m.m8 * p.z + m.m12,
+            m.m1 * p.x + m.m5 * p.y + m.m9 * p.z + m.m13,
+            m.m2 * p.x + m.m6 * p.y + m.m10 * p.z + m.m14,
+        );
+        (xyz, m.m3 * p.x + m.m7 * p.y + m.m11 * p.z + m.m15)
+    }
+
+    /// NDC position of a point (projection, then perspective divide)
+    fn project(projection: &Matrix, point: Vector3) -> Vector3 {
+        let (xyz, w) = clip(projection, point);
+        xyz / w
+    }
+
+    #[test]
+    fn perspective_maps_the_near_and_far_planes_to_the_depth_range() {
+        let (near, far) = (10.0, 1000.0);
+        let projection = perspective(FRAC_PI_3, 4.0 / 3.0, near, far);
+        assert!((project(&projection, Vector3::new(0.0, 0.0, -near)).z + 1.0).abs() < 1e-5);
+        assert!((project(&projection, Vector3::new(0.0, 0.0, -far)).z - 1.0).abs() < 1e-4);
+        // Depth grows with distance, and w is the distance in front of the camera
+        assert!(project(&projection, Vector3::new(0.0, 0.0, -50.0)).z < project(&projection, Vector3::new(0.0, 0.0, -500.0)).z);
+        let (_, w) = clip(&projection, Vector3::new(0.0, 0.0, -42.0));
+        assert!((w - 42.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn perspective_puts_the_edges_of_the_field_of_view_on_the_ndc_edges() {
+        let (fov, aspect) = (FRAC_PI_3, 2.0);
+        let projection = perspective(fov, aspect, 1.0, 100.0);
+        for distance in [5.0, 50.0] {
+            // Top-right corner of the view at this distance, then twice as close to the axis
+            let half_height = distance * (fov / 2.0).tan();
+            let corner = project(&projection, Vector3::new(half_height * aspect, half_height, -distance));
+            assert_close(Vector3::new(corner.x, corner.y, 0.0), Vector3::new(1.0, 1.0, 0.0));
+            let inner = project(&projection, Vector3::new(half_height * aspect / 2.0, -half_height / 2.0, -distance));
+            assert_close(Vector3::new(inner.x, inner.y, 0.0), Vector3::new(0.5, -0.5, 0.0));
+        }
+    }
+
+    #[test]
+    fn look_at_puts_the_target_in_the_middle_of_the_view() {
+        let projection = perspective(FRAC_PI_3, 4.0 / 3.0, 1.0, 1000.0);
+        let target = Vector3::new(12.0, -3.0, 40.0);
+        // The last eye is almost straight above the target, along `up`
+        for eye in [Vector3::zero(), Vector3::new(-50.0, 20.0, 90.0), Vector3::new(12.0, 100.0, 40.0)] {
+            let camera = look_at(eye, target, Vector3::new(0.0, 1.0, 0.0)) * projection;
+            let center = project(&camera, target);
+            assert_close(Vector3::new(center.x, center.y, 0.0), Vector3::zero());
+            // In front of the camera, as far as it is from the eye
+            let (_, w) = clip(&camera, target);
+            assert!((w - (target - eye).length()).abs() < 1e-3, "w {} from {:?}", w, eye);
+        }
+    }
+
+    #[test]
+    fn viewport_transform_maps_ndc_onto_pixels() {
+        let (width, height) = (800, 600);
+        assert_close(viewport_transform(Vector3::new(0.0, 0.0, 0.25), width, height), Vector3::new(400.0, 300.0, 0.25));
+        // NDC +Y is up: the top-left corner is (-1, 1)
+        assert_close(viewport_transform(Vector3::new(-1.0, 1.0, -1.0), width, height), Vector3::new(0.0, 0.0, -1.0));
+        assert_close(viewport_transform(Vector3::new(1.0, -1.0, 1.0), width, height), Vector3::new(800.0, 600.0, 1.0));
+    }
+
+    fn assert_identity(m: &Matrix) {
+        for (row, values) in to_rows(m).iter().enumerate() {
+            for (column, value) in values.iter().enumerate() {
+                let expected = if row == column { 1.0 } else { 0.0 };
+                assert!((value - expected).abs() < 1e-5, "entry ({}, {}) is {}", row, column, value);
+            }
+        }
+    }
+
+    #[test]
+    fn invert_undoes_a_translate_rotate_scale() {
+        let translation = new_matrix4(
+            1.0, 0.0, 0.0, 12.0,
+            0.0, 1.0, 0.0, -3.5,
+            0.0, 0.0, 1.0, 40.0,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        let rotation = axis_angle_matrix(Vector3::new(1.0, 2.0, -0.5), 0.8);
+        let scale = new_matrix3(
+            3.0, 0.0, 0.0,
+            0.0, 0.5, 0.0,
+            0.0, 0.0, 2.0,
+        );
+        let m = scale * rotation * translation;
+
+        let inverse = invert(&m).expect("invertible");
+        assert_identity(&(m * inverse));
+        assert_identity(&(inverse * m));
+    }
+
+    #[test]
+    fn invert_rejects_singular_matrices() {
+        let flatten = new_matrix3(
+            1.0, 0.0, 0.0,
+            0.0, 0.0, 0.0,
+            0.0, 0.0, 1.0,
+        );
+        assert!(invert(&flatten).is_none());
+        // Two equal rows
+        let repeated = new_matrix4(
+            1.0, 2.0, 3.0, 4.0,
+            1.0, 2.0, 3.0, 4.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        assert!(invert(&repeated).is_none());
+    }
+}
@@ -1,21 +1,17 @@
-use raylib::prelude::*;
+use crate::shaders::PlanetType;
 
 /// Represents a moon orbiting a planet
-#[allow(dead_code)]
 pub struct Moon {
-    pub name: String,
-    pub scale: f32,
-    pub orbit_radius: f32,
-    pub orbit_speed: f32,
-    pub rotation_speed: f32,
-    pub planet_type: u32, // For shader selection
+    pub scale: f32,          // Radius in world units, the same units as orbit_radius
+    pub orbit_radius: f32,   // Distance from the planet's center
+    pub orbit_speed: f32,    // Radians per time unit around the planet
+    pub rotation_speed: f32, // Radians per time unit around the moon's own Y axis
+    pub planet_type: PlanetType, // For shader selection
 }
 
 impl Moon {
-    #[allow(dead_code)]
-    pub fn new(name: &str, scale: f32, orbit_radius: f32, orbit_speed: f32, rotation_speed: f32, planet_type: u32) -> Self {
+    pub fn new(scale: f32, orbit_radius: f32, orbit_speed: f32, rotation_speed: f32, planet_type: PlanetType) -> Self {
         Moon {
-            name: name.to_string(),
             scale,
             orbit_radius,
             orbit_speed,
@@ -1,7 +1,11 @@
 use crate::vertex::Vertex;
+use crate::tangents::compute_tangents;
 use crate::texture::Texture;
 use raylib::math::{Vector2, Vector3};
 use tobj;
+use std::fmt;
+use std::fmt::Write as _;
+use std::fs;
 use std::path::Path;
 
 #[derive(Clone, Debug)]
@@ -12,24 +16,193 @@ pub struct Material {
     pub specular: Vector3,
     pub shininess: f32,
     pub texture_path: Option<String>,
+    pub emissive_texture_path: Option<String>, // MTL `map_Ke`
+    pub normal_texture_path: Option<String>,   // MTL `map_Bump`/`bump`/`norm` (tangent-space normal map)
+    pub uv_scale: Vector2,  // `-s` option of the texture line, (1, 1) by default
+    pub uv_offset: Vector2, // `-o` option of the texture line, (0, 0) by default
 }
 
+impl Material {
+    /// Texture coordinates after the material's UV scale and offset, where every map of the
+    /// material is sampled
+    pub fn transform_uv(&self, uv: Vector2) -> Vector2 {
+        Vector2::new(uv.x * self.uv_scale.x + self.uv_offset.x, uv.y * self.uv_scale.y + self.uv_offset.y)
+    }
+}
+
+/// Optional processing applied while loading an OBJ file
+#[derive(Clone, Debug)]
+pub struct ObjLoadOptions {
+    /// Flip faces whose winding disagrees with their vertex normals (heuristic, off by default)
+    pub fix_winding: bool,
+    /// For meshes without normals: average the faces around each vertex (on by default) instead
+    /// of giving every face its own flat normal
+    pub smooth_normals: bool,
+    /// Replace a diffuse texture that fails to load with a magenta/black checkerboard
+    pub missing_texture_checker: bool,
+    /// Use `1 - v` for texture coordinates, for exporters whose V axis points the other way
+    pub flip_texcoord_v: bool,
+    /// Axis the file treats as up; rotated onto Y before the usual Y flip
+    pub up_axis: UpAxis,
+    /// Handedness of the file's coordinate system; left-handed files are mirrored into right-handed
+    pub handedness: Handedness,
+}
+
+impl Default for ObjLoadOptions {
+    fn default() -> Self {
+        ObjLoadOptions {
+            fix_winding: false,
+            smooth_normals: true,
+            missing_texture_checker: false,
+            flip_texcoord_v: false,
+            up_axis: UpAxis::default(),
+            handedness: Handedness::default(),
+        }
+    }
+}
+
+/// Up axis of the coordinate system an OBJ file was exported in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UpAxis {
+    #[default]
+    Y, // The OBJ convention (Maya, Blender's OBJ exporter with default settings)
+    Z, // CAD tools, 3ds Max, Blender's native axes
+}
+
+/// Handedness of the coordinate system an OBJ file was exported in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Handedness {
+    #[default]
+    Right, // The OBJ convention
+    Left,  // e.g. DirectX/Unity-style exporters
+}
+
+impl ObjLoadOptions {
+    /// Converts a position or normal from the file's axes into Y-up right-handed axes
+    /// (the OBJ convention the rest of the loader assumes). Z-up is a rotation, so winding is
+    /// kept; the left-handed mirror also needs the faces reversed (see `reverses_winding`).
+    fn to_y_up_right_handed(&self, v: Vector3) -> Vector3 {
+        let v = match self.up_axis {
+            UpAxis::Y => v,
+            UpAxis::Z => Vector3::new(v.x, v.z, -v.y), // -90° about X: +Z up becomes +Y up
+        };
+        match self.handedness {
+            Handedness::Right => v,
+            Handedness::Left => Vector3::new(v.x, v.y, -v.z),
+        }
+    }
+
+    /// Whether the import transform is a mirror, which turns front faces into back faces
+    fn reverses_winding(&self) -> bool {
+        self.handedness == Handedness::Left
+    }
+}
+
+/// Failures of `Obj::load`
+#[derive(Debug)]
+pub enum ObjError {
+    /// tobj could not read or parse the file
+    Load(tobj::LoadError),
+    /// The file parsed but has no triangle with a non-zero area (empty or all-degenerate)
+    NoGeometry(String), // Path of the file
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Load(e) => write!(f, "{}", e),
+            ObjError::NoGeometry(path) => write!(f, "{} has no renderable triangles (empty or all zero-area)", path),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+impl From<tobj::LoadError> for ObjError {
+    fn from(e: tobj::LoadError) -> Self {
+        ObjError::Load(e)
+    }
+}
+
+/// Size of the stand-in for missing textures: 64x64 texels in 8x8 squares
+const CHECKER_TEXTURE_SIZE: u32 = 64;
+const CHECKER_SQUARE_SIZE: u32 = 8;
+
 pub struct Obj {
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
     pub materials: Vec<Material>,
     pub mesh_materials: Vec<Option<usize>>, // Material index for each mesh
     pub texture: Option<Texture>,
+    pub emissive_texture: Option<Texture>,
+    pub normal_texture: Option<Texture>,
+    pub emissive_material: Option<usize>, // Material the emissive texture came from, for its UV transform
+    pub normal_material: Option<usize>,   // Material the normal texture came from
+    pub has_normals: bool,    // Every mesh in the file provided vertex normals
+    pub has_tex_coords: bool, // Every mesh in the file provided UVs
+    pub source_bounds: (Vector3, Vector3), // Bounding box in file coordinates, before normalization
+}
+
+/// Summary of a loaded model, to spot why it renders black (no normals), untextured (no UVs) or tiny
+#[derive(Clone, Debug)]
+pub struct MeshStats {
+    pub vertex_count: usize,
+    pub triangle_count: usize,
+    pub has_normals: bool,
+    pub has_tex_coords: bool,
+    pub material_count: usize,
+    pub bounds_min: Vector3, // File coordinates
+    pub bounds_max: Vector3,
+    pub zero_area_triangles: usize, // Includes faces that repeat a vertex index
+}
+
+/// Twice the area below which a face counts as zero-area (normalized model space)
+const ZERO_AREA_EPSILON: f32 = 1e-10;
+
+impl std::fmt::Display for MeshStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let yes_no = |present: bool| if present { "sí" } else { "NO" };
+        let size = self.bounds_max - self.bounds_min;
+        write!(
+            f,
+            "{} vértices, {} triángulos, normales: {}, UVs: {}, materiales: {}, caja: ({:.3}, {:.3}, {:.3})–({:.3}, {:.3}, {:.3}) tamaño {:.3}x{:.3}x{:.3}, caras de área cero: {}",
+            self.vertex_count,
+            self.triangle_count,
+            yes_no(self.has_normals),
+            yes_no(self.has_tex_coords),
+            self.material_count,
+            self.bounds_min.x,
+            self.bounds_min.y,
+            self.bounds_min.z,
+            self.bounds_max.x,
+            self.bounds_max.y,
+            self.bounds_max.z,
+            size.x,
+            size.y,
+            size.z,
+            self.zero_area_triangles,
+        )
+    }
 }
 
 impl Obj {
-    pub fn load(path: &str) -> Result<Self, tobj::LoadError> {
+    pub fn load(path: &str) -> Result<Self, ObjError> {
+        Self::load_with_options(path, &ObjLoadOptions::default())
+    }
+
+    /// Fails with `ObjError::NoGeometry` when the file yields nothing to draw, so the caller can
+    /// substitute a stand-in mesh instead of silently rendering nothing
+    pub fn load_with_options(path: &str, options: &ObjLoadOptions) -> Result<Self, ObjError> {
         let (models, materials_result) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS)?;
 
         let mut vertices = Vec::new();
         let mut indices = Vec::new();
         let mut mesh_materials = Vec::new();
         let mut materials = Vec::new();
+        let mut has_normals = true;
+        let mut has_tex_coords = true;
+        let mut source_min = Vector3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut source_max = Vector3::new(f32::MIN, f32::MIN, f32::MIN);
 
         // Process materials if available
         if let Ok(mats) = materials_result {
@@ -54,8 +227,24 @@ impl Obj {
 
                 let shininess = mat.shininess.unwrap_or(32.0);
                 
-                // Get texture path if available
-                let texture_path = mat.diffuse_texture.clone();
+                // Get texture path if available; tobj keeps the map options in the string
+                let diffuse_map = mat.diffuse_texture.as_deref().map(parse_texture_map);
+                let texture_path = diffuse_map.as_ref().map(|map| map.path.clone());
+                let (uv_scale, uv_offset) = diffuse_map
+                    .as_ref()
+                    .map(|map| (map.uv_scale, map.uv_offset))
+                    .unwrap_or((Vector2::new(1.0, 1.0), Vector2::zero()));
+                // tobj has no dedicated field for map_Ke, it ends up in the unknown params
+                let emissive_texture_path = mat
+                    .unknown_param
+                    .get("map_Ke")
+                    .map(|spec| parse_texture_map(spec).path);
+                // tobj reads map_Bump into `normal_texture`; the other spellings stay unknown
+                let normal_texture_path = mat
+                    .normal_texture
+                    .as_deref()
+                    .or_else(|| ["map_bump", "bump", "norm"].iter().find_map(|key| mat.unknown_param.get(*key).map(String::as_str)))
+                    .map(|spec| parse_texture_map(spec).path);
 
                 materials.push(Material {
                     name: mat.name,
@@ -64,30 +253,34 @@ impl Obj {
                     specular,
                     shininess,
                     texture_path,
+                    emissive_texture_path,
+                    normal_texture_path,
+                    uv_scale,
+                    uv_offset,
                 });
             }
         }
         
-        // Try to load texture from the first material that has one
-        let mut texture = None;
+        // Try to load textures from the first material that has one
         let base_path = std::path::Path::new(path).parent().unwrap_or(std::path::Path::new("."));
-        
-        for mat in &materials {
-            if let Some(tex_path) = &mat.texture_path {
-                let full_path = base_path.join(tex_path);
-                if let Ok(tex) = Texture::load(full_path.to_str().unwrap_or("")) {
-                    println!("✓ Loaded texture: {:?}", full_path);
-                    texture = Some(tex);
-                    break;
-                } else {
-                    eprintln!("✗ Failed to load texture: {:?}", full_path);
-                }
-            }
+        let mut texture = load_first_texture(&materials, base_path, |mat| mat.texture_path.as_ref()).map(|(tex, _)| tex);
+        if texture.is_none() && options.missing_texture_checker && materials.iter().any(|mat| mat.texture_path.is_some()) {
+            eprintln!("✗ Using checkerboard for the missing texture of {}", path);
+            texture = Some(Texture::checkerboard(CHECKER_TEXTURE_SIZE, CHECKER_TEXTURE_SIZE, CHECKER_SQUARE_SIZE));
         }
+        let (emissive_texture, emissive_material) =
+            load_first_texture(&materials, base_path, |mat| mat.emissive_texture_path.as_ref()).unzip();
+        let (normal_texture, normal_material) =
+            load_first_texture(&materials, base_path, |mat| mat.normal_texture_path.as_ref()).unzip();
 
         for model in models {
             let mesh = &model.mesh;
             let num_vertices = mesh.positions.len() / 3;
+            has_normals &= !mesh.normals.is_empty();
+            has_tex_coords &= !mesh.texcoords.is_empty();
+
+            // Mesh indices are local, so offset them past the vertices of earlier meshes
+            let index_offset = vertices.len() as u32;
 
             // Store material index for this mesh
             let material_idx = mesh.material_id;
@@ -114,6 +307,9 @@ impl Obj {
                 max_z = max_z.max(z);
             }
 
+            source_min = Vector3::new(source_min.x.min(min_x), source_min.y.min(min_y), source_min.z.min(min_z));
+            source_max = Vector3::new(source_max.x.max(max_x), source_max.y.max(max_y), source_max.z.max(max_z));
+
             // Calculate center and scale
             let center = Vector3::new(
                 (min_x + max_x) / 2.0,
@@ -129,18 +325,20 @@ impl Obj {
             // Scale to fit in [-1, 1]
             let scale = if max_size > 0.0 { 2.0 / max_size } else { 1.0 };
 
-            // Second pass: normalize vertices
+            // Second pass: normalize vertices, converting the file's axes first
             for i in 0..num_vertices {
                 let x = (mesh.positions[i * 3] - center.x) * scale;
-                let y = -(mesh.positions[i * 3 + 1] - center.y) * scale; // Flip Y
+                let y = (mesh.positions[i * 3 + 1] - center.y) * scale;
                 let z = (mesh.positions[i * 3 + 2] - center.z) * scale;
-                let position = Vector3::new(x, y, z);
+                let position = options.to_y_up_right_handed(Vector3::new(x, y, z));
+                let position = Vector3::new(position.x, -position.y, position.z); // Flip Y
 
                 let normal = if !mesh.normals.is_empty() {
                     let nx = mesh.normals[i * 3];
                     let ny = mesh.normals[i * 3 + 1];
                     let nz = mesh.normals[i * 3 + 2];
-                    Vector3::new(nx, -ny, nz)
+                    let normal = options.to_y_up_right_handed(Vector3::new(nx, ny, nz));
+                    Vector3::new(normal.x, -normal.y, normal.z)
                 } else {
                     Vector3::zero()
                 };
@@ -148,6 +346,7 @@ impl Obj {
                 let tex_coords = if !mesh.texcoords.is_empty() {
                     let u = mesh.texcoords[i * 2];
                     let v = mesh.texcoords[i * 2 + 1];
+                    let v = if options.flip_texcoord_v { 1.0 - v } else { v };
                     Vector2::new(u, v)
                 } else {
                     Vector2::zero()
@@ -174,9 +373,15 @@ impl Obj {
                     Vector3::new(1.0, 1.0, 1.0)
                 };
                 
+                // Texture lookups go through the material's UV scale/offset (shared by all its maps)
+                let sample_uv = match material_idx.and_then(|mat_idx| materials.get(mat_idx)) {
+                    Some(mat) => mat.transform_uv(tex_coords),
+                    None => tex_coords,
+                };
+
                 // If texture is available, sample it and blend with material color
                 if let Some(ref tex) = texture {
-                    let tex_color = tex.sample_bilinear(tex_coords.x, tex_coords.y);
+                    let tex_color = tex.sample_bilinear(sample_uv.x, sample_uv.y);
                     // Blend texture 70% with material 30% for nice procedural/texture mix
                     material_color = Vector3::new(
                         tex_color.x * 0.7 + material_color.x * 0.3,
@@ -189,18 +394,95 @@ impl Obj {
                 vertex.color = material_color;
                 vertices.push(vertex);
             }
-            indices.extend_from_slice(&mesh.indices);
+            let first_index = indices.len();
+            indices.extend(mesh.indices.iter().map(|&index| index + index_offset));
+            if options.reverses_winding() {
+                for face in indices[first_index..].chunks_exact_mut(3) {
+                    face.swap(1, 2);
+                }
+            }
+
+            // Without normals every face would light black
+            if mesh.normals.is_empty() {
+                compute_normals(&mut vertices, &mut indices[first_index..], index_offset as usize, options.smooth_normals);
+            }
         }
 
+        if indices.chunks_exact(3).all(|face| is_zero_area(&vertices, face)) {
+            return Err(ObjError::NoGeometry(path.to_string()));
+        }
+
+        if options.fix_winding {
+            let flipped = correct_winding(&vertices, &mut indices);
+            if flipped > 0 {
+                println!("✓ Fixed winding of {} faces in {}", flipped, path);
+            }
+        }
+
+        compute_tangents(&mut vertices, &indices);
+
         Ok(Obj { 
             vertices, 
             indices,
             materials,
             mesh_materials,
             texture,
+            emissive_texture,
+            normal_texture,
+            emissive_material,
+            normal_material,
+            has_normals,
+            has_tex_coords,
+            source_bounds: (source_min, source_max),
         })
     }
 
+    /// Model built from procedural geometry (no materials or textures), e.g. the stand-in
+    /// for a file that fails to load. The mesh is expected to be normalized to [-1, 1].
+    pub fn from_mesh(mut vertices: Vec<Vertex>, indices: Vec<u32>) -> Self {
+        compute_tangents(&mut vertices, &indices);
+        let has_geometry = !vertices.is_empty();
+
+        Obj {
+            vertices,
+            indices,
+            materials: Vec::new(),
+            mesh_materials: Vec::new(),
+            texture: None,
+            emissive_texture: None,
+            normal_texture: None,
+            emissive_material: None,
+            normal_material: None,
+            has_normals: has_geometry,
+            has_tex_coords: has_geometry,
+            source_bounds: (Vector3::new(-1.0, -1.0, -1.0), Vector3::new(1.0, 1.0, 1.0)),
+        }
+    }
+
+    /// Counts and sanity checks of the loaded geometry (see `MeshStats`)
+    pub fn validate(&self) -> MeshStats {
+        let zero_area_triangles = self
+            .indices
+            .chunks_exact(3)
+            .filter(|face| is_zero_area(&self.vertices, face))
+            .count();
+
+        MeshStats {
+            vertex_count: self.vertices.len(),
+            triangle_count: self.indices.len() / 3,
+            has_normals: self.has_normals,
+            has_tex_coords: self.has_tex_coords,
+            material_count: self.materials.len(),
+            bounds_min: self.source_bounds.0,
+            bounds_max: self.source_bounds.1,
+            zero_area_triangles,
+        }
+    }
+
+    /// Expands the index buffer into a flat triangle list (one vertex copy per corner).
+    /// Faces keep the file's winding: counter-clockwise front faces, which the Y flip mirrors
+    /// into clockwise ones on screen (what back-face culling in `triangle` expects).
+    /// Prefer rendering `vertices`/`indices` directly to avoid shading duplicates.
     pub fn get_vertex_array(&self) -> Vec<Vertex> {
         let mut vertex_array = Vec::new();
         for &index in &self.indices {
@@ -213,7 +495,454 @@ impl Obj {
         &self.texture
     }
 
+    pub fn get_emissive_texture(&self) -> &Option<Texture> {
+        &self.emissive_texture
+    }
+
+    pub fn get_normal_texture(&self) -> &Option<Texture> {
+        &self.normal_texture
+    }
+
+    /// Material whose UV scale/offset the emissive texture is sampled with
+    pub fn get_emissive_material(&self) -> Option<&Material> {
+        self.emissive_material.and_then(|index| self.materials.get(index))
+    }
+
+    /// Material whose UV scale/offset the normal texture is sampled with
+    pub fn get_normal_material(&self) -> Option<&Material> {
+        self.normal_material.and_then(|index| self.materials.get(index))
+    }
+
     pub fn get_materials(&self) -> &Vec<Material> {
         &self.materials
     }
 }
+
+/// Writes a triangle list (every 3 vertices a face) as an OBJ file, using the transformed
+/// (post vertex shader) positions. Vertices are not deduplicated; leftover vertices that
+/// don't complete a face are written but not referenced.
+pub fn write_obj(path: &str, vertices: &[Vertex]) -> std::io::Result<()> {
+    let mut contents = String::new();
+    let _ = writeln!(contents, "# Transformed mesh: {} vertices, {} faces", vertices.len(), vertices.len() / 3);
+    for vertex in vertices {
+        let p = vertex.transformed_position;
+        let _ = writeln!(contents, "v {} {} {}", p.x, p.y, p.z);
+    }
+    for face in 0..vertices.len() / 3 {
+        // OBJ indices are 1-based
+        let first = face * 3 + 1;
+        let _ = writeln!(contents, "f {} {} {}", first, first + 1, first + 2);
+    }
+    fs::write(path, contents)
+}
+
+/// Texture statement of an MTL file split into the file name and the UV options we honor
+struct TextureMap {
+    path: String,
+    uv_scale: Vector2,
+    uv_offset: Vector2,
+}
+
+/// Parses `[options] file` from a `map_*` line. `-s u v w` and `-o u v w` (v, w optional,
+/// defaulting to 1 and 0) become the UV scale/offset; other known options are skipped along with their arguments.
+fn parse_texture_map(spec: &str) -> TextureMap {
+    let tokens: Vec<&str> = spec.split_whitespace().collect();
+    let mut map = TextureMap {
+        path: String::new(),
+        uv_scale: Vector2::new(1.0, 1.0),
+        uv_offset: Vector2::zero(),
+    };
+    let mut path_tokens = Vec::new();
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = tokens[i];
+        i += 1;
+
+        match token {
+            "-s" | "-o" | "-t" => {
+                // Up to three numeric arguments
+                let mut values = Vec::new();
+                while values.len() < 3 {
+                    match tokens.get(i).and_then(|value| value.parse::<f32>().ok()) {
+                        Some(value) => {
+                            values.push(value);
+                            i += 1;
+                        }
+                        None => break,
+                    }
+                }
+                let u = values.first().copied();
+                let v = values.get(1).copied();
+                match token {
+                    "-s" => map.uv_scale = Vector2::new(u.unwrap_or(1.0), v.unwrap_or(1.0)),
+                    "-o" => map.uv_offset = Vector2::new(u.unwrap_or(0.0), v.unwrap_or(0.0)),
+                    _ => {}
+                }
+            }
+            "-mm" => i += 2,
+            "-blendu" | "-blendv" | "-bm" | "-boost" | "-cc" | "-clamp" | "-imfchan" | "-texres" | "-type" => i += 1,
+            _ => path_tokens.push(token),
+        }
+    }
+
+    // File names may contain spaces
+    map.path = path_tokens.join(" ");
+    map
+}
+
+/// Loads the texture named by `path_of` from the first material that has one and loads successfully, with that material's index
+fn load_first_texture(
+    materials: &[Material],
+    base_path: &std::path::Path,
+    path_of: impl Fn(&Material) -> Option<&String>,
+) -> Option<(Texture, usize)> {
+    for (index, mat) in materials.iter().enumerate() {
+        if let Some(tex_path) = path_of(mat) {
+            let full_path = base_path.join(tex_path);
+            if let Ok(tex) = Texture::load(full_path.to_str().unwrap_or("")) {
+                println!("✓ Loaded texture: {:?}", full_path);
+                return Some((tex, index));
+            } else {
+                eprintln!("✗ Failed to load texture: {:?}", full_path);
+            }
+        }
+    }
+    None
+}
+
+/// Whether the face (three indices into `vertices`) has zero area, including faces that repeat a vertex
+fn is_zero_area(vertices: &[Vertex], face: &[u32]) -> bool {
+    let a = vertices[face[0] as usize].position;
+    let b = vertices[face[1] as usize].position;
+    let c = vertices[face[2] as usize].position;
+    (b - a).cross(c - a).length() < ZERO_AREA_EPSILON
+}
+
+/// Outward normal of a face scaled by twice its area. The loader's Y flip mirrors the mesh,
+/// so its front faces wind clockwise by the right-hand rule and the cross product is reversed.
+fn face_normal(a: Vector3, b: Vector3, c: Vector3) -> Vector3 {
+    (c - a).cross(b - a)
+}
+
+/// Fills in the normals of the mesh whose vertices start at `first_vertex` and whose faces are
+/// `faces`. Smooth: each vertex gets the area-weighted average of the faces around it. Flat: every
+/// face gets its own copies of its vertices, carrying the face normal (the indices are rewritten).
+fn compute_normals(vertices: &mut Vec<Vertex>, faces: &mut [u32], first_vertex: usize, smooth: bool) {
+    if smooth {
+        for vertex in &mut vertices[first_vertex..] {
+            vertex.normal = Vector3::zero();
+        }
+        for face in faces.chunks_exact(3) {
+            let [a, b, c] = [face[0], face[1], face[2]].map(|index| index as usize);
+            let normal = face_normal(vertices[a].position, vertices[b].position, vertices[c].position);
+            for index in [a, b, c] {
+                vertices[index].normal = vertices[index].normal + normal;
+            }
+        }
+        // Vertices only used by zero-area faces keep a zero normal
+        for vertex in &mut vertices[first_vertex..] {
+            if vertex.normal.length() > 0.0 {
+                vertex.normal = vertex.normal.normalized();
+            }
+        }
+    } else {
+        let mesh_vertices = vertices.split_off(first_vertex);
+        for face in faces.chunks_exact_mut(3) {
+            let corners = [face[0], face[1], face[2]].map(|index| &mesh_vertices[index as usize - first_vertex]);
+            let normal = face_normal(corners[0].position, corners[1].position, corners[2].position);
+            let normal = if normal.length() > 0.0 { normal.normalized() } else { normal };
+            for (index, corner) in face.iter_mut().zip(corners) {
+                let mut vertex = corner.clone();
+                vertex.normal = normal;
+                *index = vertices.len() as u32;
+                vertices.push(vertex);
+            }
+        }
+    }
+}
+
+
+/// Reorders each triangle so its winding (see `face_normal`) agrees with
+/// the average of its vertex normals. Returns the number of faces that were flipped.
+/// Faces without usable vertex normals are left untouched.
+fn correct_winding(vertices: &[Vertex], indices: &mut [u32]) -> usize {
+    let mut flipped = 0;
+
+    for face in indices.chunks_exact_mut(3) {
+        let a = &vertices[face[0] as usize];
+        let b = &vertices[face[1] as usize];
+        let c = &vertices[face[2] as usize];
+
+        // Geometric normal from the current winding
+        let face_normal = face_normal(a.position, b.position, c.position);
+
+        // Normal the exporter intended for this face
+        let vertex_normal = a.normal + b.normal + c.normal;
+
+        let agreement = face_normal.x * vertex_normal.x
+            + face_normal.y * vertex_normal.y
+            + face_normal.z * vertex_normal.z;
+
+        if agreement < 0.0 {
+            face.swap(1, 2);
+            flipped += 1;
+        }
+    }
+
+    flipped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `contents` to a file in the temp directory and returns its path
+    fn write_temp_obj(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("lab5_obj_{}_{}.obj", name, std::process::id()));
+        fs::write(&path, contents).expect("temp OBJ written");
+        path.to_string_lossy().into_owned()
+    }
+
+    /// Faces whose winding disagrees with their vertex normals
+    fn disagreeing_faces(obj: &Obj) -> usize {
+        obj.indices
+            .chunks_exact(3)
+            .filter(|face| {
+                let [a, b, c] = [face[0], face[1], face[2]].map(|index| &obj.vertices[index as usize]);
+                face_normal(a.position, b.position, c.position).dot(a.normal + b.normal + c.normal) < 0.0
+            })
+            .count()
+    }
+
+    // A unit quad facing +Z whose second triangle is wound the wrong way
+    const MIXED_WINDING_QUAD: &str = "\
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vn 0 0 1
+f 1//1 2//1 3//1
+f 1//1 4//1 3//1
+";
+
+    #[test]
+    fn fix_winding_flips_faces_against_their_normals() {
+        let path = write_temp_obj("mixed_winding", MIXED_WINDING_QUAD);
+
+        let unchanged = Obj::load(&path).unwrap();
+        assert_eq!(disagreeing_faces(&unchanged), 1);
+
+        let options = ObjLoadOptions { fix_winding: true, ..ObjLoadOptions::default() };
+        let fixed = Obj::load_with_options(&path, &options).unwrap();
+        assert_eq!(disagreeing_faces(&fixed), 0);
+        // Only the order changes: the same corners make up each face
+        for (before, after) in unchanged.indices.chunks_exact(3).zip(fixed.indices.chunks_exact(3)) {
+            let (mut before, mut after) = (before.to_vec(), after.to_vec());
+            before.sort_unstable();
+            after.sort_unstable();
+            assert_eq!(before, after);
+        }
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn files_without_triangles_are_rejected() {
+        let empty = write_temp_obj("empty", "# Nothing but a comment\n");
+        assert!(matches!(Obj::load(&empty), Err(ObjError::NoGeometry(path)) if path == empty));
+
+        // Vertices but only a collapsed face
+        let degenerate = write_temp_obj("degenerate", "v 0 0 0\nv 1 0 0\nv 2 0 0\nf 1 2 3\n");
+        assert!(matches!(Obj::load(&degenerate), Err(ObjError::NoGeometry(_))));
+
+        let _ = fs::remove_file(empty);
+        let _ = fs::remove_file(degenerate);
+    }
+
+    /// Writes `texture` as a PNG and an MTL file next to the temp OBJs, with `{texture}` in
+    /// `statements` replaced by the PNG's name. Returns the `mtllib` line and the files written.
+    fn write_temp_material(name: &str, texture: &image::RgbaImage, statements: &str) -> (String, Vec<String>) {
+        let prefix = format!("lab5_obj_{}_{}", name, std::process::id());
+        let png = std::env::temp_dir().join(format!("{}.png", prefix));
+        texture.save(&png).expect("temp texture written");
+        let mtl = std::env::temp_dir().join(format!("{}.mtl", prefix));
+        fs::write(&mtl, statements.replace("{texture}", &format!("{}.png", prefix))).expect("temp MTL written");
+        let files = [png, mtl].map(|file| file.to_string_lossy().into_owned()).to_vec();
+        (format!("mtllib {}.mtl\n", prefix), files)
+    }
+
+    #[test]
+    fn emissive_and_normal_maps_keep_the_uv_transform_of_their_material() {
+        let white = image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 255, 255, 255]));
+        let statements = "newmtl plain\nKd 1 1 1\nnewmtl mapped\nmap_Kd -s 2 3 -o 0.25 0.5 {texture}\nmap_Ke {texture}\nmap_Bump {texture}\n";
+        let (mtllib, files) = write_temp_material("maps", &white, statements);
+        let path = write_temp_obj("maps", &format!("{}v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nusemtl mapped\nf 1/1 2/1 3/1\n", mtllib));
+
+        let obj = Obj::load(&path).unwrap();
+        for material in [obj.get_emissive_material(), obj.get_normal_material()] {
+            let material = material.expect("map loaded from the textured material");
+            assert_eq!(material.name, "mapped");
+            let uv = material.transform_uv(Vector2::new(0.5, 0.5));
+            assert!((uv.x - 1.25).abs() < 1e-6 && (uv.y - 2.0).abs() < 1e-6, "{:?}", uv);
+        }
+
+        for file in files.into_iter().chain([path]) {
+            let _ = fs::remove_file(file);
+        }
+    }
+
+    #[test]
+    fn flip_texcoord_v_samples_the_top_row_at_v_near_one() {
+        // 2x2 texture whose top row (red) differs from its bottom row (blue)
+        let red = image::Rgba([255, 0, 0, 255]);
+        let blue = image::Rgba([0, 0, 255, 255]);
+        let texture = image::RgbaImage::from_fn(2, 2, |_, y| if y == 0 { red } else { blue });
+        let (mtllib, files) = write_temp_material("flip_v", &texture, "newmtl quad\nmap_Kd {texture}\n");
+        // The first vertex sits near the top of the image in OBJ terms (v = 1 is up), the second near the bottom
+        let path = write_temp_obj(
+            "flip_v",
+            &format!("{}v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0.25 0.9\nvt 0.25 0.1\nusemtl quad\nf 1/1 2/2 3/2\n", mtllib),
+        );
+
+        let top_and_bottom = |flip_texcoord_v: bool| {
+            let options = ObjLoadOptions { flip_texcoord_v, ..ObjLoadOptions::default() };
+            let obj = Obj::load_with_options(&path, &options).unwrap();
+            let texture = obj.get_texture().as_ref().expect("texture loaded");
+            [0, 1].map(|index| {
+                let uv = obj.vertices[index].tex_coords;
+                texture.sample(uv.x, uv.y)
+            })
+        };
+        let (red, blue) = (Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(top_and_bottom(true), [red, blue]);
+        // Unflipped, v = 0.9 reads the image's bottom row
+        assert_eq!(top_and_bottom(false), [blue, red]);
+
+        for file in files.into_iter().chain([path]) {
+            let _ = fs::remove_file(file);
+        }
+    }
+
+    /// Corner positions and normals of every face, in winding order
+    fn faces(obj: &Obj) -> Vec<[(Vector3, Vector3); 3]> {
+        obj.indices
+            .chunks_exact(3)
+            .map(|face| [face[0], face[1], face[2]].map(|index| {
+                let vertex = &obj.vertices[index as usize];
+                (vertex.position, vertex.normal)
+            }))
+            .collect()
+    }
+
+    fn assert_same_faces(actual: &Obj, expected: &Obj) {
+        let (actual, expected) = (faces(actual), faces(expected));
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(&expected) {
+            for ((position, normal), (expected_position, expected_normal)) in actual.iter().zip(expected) {
+                assert!((*position - *expected_position).length() < 1e-5, "{:?} != {:?}", position, expected_position);
+                assert!((*normal - *expected_normal).length() < 1e-5, "{:?} != {:?}", normal, expected_normal);
+            }
+        }
+    }
+
+    /// Lopsided tetrahedron (2 wide, 1 deep, 4 tall) written with the given axes. `to_file`
+    /// maps Y-up right-handed coordinates into the file's; `reverse` swaps the order of each face.
+    fn tetrahedron(to_file: fn(Vector3) -> Vector3, reverse: bool) -> String {
+        let corners = [Vector3::new(0.0, 0.0, 0.0), Vector3::new(2.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 4.0, 0.0)];
+        let mut contents = String::new();
+        for corner in corners {
+            let v = to_file(corner);
+            let _ = writeln!(contents, "v {} {} {}", v.x, v.y, v.z);
+        }
+        for [a, b, c] in [[1, 3, 2], [1, 2, 4], [1, 4, 3], [2, 3, 4]] {
+            let (b, c) = if reverse { (c, b) } else { (b, c) };
+            let _ = writeln!(contents, "f {} {} {}", a, b, c);
+        }
+        contents
+    }
+
+    #[test]
+    fn z_up_files_load_like_their_y_up_equivalent() {
+        let y_up = write_temp_obj("tetra_y_up", &tetrahedron(|v| v, false));
+        // Y-up to Z-up: +Y becomes +Z, +Z becomes -Y
+        let z_up = write_temp_obj("tetra_z_up", &tetrahedron(|v| Vector3::new(v.x, -v.z, v.y), false));
+
+        let expected = Obj::load(&y_up).unwrap();
+        let options = ObjLoadOptions { up_axis: UpAxis::Z, ..ObjLoadOptions::default() };
+        let converted = Obj::load_with_options(&z_up, &options).unwrap();
+        assert_same_faces(&converted, &expected);
+
+        // The tall corner ends up on top: -1 after normalizing and flipping Y for the screen
+        let top = converted.vertices.iter().map(|vertex| vertex.position.y).fold(f32::MAX, f32::min);
+        assert!((top + 1.0).abs() < 1e-5);
+        // Read as Y-up, the same file lies on its side
+        let sideways = Obj::load(&z_up).unwrap();
+        let top = sideways.vertices.iter().map(|vertex| vertex.position.y).fold(f32::MAX, f32::min);
+        assert!(top > -0.5);
+
+        let _ = fs::remove_file(y_up);
+        let _ = fs::remove_file(z_up);
+    }
+
+    #[test]
+    fn left_handed_files_are_mirrored_and_rewound() {
+        let right = write_temp_obj("tetra_right", &tetrahedron(|v| v, false));
+        // Left-handed exporters flip Z and wind front faces the other way
+        let left = write_temp_obj("tetra_left", &tetrahedron(|v| Vector3::new(v.x, v.y, -v.z), true));
+
+        let expected = Obj::load(&right).unwrap();
+        let options = ObjLoadOptions { handedness: Handedness::Left, ..ObjLoadOptions::default() };
+        let converted = Obj::load_with_options(&left, &options).unwrap();
+        assert_same_faces(&converted, &expected);
+
+        let _ = fs::remove_file(right);
+        let _ = fs::remove_file(left);
+    }
+
+    // Cube without normals, faces wound counter-clockwise seen from outside
+    const CUBE_WITHOUT_NORMALS: &str = "\
+v -1 -1 -1
+v 1 -1 -1
+v 1 1 -1
+v -1 1 -1
+v -1 -1 1
+v 1 -1 1
+v 1 1 1
+v -1 1 1
+f 1 4 3 2
+f 5 6 7 8
+f 1 2 6 5
+f 4 8 7 3
+f 1 5 8 4
+f 2 3 7 6
+";
+
+    #[test]
+    fn missing_normals_are_computed_pointing_outward() {
+        let path = write_temp_obj("cube_without_normals", CUBE_WITHOUT_NORMALS);
+
+        for smooth_normals in [true, false] {
+            let options = ObjLoadOptions { smooth_normals, ..ObjLoadOptions::default() };
+            let cube = Obj::load_with_options(&path, &options).unwrap();
+            assert_eq!(cube.indices.len() / 3, 12);
+
+            for face in faces(&cube) {
+                // The cube is centered on the origin, so outward is away from it
+                let center = (face[0].0 + face[1].0 + face[2].0) / 3.0;
+                let winding = face_normal(face[0].0, face[1].0, face[2].0);
+                assert!(winding.dot(center) > 0.0, "face at {:?} is wound inward", center);
+                for (_, normal) in face {
+                    assert!((normal.length() - 1.0).abs() < 1e-5);
+                    assert!(normal.dot(center) > 0.0, "normal {:?} points inward at {:?}", normal, center);
+                    if !smooth_normals {
+                        // Flat normals are the face's own
+                        assert!((normal - winding.normalized()).length() < 1e-5);
+                    }
+                }
+            }
+        }
+
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,225 @@
+use crate::matrix::axis_angle_matrix;
+use raylib::math::{Vector2, Vector3};
+use raylib::prelude::Matrix;
+use std::f32::consts::PI;
+
+/// Newton iterations spent on Kepler's equation before giving up on convergence
+pub const KEPLER_ITERATIONS: u32 = 12;
+/// Change in the eccentric anomaly (radians) below which the solution is considered converged
+const KEPLER_TOLERANCE: f32 = 1e-6;
+/// Eccentricities are clamped below 1: the orbit must stay a closed ellipse
+const MAX_ECCENTRICITY: f32 = 0.99;
+
+/// Solves Kepler's equation `M = E - e sin E` for the eccentric anomaly `E` with Newton's method.
+/// If it doesn't converge within `KEPLER_ITERATIONS` the last estimate is kept, and if that is not
+/// a number the mean anomaly is returned instead (the circular-orbit answer).
+pub fn eccentric_anomaly(mean_anomaly: f32, eccentricity: f32) -> f32 {
+    let e = eccentricity.clamp(0.0, MAX_ECCENTRICITY);
+    // Wrapping to [-π, π] keeps the iteration well conditioned after long runs
+    let m = (mean_anomaly + PI).rem_euclid(2.0 * PI) - PI;
+
+    // Starting from π is more robust than starting from M on very eccentric orbits
+    let mut anomaly = if e > 0.8 { PI.copysign(m) } else { m };
+    for _ in 0..KEPLER_ITERATIONS {
+        let step = (anomaly - e * anomaly.sin() - m) / (1.0 - e * anomaly.cos());
+        anomaly -= step;
+        if step.abs() < KEPLER_TOLERANCE {
+            break;
+        }
+    }
+
+    let anomaly = if anomaly.is_finite() { anomaly } else { m };
+    // Undo the wrap so the result keeps increasing with the mean anomaly
+    anomaly + (mean_anomaly - m)
+}
+
+/// Position on an ellipse with semi-major axis `semi_major_axis` and the given eccentricity, with
+/// its focus (the body being orbited) at the origin and the perihelion on +X. The mean anomaly
+/// grows at a constant rate, so the body sweeps equal areas in equal times: it moves faster near
+/// the perihelion than near the aphelion. With eccentricity 0 this is a circle at constant speed.
+pub fn kepler_position(semi_major_axis: f32, eccentricity: f32, mean_anomaly: f32) -> Vector2 {
+    let e = eccentricity.clamp(0.0, MAX_ECCENTRICITY);
+    ellipse_point(semi_major_axis, e, eccentric_anomaly(mean_anomaly, e))
+}
+
+/// Point of the orbit ellipse at the given eccentric anomaly (0 = perihelion), in the same frame as
+/// `kepler_position`. Evenly spaced anomalies give evenly spread points, for tracing the ellipse.
+pub fn ellipse_point(semi_major_axis: f32, eccentricity: f32, eccentric_anomaly: f32) -> Vector2 {
+    let e = eccentricity.clamp(0.0, MAX_ECCENTRICITY);
+    let semi_minor_axis = semi_major_axis * (1.0 - e * e).sqrt();
+    Vector2::new(semi_major_axis * (eccentric_anomaly.cos() - e), semi_minor_axis * eccentric_anomaly.sin())
+}
+
+/// The focus of the ellipse `kepler_position` traces that is not at the origin: nothing sits
+/// there, `2ae` from the occupied focus towards the aphelion. It meets the origin on a circle.
+pub fn empty_focus(semi_major_axis: f32, eccentricity: f32) -> Vector2 {
+    let e = eccentricity.clamp(0.0, MAX_ECCENTRICITY);
+    Vector2::new(-2.0 * semi_major_axis * e, 0.0)
+}
+
+/// Ends of the major axis (perihelion, aphelion) and of the minor axis of the same ellipse. Both
+/// axes cross at its center, halfway between the foci.
+pub fn axis_ends(semi_major_axis: f32, eccentricity: f32) -> [(Vector2, Vector2); 2] {
+    let quarter = PI / 2.0;
+    let point = |anomaly: f32| ellipse_point(semi_major_axis, eccentricity, anomaly);
+    [(point(0.0), point(PI)), (point(quarter), point(-quarter))]
+}
+
+/// Tilts a position on the reference plane (XY) into an orbit inclined by `inclination` radians
+/// about the line of nodes, the axis through the origin at `ascending_node` radians from +X where
+/// the orbit crosses the plane. Points on the line of nodes stay put, so the orbit remains a
+/// closed ellipse around the same focus.
+pub fn incline(position: Vector2, inclination: f32, ascending_node: f32) -> Vector3 {
+    let (node_y, node_x) = ascending_node.sin_cos();
+    let (sin_i, cos_i) = inclination.sin_cos();
+
+    // Rodrigues' rotation about the (unit, in-plane) node axis: the part along the axis is kept,
+    // the part across it turns out of the plane
+    let along = position.x * node_x + position.y * node_y;
+    let across = Vector2::new(position.x - node_x * along, position.y - node_y * along);
+    let lift = node_x * position.y - node_y * position.x;
+    Vector3::new(node_x * along + across.x * cos_i, node_y * along + across.y * cos_i, lift * sin_i)
+}
+
+/// Rotation that carries the reference plane into the orbit's plane, as `incline` does points:
+/// the frame a body's satellites hang from, so they follow its inclination
+pub fn orbital_plane(inclination: f32, ascending_node: f32) -> Matrix {
+    let (node_y, node_x) = ascending_node.sin_cos();
+    axis_angle_matrix(Vector3::new(node_x, node_y, 0.0), inclination)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mean anomalies spread over a few turns, both signs
+    fn mean_anomalies() -> impl Iterator<Item = f32> {
+        (-40..=40).map(|i| i as f32 * 0.37)
+    }
+
+    #[test]
+    fn eccentric_anomaly_solves_keplers_equation() {
+        for e in [0.0, 0.5, 0.99] {
+            for m in mean_anomalies() {
+                let anomaly = eccentric_anomaly(m, e);
+                let residual = anomaly - e * anomaly.sin() - m;
+                assert!(residual.abs() < 1e-4, "e {} M {}: residual {}", e, m, residual);
+            }
+        }
+        // Circular orbits need no iteration: E = M
+        assert!((eccentric_anomaly(1.25, 0.0) - 1.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn orbital_plane_tilts_points_like_incline() {
+        for (inclination, node) in [(0.0, 0.0), (0.25, 1.2), (-0.6, 4.0), (PI / 2.0, 0.3)] {
+            let plane = orbital_plane(inclination, node);
+            for m in mean_anomalies() {
+                let p = kepler_position(10.0, 0.4, m);
+                let tilted = crate::matrix::transform_direction(&plane, Vector3::new(p.x, p.y, 0.0));
+                assert!((tilted - incline(p, inclination, node)).length() < 1e-4, "i {} node {} M {}", inclination, node, m);
+            }
+        }
+    }
+
+    #[test]
+    fn perihelion_is_on_positive_x() {
+        for e in [0.0, 0.5, 0.99] {
+            let perihelion = kepler_position(10.0, e, 0.0);
+            assert!((perihelion.x - 10.0 * (1.0 - e)).abs() < 1e-4 && perihelion.y.abs() < 1e-4);
+            let aphelion = kepler_position(10.0, e, PI);
+            assert!((aphelion.x + 10.0 * (1.0 + e)).abs() < 1e-3 && aphelion.y.abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn equal_times_sweep_equal_areas() {
+        let (a, e) = (10.0, 0.5);
+        let steps = 2000;
+        // Area swept from the focus over one twelfth of the period, as a fan of thin triangles
+        let swept_area = |start: f32| {
+            let span = 2.0 * PI / 12.0;
+            (0..steps)
+                .map(|i| {
+                    let p = kepler_position(a, e, start + span * i as f32 / steps as f32);
+                    let q = kepler_position(a, e, start + span * (i + 1) as f32 / steps as f32);
+                    (p.x * q.y - p.y * q.x) / 2.0
+                })
+                .sum::<f32>()
+        };
+
+        let ellipse_area = PI * a * a * (1.0 - e * e).sqrt();
+        for start in [0.0, 1.0, PI, 4.5] {
+            let area = swept_area(start);
+            assert!((area - ellipse_area / 12.0).abs() < ellipse_area * 1e-3, "swept {} from M = {}", area, start);
+        }
+    }
+
+    #[test]
+    fn bodies_move_faster_at_perihelion() {
+        let (a, e, dm) = (10.0, 0.5, 0.01);
+        let speed = |m: f32| (kepler_position(a, e, m + dm) - kepler_position(a, e, m)).length() / dm;
+        assert!(speed(0.0) > 2.0 * speed(PI), "perihelion {} vs aphelion {}", speed(0.0), speed(PI));
+    }
+
+    #[test]
+    fn incline_keeps_the_line_of_nodes_fixed() {
+        for node in [0.0, 0.8, 2.5, -1.9] {
+            let (sin, cos) = f32::sin_cos(node);
+            for distance in [-7.0, 3.0, 12.0] {
+                let on_node = Vector2::new(cos * distance, sin * distance);
+                let tilted = incline(on_node, 0.7, node);
+                assert!((tilted - Vector3::new(on_node.x, on_node.y, 0.0)).length() < 1e-4, "{:?} moved to {:?}", on_node, tilted);
+            }
+            // Across the node the point rises out of the plane by sin(i), keeping its distance
+            let across = Vector2::new(-sin * 5.0, cos * 5.0);
+            let tilted = incline(across, 0.7, node);
+            assert!((tilted.length() - 5.0).abs() < 1e-4);
+            assert!((tilted.z - 5.0 * 0.7_f32.sin()).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn zero_inclination_leaves_the_orbit_in_the_plane() {
+        let position = Vector2::new(3.0, -4.0);
+        let flat = incline(position, 0.0, 1.3);
+        assert!((flat - Vector3::new(3.0, -4.0, 0.0)).length() < 1e-5);
+    }
+
+    #[test]
+    fn the_distances_to_both_foci_add_up_to_the_major_axis() {
+        for e in [0.0, 0.3, 0.9] {
+            let focus = empty_focus(10.0, e);
+            for m in mean_anomalies() {
+                let p = kepler_position(10.0, e, m);
+                let sum = p.length() + (p - focus).length();
+                assert!((sum - 20.0).abs() < 1e-3, "e {} M {}: {}", e, m, sum);
+            }
+        }
+        // A circle has a single focus
+        assert_eq!(empty_focus(10.0, 0.0), Vector2::zero());
+    }
+
+    #[test]
+    fn the_axes_cross_halfway_between_the_foci() {
+        let (a, e) = (10.0, 0.6);
+        let [(perihelion, aphelion), (minor_a, minor_b)] = axis_ends(a, e);
+        assert!((perihelion - Vector2::new(4.0, 0.0)).length() < 1e-4);
+        assert!((aphelion - Vector2::new(-16.0, 0.0)).length() < 1e-4);
+        // The semi-minor axis is b = a sqrt(1 - e²)
+        assert!(((minor_a - minor_b).length() - 16.0).abs() < 1e-4);
+        let center = empty_focus(a, e) * 0.5;
+        for (from, to) in [(perihelion, aphelion), (minor_a, minor_b)] {
+            assert!(((from + to) * 0.5 - center).length() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn a_full_period_returns_to_the_start() {
+        for m in [0.3, 2.0, -1.1] {
+            let start = incline(kepler_position(10.0, 0.6, m), 0.4, 1.2);
+            let end = incline(kepler_position(10.0, 0.6, m + 2.0 * PI), 0.4, 1.2);
+            assert!((end - start).length() < 1e-3);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+/// Depth peeling: orders the translucent fragments of a frame into layers, nearest first, so they
+/// can be composited back to front whatever order they were drawn in.
+///
+/// `fragments` holds the (pixel index, depth) of every translucent fragment and `opaque_depth` the
+/// depth buffer once the opaque surfaces are in (smaller = closer). Each pass peels, at every
+/// pixel, the nearest fragment behind the one peeled there by the previous pass (kept in an
+/// auxiliary depth buffer) and in front of the opaque surface. Up to `peels` passes are made;
+/// fragments deeper than that are dropped. Every layer lists the indices into `fragments` it
+/// peeled, at most one per pixel.
+pub fn peel_layers(fragments: &[(usize, f32)], opaque_depth: &[f32], peels: usize) -> Vec<Vec<usize>> {
+    // Auxiliary depth buffer: (depth, fragment index) of the last layer peeled at each pixel. The
+    // index breaks ties, so fragments at exactly the same depth are peeled one after the other
+    let mut peeled: Vec<(f32, usize)> = vec![(f32::NEG_INFINITY, 0); opaque_depth.len()];
+    let mut layers = Vec::new();
+
+    for _ in 0..peels {
+        let mut nearest: Vec<Option<usize>> = vec![None; opaque_depth.len()];
+        for (index, &(pixel, depth)) in fragments.iter().enumerate() {
+            let behind_last = (depth, index) > peeled[pixel];
+            let in_front = depth < opaque_depth[pixel];
+            let nearer = nearest[pixel].is_none_or(|other| (depth, index) < (fragments[other].1, other));
+            if behind_last && in_front && nearer {
+                nearest[pixel] = Some(index);
+            }
+        }
+
+        let layer: Vec<usize> = nearest.into_iter().flatten().collect();
+        if layer.is_empty() {
+            break;
+        }
+        for &index in &layer {
+            let (pixel, depth) = fragments[index];
+            peeled[pixel] = (depth, index);
+        }
+        layers.push(layer);
+    }
+    layers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Depths peeled at `pixel`, layer by layer
+    fn peeled_depths(fragments: &[(usize, f32)], layers: &[Vec<usize>], pixel: usize) -> Vec<f32> {
+        layers
+            .iter()
+            .filter_map(|layer| layer.iter().find(|&&index| fragments[index].0 == pixel))
+            .map(|&index| fragments[index].1)
+            .collect()
+    }
+
+    #[test]
+    fn layers_come_out_nearest_first_whatever_the_submission_order() {
+        let opaque = [f32::INFINITY; 2];
+        let fragments = [(0, 0.7), (0, 0.2), (1, 0.5), (0, 0.4)];
+        let layers = peel_layers(&fragments, &opaque, 4);
+        assert_eq!(peeled_depths(&fragments, &layers, 0), vec![0.2, 0.4, 0.7]);
+        assert_eq!(peeled_depths(&fragments, &layers, 1), vec![0.5]);
+        // No pass is wasted once everything is peeled
+        assert_eq!(layers.len(), 3);
+    }
+
+    #[test]
+    fn opaque_surfaces_hide_what_is_behind_them() {
+        let fragments = [(0, 0.3), (0, 0.6), (0, 0.9)];
+        let layers = peel_layers(&fragments, &[0.5], 4);
+        assert_eq!(peeled_depths(&fragments, &layers, 0), vec![0.3]);
+    }
+
+    #[test]
+    fn layers_beyond_the_peel_count_are_dropped() {
+        let fragments = [(0, 0.1), (0, 0.2), (0, 0.3), (0, 0.4)];
+        let layers = peel_layers(&fragments, &[f32::INFINITY], 2);
+        assert_eq!(peeled_depths(&fragments, &layers, 0), vec![0.1, 0.2]);
+    }
+
+    #[test]
+    fn fragments_at_the_same_depth_are_all_peeled() {
+        let fragments = [(0, 0.5), (0, 0.5), (0, 0.5)];
+        let layers = peel_layers(&fragments, &[f32::INFINITY], 4);
+        assert_eq!(layers, vec![vec![0], vec![1], vec![2]]);
+    }
+}
@@ -0,0 +1,96 @@
+use raylib::prelude::*;
+use crate::framebuffer::{BlendMode, Framebuffer};
+
+/// A full-screen effect applied to the finished frame, before it is uploaded to the GPU
+pub trait PostEffect {
+    /// Name used to enable the effect from the config file
+    fn name(&self) -> &'static str;
+    fn apply(&self, framebuffer: &mut Framebuffer);
+}
+
+/// Ordered list of enabled post-process effects, run front to back
+pub struct PostProcessChain {
+    effects: Vec<Box<dyn PostEffect>>,
+}
+
+impl PostProcessChain {
+    pub fn new() -> Self {
+        PostProcessChain { effects: Vec::new() }
+    }
+
+    /// Builds the chain from effect names in the order given (e.g. the config's `post_effects`).
+    /// Unknown names are reported and skipped.
+    pub fn from_names(names: &[String]) -> Self {
+        let mut chain = PostProcessChain::new();
+        for name in names {
+            match effect_by_name(name) {
+                Some(effect) => chain.push(effect),
+                None => eprintln!("✗ Unknown post effect `{}`", name),
+            }
+        }
+        chain
+    }
+
+    pub fn push(&mut self, effect: Box<dyn PostEffect>) {
+        self.effects.push(effect);
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.effects.iter().map(|effect| effect.name()).collect()
+    }
+
+    pub fn apply(&self, framebuffer: &mut Framebuffer) {
+        for effect in &self.effects {
+            effect.apply(framebuffer);
+        }
+    }
+}
+
+fn effect_by_name(name: &str) -> Option<Box<dyn PostEffect>> {
+    match name {
+        "vignette" => Some(Box::new(Vignette::default())),
+        _ => None,
+    }
+}
+
+/// Darkens the frame towards the corners
+pub struct Vignette {
+    pub strength: f32, // Darkening at the corners (0 = none, 1 = black)
+    pub radius: f32,   // Normalized distance from the center where darkening starts
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Vignette {
+            strength: 0.6,
+            radius: 0.55,
+        }
+    }
+}
+
+impl PostEffect for Vignette {
+    fn name(&self) -> &'static str {
+        "vignette"
+    }
+
+    fn apply(&self, framebuffer: &mut Framebuffer) {
+        let half_width = framebuffer.width as f32 / 2.0;
+        let half_height = framebuffer.height as f32 / 2.0;
+        // Distance to a corner is ~1.41 in normalized units
+        let span = (std::f32::consts::SQRT_2 - self.radius).max(0.0001);
+
+        framebuffer.with_blend_mode(BlendMode::Multiply, |framebuffer| {
+            for y in 0..framebuffer.height as i32 {
+                for x in 0..framebuffer.width as i32 {
+                    let dx = (x as f32 + 0.5 - half_width) / half_width;
+                    let dy = (y as f32 + 0.5 - half_height) / half_height;
+                    let t = (((dx * dx + dy * dy).sqrt() - self.radius) / span).clamp(0.0, 1.0);
+                    if t > 0.0 {
+                        let factor = 1.0 - self.strength * t * t;
+                        framebuffer.point_blended(x, y, Vector3::new(factor, factor, factor), 1.0);
+                    }
+                }
+            }
+        });
+    }
+}
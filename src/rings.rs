@@ -1,101 +1,196 @@
 use raylib::prelude::*;
+use crate::tangents::compute_tangents_unindexed;
 use crate::vertex::Vertex;
 
-/// Generate a torus geometry for planet rings
-#[allow(dead_code)]
+/// Generate a torus as a triangle list (two triangles per grid cell).
+/// UVs: u runs around the major circle, v around the tube (0.5 is the inner edge, facing the center).
 pub fn generate_torus_ring(major_radius: f32, minor_radius: f32, major_segments: usize, minor_segments: usize) -> Vec<Vertex> {
-    let mut vertices = Vec::new();
-    
     let two_pi = std::f32::consts::PI * 2.0;
-    
-    for i in 0..=major_segments {
+
+    // Point of the torus at grid cell corner (i, j)
+    let torus_vertex = |i: usize, j: usize| {
         let u = (i as f32) / (major_segments as f32) * two_pi;
         let (sin_u, cos_u) = u.sin_cos();
-        
-        for j in 0..=minor_segments {
-            let v = (j as f32) / (minor_segments as f32) * two_pi;
-            let (sin_v, cos_v) = v.sin_cos();
-            
-            // Torus parametric equations
-            let x = (major_radius + minor_radius * cos_v) * cos_u;
-            let y = minor_radius * sin_v;
-            let z = (major_radius + minor_radius * cos_v) * sin_u;
-            
-            let position = Vector3::new(x, y, z);
-            
-            // Normal pointing outward from torus surface
-            let normal = Vector3::new(
-                cos_v * cos_u,
-                sin_v,
-                cos_v * sin_u,
-            );
-            
-            vertices.push(Vertex::new(
-                position,
-                normal,
-                Vector2::new(u / two_pi, v / two_pi),
-            ));
+        let v = (j as f32) / (minor_segments as f32) * two_pi;
+        let (sin_v, cos_v) = v.sin_cos();
+
+        // Torus parametric equations
+        let x = (major_radius + minor_radius * cos_v) * cos_u;
+        let y = minor_radius * sin_v;
+        let z = (major_radius + minor_radius * cos_v) * sin_u;
+
+        // Normal pointing outward from torus surface
+        let normal = Vector3::new(
+            cos_v * cos_u,
+            sin_v,
+            cos_v * sin_u,
+        );
+
+        Vertex::new(
+            Vector3::new(x, y, z),
+            normal,
+            Vector2::new(u / two_pi, v / two_pi),
+        )
+    };
+
+    let mut vertices = Vec::with_capacity(major_segments * minor_segments * 6);
+    for i in 0..major_segments {
+        for j in 0..minor_segments {
+            let a = torus_vertex(i, j);
+            let b = torus_vertex(i + 1, j);
+            let c = torus_vertex(i + 1, j + 1);
+            let d = torus_vertex(i, j + 1);
+
+            vertices.extend([a.clone(), b, c.clone(), a, c, d]);
         }
     }
-    
+
+    compute_tangents_unindexed(&mut vertices);
     vertices
 }
 
+/// Gaps of the gas giant's rings, as (start, end) ranges of the radial coordinate u
+/// (0 = inner edge, 1 = outer edge): the Cassini division
+pub const RING_GAPS: &[(f32, f32)] = &[(0.6, 0.65)];
+
 /// Generate a simple disk ring (flat)
-pub fn generate_flat_ring(inner_radius: f32, outer_radius: f32, segments: usize) -> Vec<Vertex> {
+/// UVs: u is the radial band coordinate (0 at the inner edge, 1 at the outer edge) and v the
+/// angle around the ring (0..1), so the ring shader's bands come out concentric.
+/// No geometry is emitted between the (start, end) values of u in `gaps`, leaving real holes.
+pub fn generate_flat_ring(inner_radius: f32, outer_radius: f32, segments: usize, gaps: &[(f32, f32)]) -> Vec<Vertex> {
     let mut vertices = Vec::new();
     
     let two_pi = std::f32::consts::PI * 2.0;
+    let normal = Vector3::new(0.0, 1.0, 0.0);
     
-    for i in 0..segments {
-        let angle1 = (i as f32) / (segments as f32) * two_pi;
-        let angle2 = ((i + 1) as f32) / (segments as f32) * two_pi;
-        
-        let (sin1, cos1) = angle1.sin_cos();
-        let (sin2, cos2) = angle2.sin_cos();
-        
-        // Inner point 1
-        vertices.push(Vertex::new(
-            Vector3::new(inner_radius * cos1, 0.0, inner_radius * sin1),
-            Vector3::new(0.0, 1.0, 0.0),
-            Vector2::new(0.0, 0.0),
-        ));
-        
-        // Outer point 1
-        vertices.push(Vertex::new(
-            Vector3::new(outer_radius * cos1, 0.0, outer_radius * sin1),
-            Vector3::new(0.0, 1.0, 0.0),
-            Vector2::new(1.0, 0.0),
-        ));
-        
-        // Outer point 2
-        vertices.push(Vertex::new(
-            Vector3::new(outer_radius * cos2, 0.0, outer_radius * sin2),
-            Vector3::new(0.0, 1.0, 0.0),
-            Vector2::new(1.0, 1.0),
-        ));
-        
-        // Inner point 1 (again for second triangle)
-        vertices.push(Vertex::new(
-            Vector3::new(inner_radius * cos1, 0.0, inner_radius * sin1),
-            Vector3::new(0.0, 1.0, 0.0),
-            Vector2::new(0.0, 0.0),
-        ));
-        
-        // Outer point 2
-        vertices.push(Vertex::new(
-            Vector3::new(outer_radius * cos2, 0.0, outer_radius * sin2),
-            Vector3::new(0.0, 1.0, 0.0),
-            Vector2::new(1.0, 1.0),
-        ));
-        
-        // Inner point 2
-        vertices.push(Vertex::new(
-            Vector3::new(inner_radius * cos2, 0.0, inner_radius * sin2),
-            Vector3::new(0.0, 1.0, 0.0),
-            Vector2::new(0.0, 1.0),
-        ));
+    for (u_inner, u_outer) in solid_bands(gaps) {
+        let band_inner = inner_radius + (outer_radius - inner_radius) * u_inner;
+        let band_outer = inner_radius + (outer_radius - inner_radius) * u_outer;
+
+        for i in 0..segments {
+            let v1 = (i as f32) / (segments as f32);
+            let v2 = ((i + 1) as f32) / (segments as f32);
+            
+            let (sin1, cos1) = (v1 * two_pi).sin_cos();
+            let (sin2, cos2) = (v2 * two_pi).sin_cos();
+            
+            let inner1 = Vertex::new(Vector3::new(band_inner * cos1, 0.0, band_inner * sin1), normal, Vector2::new(u_inner, v1));
+            let outer1 = Vertex::new(Vector3::new(band_outer * cos1, 0.0, band_outer * sin1), normal, Vector2::new(u_outer, v1));
+            let outer2 = Vertex::new(Vector3::new(band_outer * cos2, 0.0, band_outer * sin2), normal, Vector2::new(u_outer, v2));
+            let inner2 = Vertex::new(Vector3::new(band_inner * cos2, 0.0, band_inner * sin2), normal, Vector2::new(u_inner, v2));
+            
+            vertices.extend([inner1.clone(), outer1, outer2.clone(), inner1, outer2, inner2]);
+        }
     }
     
+    compute_tangents_unindexed(&mut vertices);
     vertices
 }
+
+/// The (start, end) ranges of u in 0..1 left after removing `gaps` (clamped to 0..1, in any order)
+fn solid_bands(gaps: &[(f32, f32)]) -> Vec<(f32, f32)> {
+    let mut gaps: Vec<(f32, f32)> = gaps
+        .iter()
+        .map(|&(start, end)| (start.min(end).clamp(0.0, 1.0), start.max(end).clamp(0.0, 1.0)))
+        .collect();
+    gaps.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut bands = Vec::new();
+    let mut start = 0.0;
+    for (gap_start, gap_end) in gaps {
+        if gap_start > start {
+            bands.push((start, gap_start));
+        }
+        start = start.max(gap_end);
+    }
+    if start < 1.0 {
+        bands.push((start, 1.0));
+    }
+    bands
+}
+
+/// Distance in u from `u` to the nearest edge of a gap (0 inside a gap; infinite without gaps)
+pub fn distance_to_gap(u: f32, gaps: &[(f32, f32)]) -> f32 {
+    gaps.iter()
+        .map(|&(start, end)| {
+            let (start, end) = (start.min(end), start.max(end));
+            (start - u).max(u - end).max(0.0)
+        })
+        .fold(f32::INFINITY, f32::min)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INNER: f32 = 1.5;
+    const OUTER: f32 = 2.5;
+
+    /// Radial position of a vertex from 0 at the inner radius to 1 at the outer one
+    fn normalized_radius(vertex: &Vertex) -> f32 {
+        let p = vertex.position;
+        ((p.x * p.x + p.z * p.z).sqrt() - INNER) / (OUTER - INNER)
+    }
+
+    #[test]
+    fn flat_ring_u_is_the_normalized_radius() {
+        for segments in [3, 16, 64] {
+            let vertices = generate_flat_ring(INNER, OUTER, segments, &[]);
+            assert_eq!(vertices.len(), segments * 6);
+            for vertex in &vertices {
+                let radius = normalized_radius(vertex);
+                assert!(radius.abs() < 1e-5 || (radius - 1.0).abs() < 1e-5, "vertex between the edges at {}", radius);
+                // Inner edge u ≈ 0, outer edge u ≈ 1, whatever the segment count
+                assert!((vertex.tex_coords.x - radius).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn flat_ring_v_follows_the_angle() {
+        let two_pi = std::f32::consts::PI * 2.0;
+        for vertex in generate_flat_ring(INNER, OUTER, 12, &[]) {
+            let angle = vertex.position.z.atan2(vertex.position.x).rem_euclid(two_pi) / two_pi;
+            // The last segment closes the ring at v = 1, the same angle as v = 0
+            let v = vertex.tex_coords.y;
+            assert!((v - angle).abs() < 1e-4 || ((v - 1.0).abs() < 1e-4 && angle < 1e-4), "v {} at angle {}", v, angle);
+        }
+    }
+
+    #[test]
+    fn no_ring_geometry_falls_inside_a_gap() {
+        let gaps = [(0.6, 0.65), (0.2, 0.1)]; // Ends in any order
+        let vertices = generate_flat_ring(INNER, OUTER, 32, &gaps);
+        assert!(!vertices.is_empty());
+        for vertex in &vertices {
+            let radius = normalized_radius(vertex);
+            for (start, end) in [(0.6, 0.65), (0.1, 0.2)] {
+                assert!(radius <= start + 1e-5 || radius >= end - 1e-5, "vertex at {} inside the gap {}-{}", radius, start, end);
+            }
+        }
+        // Every triangle lies within one band, so none spans a gap either
+        for triangle in vertices.chunks_exact(3) {
+            let radii = triangle.iter().map(normalized_radius);
+            let (low, high) = radii.fold((f32::MAX, f32::MIN), |(low, high), r| (low.min(r), high.max(r)));
+            for (start, end) in [(0.6, 0.65), (0.1, 0.2)] {
+                assert!(high <= start + 1e-5 || low >= end - 1e-5, "triangle from {} to {} crosses the gap {}-{}", low, high, start, end);
+            }
+        }
+    }
+
+    #[test]
+    fn solid_bands_skip_gaps_and_merge_overlaps() {
+        assert_eq!(solid_bands(&[]), vec![(0.0, 1.0)]);
+        assert_eq!(solid_bands(&[(0.6, 0.65)]), vec![(0.0, 0.6), (0.65, 1.0)]);
+        // Overlapping gaps, one reaching past the outer edge
+        assert_eq!(solid_bands(&[(0.3, 0.5), (0.4, 0.45), (0.9, 1.5)]), vec![(0.0, 0.3), (0.5, 0.9)]);
+    }
+
+    #[test]
+    fn distance_to_gap_is_zero_inside_and_grows_outside() {
+        assert_eq!(distance_to_gap(0.62, RING_GAPS), 0.0);
+        assert!((distance_to_gap(0.55, RING_GAPS) - 0.05).abs() < 1e-6);
+        assert!((distance_to_gap(0.7, RING_GAPS) - 0.05).abs() < 1e-6);
+        assert_eq!(distance_to_gap(0.5, &[]), f32::INFINITY);
+    }
+}
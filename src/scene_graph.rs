@@ -0,0 +1,43 @@
+use raylib::prelude::Matrix;
+
+/// Handle of a node inside a `SceneGraph`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodeId(usize);
+
+struct SceneNode {
+    local: Matrix,
+    parent: Option<NodeId>,
+}
+
+/// Minimal transform hierarchy rebuilt every frame.
+/// A node's world matrix is its local matrix followed by its parent's world matrix
+/// (`parent_world * local` in column-vector notation), so children follow their parent's
+/// position and orientation: moons follow their planet, rings follow the planet's frame.
+pub struct SceneGraph {
+    nodes: Vec<SceneNode>,
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        SceneGraph { nodes: Vec::new() }
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+    }
+
+    /// Adds a node; parents must be added before their children
+    pub fn add(&mut self, local: Matrix, parent: Option<NodeId>) -> NodeId {
+        self.nodes.push(SceneNode { local, parent });
+        NodeId(self.nodes.len() - 1)
+    }
+
+    pub fn world_matrix(&self, node: NodeId) -> Matrix {
+        let node = &self.nodes[node.0];
+        match node.parent {
+            // raylib multiplies so that the left matrix is applied first
+            Some(parent) => node.local * self.world_matrix(parent),
+            None => node.local,
+        }
+    }
+}
@@ -0,0 +1,33 @@
+/// Shortest span the timeline scrubber covers, in seconds of simulation time
+const MIN_SCRUB_RANGE: f32 = 60.0;
+
+/// Timeline scrubber: maps a position along a bar (0 = left end, 1 = right end) to the
+/// simulation time. The bar spans from 0 to the latest time reached, so scrubbing back
+/// keeps the range and the run can be scrubbed forward again.
+#[derive(Default)]
+pub struct TimeScrubber {
+    extent: f32,        // Latest time reached so far
+    pub dragging: bool, // The bar is being dragged; time follows the mouse instead of advancing
+}
+
+impl TimeScrubber {
+    /// Records the current time so the bar grows to include it
+    pub fn track(&mut self, time: f32) {
+        self.extent = self.extent.max(time);
+    }
+
+    /// Time at the right end of the bar
+    pub fn range(&self) -> f32 {
+        self.extent.max(MIN_SCRUB_RANGE)
+    }
+
+    /// Position of `time` along the bar, in [0, 1]
+    pub fn fraction(&self, time: f32) -> f32 {
+        (time / self.range()).clamp(0.0, 1.0)
+    }
+
+    /// Time at a position along the bar (clamped to the ends)
+    pub fn time_at(&self, fraction: f32) -> f32 {
+        fraction.clamp(0.0, 1.0) * self.range()
+    }
+}
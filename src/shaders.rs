@@ -2,17 +2,99 @@ use raylib::prelude::*;
 use crate::vertex::Vertex;
 use crate::fragment::Fragment;
 use crate::Uniforms;
+use crate::clip::near_plane_distance;
+use crate::color_ramp::ColorRamp;
+use crate::rings::{distance_to_gap, RING_GAPS};
+use crate::matrix::{multiply_matrix_vector4, transform_direction, viewport_transform};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{LazyLock, RwLock, RwLockReadGuard};
 
-// This function manually multiplies a 4x4 matrix with a 4D vector (in homogeneous coordinates)
-fn multiply_matrix_vector4(matrix: &Matrix, vector: &Vector4) -> Vector4 {
-    Vector4::new(
-        matrix.m0 * vector.x + matrix.m4 * vector.y + matrix.m8 * vector.z + matrix.m12 * vector.w,
-        matrix.m1 * vector.x + matrix.m5 * vector.y + matrix.m9 * vector.z + matrix.m13 * vector.w,
-        matrix.m2 * vector.x + matrix.m6 * vector.y + matrix.m10 * vector.z + matrix.m14 * vector.w,
-        matrix.m3 * vector.x + matrix.m7 * vector.y + matrix.m11 * vector.z + matrix.m15 * vector.w,
-    )
+/// Shader selector for a body; the discriminants are the ids shown in the legend and gallery
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PlanetType {
+    Sun = 0,
+    EarthLike = 1,
+    GasGiant = 2,
+    Moon = 3,
+    Ring = 4,
+    Neptune = 5,
+    Uranus = 6,
+    Venus = 7,
+    Mars = 8,
+    AccretionDisk = 9,
+}
+
+impl PlanetType {
+    pub const ALL: [PlanetType; 10] = [
+        PlanetType::Sun,
+        PlanetType::EarthLike,
+        PlanetType::GasGiant,
+        PlanetType::Moon,
+        PlanetType::Ring,
+        PlanetType::Neptune,
+        PlanetType::Uranus,
+        PlanetType::Venus,
+        PlanetType::Mars,
+        PlanetType::AccretionDisk,
+    ];
+
+    /// Legend id of the shader (its discriminant)
+    pub fn id(self) -> u32 {
+        self as u32
+    }
+
+    /// Display name of the shader
+    pub fn name(self) -> &'static str {
+        match self {
+            PlanetType::Sun => "Sol",
+            PlanetType::EarthLike => "Tierra",
+            PlanetType::GasGiant => "Gigante Gaseoso",
+            PlanetType::Moon => "Luna",
+            PlanetType::Ring => "Anillos",
+            PlanetType::Neptune => "Neptuno",
+            PlanetType::Uranus => "Urano",
+            PlanetType::Venus => "Venus",
+            PlanetType::Mars => "Marte",
+            PlanetType::AccretionDisk => "Disco de acreción",
+        }
+    }
+}
+
+/// A legend id that no shader has
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnknownPlanetType(pub u32);
+
+impl fmt::Display for UnknownPlanetType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no shader has id {} (ids go from 0 to {})", self.0, PlanetType::ALL.len() - 1)
+    }
 }
 
+impl std::error::Error for UnknownPlanetType {}
+
+impl TryFrom<u32> for PlanetType {
+    type Error = UnknownPlanetType;
+
+    /// Shader with the given legend id
+    fn try_from(id: u32) -> Result<Self, Self::Error> {
+        PlanetType::ALL.iter().copied().find(|planet_type| planet_type.id() == id).ok_or(UnknownPlanetType(id))
+    }
+}
+
+// Shared palettes, built once on first use
+static OCEAN_RAMP: LazyLock<ColorRamp> = LazyLock::new(ColorRamp::ocean);
+static ICE_RAMP: LazyLock<ColorRamp> = LazyLock::new(ColorRamp::ice);
+static LAVA_RAMP: LazyLock<ColorRamp> = LazyLock::new(ColorRamp::lava);
+static MARS_DUNE_RAMP: LazyLock<ColorRamp> = LazyLock::new(|| {
+    ColorRamp::new(vec![
+        (0.0, Vector3::new(0.35, 0.12, 0.06)), // Dark basalt
+        (0.4, Vector3::new(0.65, 0.25, 0.1)),  // Iron-oxide plains
+        (0.75, Vector3::new(0.8, 0.4, 0.18)),  // Dune crests
+        (1.0, Vector3::new(0.9, 0.6, 0.35)),   // Bright dust
+    ])
+});
+
 pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
   // Convert vertex position to homogeneous coordinates (Vec4) by adding a w-component of 1.0
   let position_vec4 = Vector4::new(
@@ -22,42 +104,120 @@ pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
     1.0
   );
 
-  // Apply the transformation by multiplying the model matrix with the vector
-  let transformed_vec4 = multiply_matrix_vector4(&uniforms.model_matrix, &position_vec4);
+  // Model, then view, then projection (raylib multiplies so that the left matrix is applied first)
+  let model_view = uniforms.model_matrix * uniforms.view_matrix;
+  let transformed_vec4 = multiply_matrix_vector4(&(model_view * uniforms.projection_matrix), &position_vec4);
+
+  // A vertex nearer than the near plane (z < -w) is flagged so the triangles using it are clipped
+  // (see clip.rs). That includes everything at or behind the camera (w <= 0), which can't be
+  // divided: the signs flip and its triangles smear across the screen.
+  let behind_camera = near_plane_distance(transformed_vec4) < 0.0;
 
-  // Perform perspective division to convert from homogeneous coordinates back to 3D Cartesian coordinates
-  let transformed_position_3d = if transformed_vec4.w != 0.0 {
-      Vector3::new(
+  // Perform perspective division to get NDC, then map it onto the framebuffer's pixels
+  let transformed_position = if !behind_camera {
+      let ndc = Vector3::new(
           transformed_vec4.x / transformed_vec4.w,
           transformed_vec4.y / transformed_vec4.w,
           transformed_vec4.z / transformed_vec4.w,
-      )
+      );
+      let mut screen = viewport_transform(ndc, uniforms.viewport.0, uniforms.viewport.1);
+      // Logarithmic depth replaces the projection's z, which bunches its precision at the near plane
+      if let Some(far) = uniforms.log_depth_far {
+          screen.z = log_depth(transformed_vec4.w, far);
+      }
+      screen
   } else {
-      // Avoid division by zero, though w should usually be 1 for model transformations
+      // Left undivided; never rasterized
       Vector3::new(transformed_vec4.x, transformed_vec4.y, transformed_vec4.z)
   };
 
-  // Simple isometric projection with subtle Z effect
-  // The Z position slightly affects Y and scale but doesn't distort too much
-  let z_factor = transformed_position_3d.z * 0.02;
-  
-  let transformed_position = Vector3::new(
-      transformed_position_3d.x,
-      transformed_position_3d.y + z_factor * 5.0, // Slight vertical shift for depth
-      transformed_position_3d.z,
-  );
-
   // Create a new Vertex with the transformed position
   Vertex {
     position: vertex.position,
     normal: vertex.normal,
+    tangent: vertex.tangent,
     tex_coords: vertex.tex_coords,
     color: vertex.color,
     transformed_position,
-    transformed_normal: vertex.normal, // Note: Correct normal transformation is more complex
+    // Normals are lit in world space, where the light and view directions are given
+    transformed_normal: normalize(transform_direction(&uniforms.normal_transform, vertex.normal)),
+    clip_position: transformed_vec4,
+    behind_camera,
+    inv_w: if behind_camera { 0.0 } else { 1.0 / transformed_vec4.w },
   }
 }
 
+/// Logarithmic depth of a point `w` units in front of the camera (its clip-space w), in the range
+/// of NDC depth: the eye maps to -1 and `far` to 1. Equal ratios of distance get equal steps, so
+/// far surfaces keep the precision the perspective divide would spend next to the near plane.
+pub fn log_depth(w: f32, far: f32) -> f32 {
+    2.0 * (1.0 + w.max(0.0)).log2() / (1.0 + far).log2() - 1.0
+}
+
+// ==================== DYNAMIC LIGHTS ====================
+
+/// Point light in world space, like the surfaces and normals it lights
+#[derive(Clone, Copy, Debug)]
+pub struct PointLight {
+    pub position: Vector3,
+    pub color: Vector3,
+    pub intensity: f32,
+    pub radius: f32, // Distance at which the contribution fades to zero
+}
+
+/// Adds the diffuse contribution of nearby point lights to an already shaded color, for a surface
+/// at world `position` facing `normal` (unit length). Each light falls off with the squared
+/// distance, windowed to reach zero at its radius, and only lights the side facing it.
+pub fn apply_point_lights(color: Vector3, position: Vector3, normal: Vector3, lights: &[PointLight]) -> Vector3 {
+    let mut result = color;
+    for light in lights {
+        let to_light = light.position - position;
+        let distance = to_light.length();
+        if distance >= light.radius || distance < 1e-6 {
+            continue;
+        }
+        let window = 1.0 - (distance / light.radius).powi(2);
+        let attenuation = window * window / (1.0 + (distance / light.radius * 4.0).powi(2));
+        let n_dot_l = normal.dot(to_light / distance).max(0.0);
+        result += light.color * (light.intensity * attenuation * n_dot_l);
+    }
+    result
+}
+
+// Lightning flashes: every slot one random spot on the gas giant is checked, and if the
+// shader's lightning is near its peak there, a light flashes for about two frames.
+const LIGHTNING_SLOT: f32 = 0.4;
+const LIGHTNING_FLASH_DURATION: f32 = 0.035;
+const LIGHTNING_THRESHOLD: f32 = 0.08; // Peak lightning intensity is 0.1
+const LIGHTNING_LIGHT_INTENSITY: f32 = 0.35;
+const LIGHTNING_LIGHT_REACH: f32 = 2.5; // In planet radii
+const LIGHTNING_ALTITUDE: f32 = 1.05; // Distance of the flash from the center, in planet radii
+
+/// Flash light produced by the gas giant's brightest lightning, if one is active at `time`, for a
+/// giant of world-space `radius` centered at `center`. The strike sits just above its clouds.
+pub fn gas_giant_lightning_flash(center: Vector3, radius: f32, time: f32) -> Option<PointLight> {
+    let slot = (time / LIGHTNING_SLOT).floor();
+    let slot_start = slot * LIGHTNING_SLOT;
+    if time - slot_start > LIGHTNING_FLASH_DURATION {
+        return None;
+    }
+
+    // Pseudo-random strike location for this slot
+    let uv = Vector2::new(hash(slot * 12.9898 + 1.0), hash(slot * 78.233 + 2.0));
+    if gas_giant_lightning(uv, slot_start) < LIGHTNING_THRESHOLD {
+        return None;
+    }
+
+    let direction = uv_to_sphere(uv);
+
+    Some(PointLight {
+        position: center + direction * (radius * LIGHTNING_ALTITUDE),
+        color: Vector3::new(1.0, 1.0, 0.6),
+        intensity: LIGHTNING_LIGHT_INTENSITY,
+        radius: radius * LIGHTNING_LIGHT_REACH,
+    })
+}
+
 // ==================== FRAGMENT SHADERS ====================
 
 /// Simple hash function for noise generation
@@ -121,7 +281,8 @@ fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
 }
 
 /// Calculate lighting based on normal and light direction
-fn calculate_lighting(normal: Vector3, light_dir: Vector3, view_dir: Vector3) -> f32 {
+/// Unweighted (diffuse, specular) terms; the body's `SurfaceMaterial` scales and tints them
+fn calculate_lighting(normal: Vector3, light_dir: Vector3, view_dir: Vector3) -> (f32, f32) {
     // Normalize vectors
     let n = normalize(normal);
     let l = normalize(light_dir);
@@ -134,8 +295,20 @@ fn calculate_lighting(normal: Vector3, light_dir: Vector3, view_dir: Vector3) ->
     let r = reflect(l, n);
     let specular = (r.x * v.x + r.y * v.y + r.z * v.z).max(0.0).powf(32.0);
     
-    // Ambient + Diffuse + Specular
-    0.2 + diffuse * 0.7 + specular * 0.3
+    // The ambient floor is added by the caller
+    (diffuse, specular)
+}
+
+/// Bends `normal` by a tangent-space normal map sample (RGB in 0..1, as stored in the image).
+/// `strength` scales the tangential part: 0 keeps the surface flat, 1 is the map as authored.
+pub fn perturb_normal(normal: Vector3, tangent: Vector3, sample: Vector3, strength: f32) -> Vector3 {
+    let n = normalize(normal);
+    // Re-orthogonalize: interpolation across the triangle skews the basis slightly
+    let t = normalize(tangent - n * (n.x * tangent.x + n.y * tangent.y + n.z * tangent.z));
+    let b = Vector3::new(n.y * t.z - n.z * t.y, n.z * t.x - n.x * t.z, n.x * t.y - n.y * t.x);
+
+    let mapped = sample * 2.0 - Vector3::one();
+    normalize(t * (mapped.x * strength) + b * (mapped.y * strength) + n * mapped.z)
 }
 
 /// Normalize a vector
@@ -148,6 +321,36 @@ fn normalize(v: Vector3) -> Vector3 {
     }
 }
 
+/// Spherical (u, v) of a position around the origin: u from the longitude (atan2 of x/z),
+/// v from the latitude (asin of y). `None` at the degenerate origin.
+fn sphere_uv(pos: Vector3) -> Option<Vector2> {
+    let len = (pos.x * pos.x + pos.y * pos.y + pos.z * pos.z).sqrt();
+    if len < 0.001 {
+        return None;
+    }
+
+    let norm = Vector3::new(pos.x / len, pos.y / len, pos.z / len);
+    let u = (norm.x.atan2(norm.z) / std::f32::consts::PI + 1.0) * 0.5;
+    let v = (norm.y).asin() / std::f32::consts::PI + 0.5;
+
+    Some(Vector2::new(u, v))
+}
+
+/// Distance on the sphere between `uv` and a feature `center`, in v units (1 = pole to pole).
+/// u spans 2π around but v only π, so a u step is twice as long as a v step at the equator
+/// and shrinks by cos(latitude) towards the poles; u wraps across the seam.
+/// Using this instead of the raw UV distance keeps spots and storms circular on the surface.
+fn sphere_uv_distance(uv: Vector2, center: Vector2) -> f32 {
+    let mut du = (uv.x - center.x).abs() % 1.0;
+    if du > 0.5 {
+        du = 1.0 - du;
+    }
+    let latitude = (center.y - 0.5) * std::f32::consts::PI;
+    let dx = du * 2.0 * latitude.cos();
+    let dy = uv.y - center.y;
+    (dx * dx + dy * dy).sqrt()
+}
+
 /// Reflect vector v around normal n
 fn reflect(v: Vector3, n: Vector3) -> Vector3 {
     let dot2 = 2.0 * (v.x * n.x + v.y * n.y + v.z * n.z);
@@ -161,17 +364,10 @@ fn reflect(v: Vector3, n: Vector3) -> Vector3 {
 /// SUN SHADER - Dynamic solar surface with 5 layers
 fn sun_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3 {
     // UV coordinates from position
-    let pos = vertex.transformed_position;
-    let len = (pos.x * pos.x + pos.y * pos.y + pos.z * pos.z).sqrt();
-    if len < 0.001 {
-        return Vector3::new(0.0, 0.0, 0.0);
-    }
-    
-    let norm = Vector3::new(pos.x / len, pos.y / len, pos.z / len);
-    let u = (norm.x.atan2(norm.z) / std::f32::consts::PI + 1.0) * 0.5;
-    let v = (norm.y).asin() / std::f32::consts::PI + 0.5;
-    
-    let uv = Vector2::new(u, v);
+    let uv = match sphere_uv(vertex.position) {
+        Some(uv) => uv,
+        None => return Vector3::new(0.0, 0.0, 0.0),
+    };
     
     // Get material color from MTL file if available (now includes texture!)
     let material_color = vertex.color;
@@ -210,40 +406,38 @@ fn sun_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3 {
     result
 }
 
+/// Continents of the earth shader: 0 over open ocean, 1 over land. Shared with the lighting,
+/// which only puts the specular glint on the water
+fn earth_land_mask(uv: Vector2, time: f32) -> f32 {
+    let land_noise1 = fbm(uv * 4.0, 5);
+    let land_noise2 = fbm(uv * 8.0 - time * 0.01, 4);
+    smoothstep(0.35, 0.65, land_noise1 * 0.7 + land_noise2 * 0.3)
+}
+
 /// EARTH-LIKE PLANET - Hyper-realistic with 7 detailed layers
-fn earth_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3 {
+fn earth_shader(_fragment: &Fragment, vertex: &Vertex, time: f32, animation: AnimationSpeeds) -> Vector3 {
     // UV coordinates from position
-    let pos = vertex.transformed_position;
-    let len = (pos.x * pos.x + pos.y * pos.y + pos.z * pos.z).sqrt();
-    if len < 0.001 {
-        return Vector3::new(0.0, 0.0, 0.0);
-    }
-    
-    let norm = Vector3::new(pos.x / len, pos.y / len, pos.z / len);
-    let u = (norm.x.atan2(norm.z) / std::f32::consts::PI + 1.0) * 0.5;
-    let v = (norm.y).asin() / std::f32::consts::PI + 0.5;
-    
-    let uv = Vector2::new(u, v);
+    let uv = match sphere_uv(vertex.position) {
+        Some(uv) => uv,
+        None => return Vector3::new(0.0, 0.0, 0.0),
+    };
+    let v = uv.y;
+    // Clouds drift and storms swirl at the body's own rates (1.0 = default speed)
+    let cloud_time = time * animation.cloud;
+    let storm_time = time * animation.storm;
     
     // Get material color from MTL file if available (now includes texture!)
     let material_color = vertex.color;
     
     // Layer 1: Ocean base with depth variation
     let ocean_depth = fbm(uv * 3.0, 2);
-    let ocean_base = mix_color(
-        Vector3::new(0.0, 0.2, 0.5),  // Deep ocean
-        Vector3::new(0.0, 0.4, 0.8),  // Shallow ocean
-        ocean_depth
-    );
+    let ocean_base = OCEAN_RAMP.sample(ocean_depth * 0.8); // Deep to shallow, coasts left out
     
     // BLEND WITH TEXTURE: Use texture as base, only add procedural detail
     let ocean_with_texture = mix_color(ocean_base, material_color, 0.6); // 60% texture visible
     
     // Layer 2: Landmasses (MUCH more detailed continents)
-    let land_noise1 = fbm(uv * 4.0, 5);
-    let land_noise2 = fbm(uv * 8.0 - time * 0.01, 4);
-    let land_combined = land_noise1 * 0.7 + land_noise2 * 0.3;
-    let land_mask = smoothstep(0.35, 0.65, land_combined);
+    let land_mask = earth_land_mask(uv, time);
     
     // Multi-texture landmass with forests, deserts, and grasslands
     let land_texture = fbm(uv * 12.0 + time * 0.001, 3);
@@ -276,30 +470,24 @@ fn earth_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3 {
     let with_trenches = mix_color(with_mountains, trench_color, trench_mask * 0.5);
     
     // Layer 5: Clouds (animated swirling patterns - MORE detailed)
-    let cloud_noise1 = fbm(uv * 5.0 + time * 0.08, 4);
-    let cloud_noise2 = fbm(uv * 7.0 - time * 0.05, 3);
-    let cloud_noise3 = fbm(uv * 3.0 + time * 0.03, 2);
+    let cloud_noise1 = fbm(uv * 5.0 + cloud_time * 0.08, 4);
+    let cloud_noise2 = fbm(uv * 7.0 - cloud_time * 0.05, 3);
+    let cloud_noise3 = fbm(uv * 3.0 + cloud_time * 0.03, 2);
     let clouds_combined = (cloud_noise1 + cloud_noise2 + cloud_noise3) / 3.0;
     let clouds = smoothstep(0.25, 0.85, clouds_combined);
     let cloud_color = Vector3::new(0.95, 0.98, 1.0);
     let with_clouds = mix_color(with_trenches, cloud_color, clouds * 0.65);
     
     // Layer 6: Storm systems (darker cloud formations)
-    let storm_x = (u - 0.4) * (u - 0.4);
-    let storm_y = (v - 0.3) * (v - 0.3);
-    let storm_dist = (storm_x + storm_y).sqrt();
-    let storm_interior = fbm(uv * 25.0 + time * 0.1, 3);
+    let storm_dist = sphere_uv_distance(uv, Vector2::new(0.4, 0.3));
+    let storm_interior = fbm(uv * 25.0 + storm_time * 0.1, 3);
     let storm_color = Vector3::new(0.4, 0.4, 0.5);
     let with_storms = mix_color(with_clouds, storm_color, smoothstep(0.25, 0.05, storm_dist) * storm_interior * 0.6);
     
     // Layer 7: Polar ice caps and atmospheric effects
     let ice_factor = (1.0 - (v - 0.5).abs() * 2.5).clamp(0.0, 1.0);
     let ice_sparkle = fbm(uv * 40.0 - time * 0.05, 2);
-    let ice_color = mix_color(
-        Vector3::new(0.9, 0.95, 1.0),    // Pure ice
-        Vector3::new(1.0, 1.0, 0.95),    // Ice sparkle
-        ice_sparkle * 0.5
-    );
+    let ice_color = ICE_RAMP.sample(0.5 + ice_sparkle * 0.25); // Pure ice to sparkle
     let with_ice = mix_color(with_storms, ice_color, ice_factor * 0.5);
     
     // Atmospheric rim glow (blue edge effect)
@@ -312,19 +500,16 @@ fn earth_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3 {
 }
 
 /// GAS GIANT - Complex with 5 layers (bands, storms, great red spot, lightning, atmospheric depth)
-fn gas_giant_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3 {
+fn gas_giant_shader(_fragment: &Fragment, vertex: &Vertex, time: f32, animation: AnimationSpeeds) -> Vector3 {
     // UV coordinates from position
-    let pos = vertex.transformed_position;
-    let len = (pos.x * pos.x + pos.y * pos.y + pos.z * pos.z).sqrt();
-    if len < 0.001 {
-        return Vector3::new(0.0, 0.0, 0.0);
-    }
-    
-    let norm = Vector3::new(pos.x / len, pos.y / len, pos.z / len);
-    let u = (norm.x.atan2(norm.z) / std::f32::consts::PI + 1.0) * 0.5;
-    let v = (norm.y).asin() / std::f32::consts::PI + 0.5;
-    
-    let uv = Vector2::new(u, v);
+    let uv = match sphere_uv(vertex.position) {
+        Some(uv) => uv,
+        None => return Vector3::new(0.0, 0.0, 0.0),
+    };
+    let (u, v) = (uv.x, uv.y);
+    // Clouds drift and storms swirl at the body's own rates (1.0 = default speed)
+    let cloud_time = time * animation.cloud;
+    let storm_time = time * animation.storm;
     
     // Get material color from MTL file if available (now includes texture!)
     let material_color = vertex.color;
@@ -340,14 +525,14 @@ fn gas_giant_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3
     let base_with_material = mix_color(base_color, material_color, 0.6);  // 60% texture visible
     
     // Layer 2: Atmospheric bands (horizontal stripes)
-    let bands = ((v * 20.0 + time * 0.1).sin() * 0.5 + 0.5).max(0.0).min(1.0);
+    let bands = ((v * 20.0 + cloud_time * 0.1).sin() * 0.5 + 0.5).max(0.0).min(1.0);
     let band_darkness = smoothstep(0.3, 0.6, bands);
     let band_color = Vector3::new(0.6, 0.3, 0.1);
     let with_bands = mix_color(base_with_material, band_color, band_darkness * 0.3);  // Reduced from 0.5
     
     // Layer 3: Turbulent storms and wind patterns
-    let storm_noise1 = fbm(uv * 8.0 + time * 0.08, 4);
-    let storm_noise2 = fbm(uv * 5.0 - time * 0.12, 3);
+    let storm_noise1 = fbm(uv * 8.0 + storm_time * 0.08, 4);
+    let storm_noise2 = fbm(uv * 5.0 - storm_time * 0.12, 3);
     let storms = (storm_noise1 + storm_noise2) * 0.5;
     let storm_mask = smoothstep(0.2, 0.8, storms);
     let storm_color = mix_color(
@@ -358,13 +543,9 @@ fn gas_giant_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3
     let with_storms = mix_color(with_bands, storm_color, storm_mask * 0.6);
     
     // Layer 4: Great Red Spot (massive storm system)
-    let spot_center_x = 0.6;
-    let spot_center_y = 0.35;
-    let spot_x = u - spot_center_x;
-    let spot_y = v - spot_center_y;
-    let spot_dist = (spot_x * spot_x + spot_y * spot_y).sqrt();
+    let spot_dist = sphere_uv_distance(uv, Vector2::new(0.6, 0.35));
     
-    let spot_swirl = fbm(Vector2::new(u * 10.0 + spot_dist * 20.0 - time * 0.1, v * 5.0), 3);
+    let spot_swirl = fbm(Vector2::new(u * 10.0 + spot_dist * 20.0 - storm_time * 0.1, v * 5.0), 3);
     let red_spot_color = mix_color(
         Vector3::new(1.0, 0.3, 0.0),   // Bright red
         Vector3::new(0.8, 0.1, 0.0),   // Deep red
@@ -374,29 +555,28 @@ fn gas_giant_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3
     let with_spot = mix_color(with_storms, red_spot_color, spot_effect * 0.9);
     
     // Layer 5: Lightning and atmospheric disturbances
-    let lightning_x = (u * 50.0 + time * 0.3).sin() * 0.1;
-    let lightning_y = (v * 40.0 - time * 0.25).sin() * 0.1;
-    let lightning_intensity = ((lightning_x + lightning_y).abs() - 0.1).clamp(0.0, 0.2);
+    let lightning_intensity = gas_giant_lightning(uv, time);
     let lightning_color = Vector3::new(1.0, 1.0, 0.3);
     let result = mix_color(with_spot, lightning_color, lightning_intensity * 0.3);
     
     result
 }
 
+/// Lightning strength of the gas giant at a UV coordinate (0.0 - 0.1)
+fn gas_giant_lightning(uv: Vector2, time: f32) -> f32 {
+    let lightning_x = (uv.x * 50.0 + time * 0.3).sin() * 0.1;
+    let lightning_y = (uv.y * 40.0 - time * 0.25).sin() * 0.1;
+    ((lightning_x + lightning_y).abs() - 0.1).clamp(0.0, 0.2)
+}
+
 /// MOON SHADER - Gray/Rocky surface (for Earth's Moon, etc)
 /// MOON SHADER - Highly detailed lunar surface with craters and rocks (6 layers)
 fn moon_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3 {
-    let pos = vertex.transformed_position;
-    let len = (pos.x * pos.x + pos.y * pos.y + pos.z * pos.z).sqrt();
-    if len < 0.001 {
-        return Vector3::new(0.0, 0.0, 0.0);
-    }
-    
-    let norm = Vector3::new(pos.x / len, pos.y / len, pos.z / len);
-    let u = (norm.x.atan2(norm.z) / std::f32::consts::PI + 1.0) * 0.5;
-    let v = (norm.y).asin() / std::f32::consts::PI + 0.5;
-    
-    let uv = Vector2::new(u, v);
+    // UV coordinates from position
+    let uv = match sphere_uv(vertex.position) {
+        Some(uv) => uv,
+        None => return Vector3::new(0.0, 0.0, 0.0),
+    };
     
     // Get material color from MTL file if available (now includes texture!)
     let material_color = vertex.color;
@@ -460,14 +640,13 @@ fn moon_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3 {
     result
 }
 
+/// Width (in the ring's radial u) of the dark fringe along the ring gaps
+const RING_GAP_SHADE_WIDTH: f32 = 0.04;
+
 /// RING SHADER - Saturn-like rings with bands
 fn ring_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3 {
-    let u = vertex.tex_coords.x;
-    let v = vertex.tex_coords.y;
-    
-    // Distance from center (0 = inner, 1 = outer)
-    let r = (u - 0.5) * (u - 0.5) + (v - 0.5) * (v - 0.5);
-    let r = r.sqrt() * 2.0;
+    // Radial band coordinate from the ring's UVs (0 = inner edge, 1 = outer edge)
+    let r = vertex.tex_coords.x;
     
     // Layer 1: Base ring color (pale gold)
     let base = Vector3::new(0.9, 0.85, 0.6);
@@ -478,30 +657,28 @@ fn ring_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3 {
     let with_bands = mix_color(base, band_color, bands * 0.5);
     
     // Layer 3: Particle shadows
-    let particles = fbm(Vector2::new(u * 10.0, r * 20.0 + time * 0.5), 3);
+    let particles = fbm(Vector2::new(r * 10.0, r * 20.0 + time * 0.5), 3);
     let shadow = mix_color(with_bands, Vector3::new(0.5, 0.4, 0.1), particles * 0.4);
     
-    // Layer 4: Edge darker (depth effect)
-    let edge_darkness = smoothstep(0.0, 0.2, r) * smoothstep(1.5, 1.0, r);
-    let result = mix_color(shadow, Vector3::new(0.2, 0.15, 0.05), (1.0 - edge_darkness) * 0.6);
+    // Layer 4: Edge darker (depth effect), also along the gaps
+    let edge_darkness = smoothstep(0.0, 0.15, r) * smoothstep(1.0, 0.85, r);
+    let gap_darkness = smoothstep(0.0, RING_GAP_SHADE_WIDTH, distance_to_gap(r, RING_GAPS));
+    let result = mix_color(shadow, Vector3::new(0.2, 0.15, 0.05), (1.0 - edge_darkness * gap_darkness) * 0.6);
     
     result
 }
 
 /// NEPTUNE - Deep blue with dynamic storms and white clouds
-fn neptune_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3 {
+fn neptune_shader(_fragment: &Fragment, vertex: &Vertex, time: f32, animation: AnimationSpeeds) -> Vector3 {
     // UV coordinates from position
-    let pos = vertex.transformed_position;
-    let len = (pos.x * pos.x + pos.y * pos.y + pos.z * pos.z).sqrt();
-    if len < 0.001 {
-        return Vector3::new(0.0, 0.0, 0.0);
-    }
-    
-    let norm = Vector3::new(pos.x / len, pos.y / len, pos.z / len);
-    let u = (norm.x.atan2(norm.z) / std::f32::consts::PI + 1.0) * 0.5;
-    let v = (norm.y).asin() / std::f32::consts::PI + 0.5;
-    
-    let uv = Vector2::new(u, v);
+    let uv = match sphere_uv(vertex.position) {
+        Some(uv) => uv,
+        None => return Vector3::new(0.0, 0.0, 0.0),
+    };
+    let (u, v) = (uv.x, uv.y);
+    // Clouds drift and storms swirl at the body's own rates (1.0 = default speed)
+    let cloud_time = time * animation.cloud;
+    let storm_time = time * animation.storm;
     
     // Get material color from MTL file if available
     let material_color = vertex.color;
@@ -517,19 +694,15 @@ fn neptune_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3 {
     let base_with_material = mix_color(base_color, material_color, 0.25);  // Increased from 0.15
     
     // Layer 2: Methane cloud bands
-    let cloud_bands = ((v * 15.0 - time * 0.08).sin() * 0.5 + 0.5).max(0.0).min(1.0);
+    let cloud_bands = ((v * 15.0 - cloud_time * 0.08).sin() * 0.5 + 0.5).max(0.0).min(1.0);
     let band_noise = fbm(uv * 4.0, 2);
     let cloud_mask = smoothstep(0.3, 0.7, cloud_bands + band_noise * 0.3);
     let with_clouds = mix_color(base_with_material, Vector3::new(0.9, 0.95, 1.0), cloud_mask * 0.4);
     
     // Layer 3: Great Dark Spot (storm system similar to Jupiter)
-    let spot_center_x = 0.5;
-    let spot_center_y = 0.5;
-    let spot_x = (u - spot_center_x) * (u - spot_center_x);
-    let spot_y = (v - spot_center_y - 0.1) * (v - spot_center_y - 0.1);
-    let spot_dist = (spot_x + spot_y).sqrt();
+    let spot_dist = sphere_uv_distance(uv, Vector2::new(0.5, 0.6));
     
-    let spot_interior = fbm(uv * 12.0 + time * 0.15, 3);
+    let spot_interior = fbm(uv * 12.0 + storm_time * 0.15, 3);
     let dark_spot = mix_color(
         Vector3::new(0.0, 0.1, 0.3),  // Dark blue center
         Vector3::new(0.1, 0.2, 0.5),  // Lighter blue edges
@@ -539,13 +712,13 @@ fn neptune_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3 {
     let with_spot = mix_color(with_clouds, dark_spot, spot_effect * 0.8);
     
     // Layer 4: High-altitude white streaks (fast winds)
-    let wind_streak = (u * 30.0 - time * 0.4).sin() * 0.5 + 0.5;
+    let wind_streak = (u * 30.0 - cloud_time * 0.4).sin() * 0.5 + 0.5;
     let streak_mask = smoothstep(0.3, 0.55, v) * smoothstep(0.65, 0.55, v);
     let white_streaks = Vector3::new(1.0, 1.0, 1.0);
     let with_streaks = mix_color(with_spot, white_streaks, (wind_streak.abs() - 0.3) * streak_mask * 0.3);
     
     // Layer 5: Atmospheric turbulence and depth
-    let turbulence = fbm(uv * 7.0 - time * 0.12, 4);
+    let turbulence = fbm(uv * 7.0 - storm_time * 0.12, 4);
     let depth_color = Vector3::new(0.0, 0.1, 0.4);
     let result = mix_color(with_streaks, depth_color, turbulence * 0.15);
     
@@ -553,19 +726,15 @@ fn neptune_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3 {
 }
 
 /// URANUS - Cyan ice giant with tilted appearance and icy rings
-fn uranus_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3 {
+fn uranus_shader(_fragment: &Fragment, vertex: &Vertex, time: f32, animation: AnimationSpeeds) -> Vector3 {
     // UV coordinates from position
-    let pos = vertex.transformed_position;
-    let len = (pos.x * pos.x + pos.y * pos.y + pos.z * pos.z).sqrt();
-    if len < 0.001 {
-        return Vector3::new(0.0, 0.0, 0.0);
-    }
-    
-    let norm = Vector3::new(pos.x / len, pos.y / len, pos.z / len);
-    let u = (norm.x.atan2(norm.z) / std::f32::consts::PI + 1.0) * 0.5;
-    let v = (norm.y).asin() / std::f32::consts::PI + 0.5;
-    
-    let uv = Vector2::new(u, v);
+    let uv = match sphere_uv(vertex.position) {
+        Some(uv) => uv,
+        None => return Vector3::new(0.0, 0.0, 0.0),
+    };
+    let v = uv.y;
+    // Storms swirl at the body's own rate (1.0 = default speed)
+    let storm_time = time * animation.storm;
     
     // Get material color from MTL file if available
     let material_color = vertex.color;
@@ -595,13 +764,10 @@ fn uranus_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3 {
     let with_bands = mix_color(with_frost, band_color, polar_bands * 0.3);
     
     // Layer 4: Tilted storm spot (Uranus rotates on its side)
-    let tilted_u = u - 0.3 * (time * 0.1).sin();
-    let tilted_v = v - 0.5 + 0.2 * (time * 0.08).cos();
-    let storm_x = (tilted_u - 0.5) * (tilted_u - 0.5);
-    let storm_y = (tilted_v - 0.3) * (tilted_v - 0.3);
-    let storm_dist = (storm_x + storm_y).sqrt();
+    let storm_center = Vector2::new(0.5 + 0.3 * (storm_time * 0.1).sin(), 0.8 - 0.2 * (storm_time * 0.08).cos());
+    let storm_dist = sphere_uv_distance(uv, storm_center);
     
-    let storm_interior = fbm(uv * 14.0 + time * 0.2, 3);
+    let storm_interior = fbm(uv * 14.0 + storm_time * 0.2, 3);
     let storm_color = mix_color(
         Vector3::new(0.1, 0.4, 0.6),
         Vector3::new(0.5, 0.9, 1.0),
@@ -620,19 +786,15 @@ fn uranus_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3 {
 }
 
 /// VENUS - Hellish planet with thick atmosphere and volcanic surface (ENHANCED - 7 layers)
-fn venus_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3 {
+fn venus_shader(_fragment: &Fragment, vertex: &Vertex, time: f32, animation: AnimationSpeeds) -> Vector3 {
     // UV coordinates from position
-    let pos = vertex.transformed_position;
-    let len = (pos.x * pos.x + pos.y * pos.y + pos.z * pos.z).sqrt();
-    if len < 0.001 {
-        return Vector3::new(0.0, 0.0, 0.0);
-    }
-    
-    let norm = Vector3::new(pos.x / len, pos.y / len, pos.z / len);
-    let u = (norm.x.atan2(norm.z) / std::f32::consts::PI + 1.0) * 0.5;
-    let v = (norm.y).asin() / std::f32::consts::PI + 0.5;
-    
-    let uv = Vector2::new(u, v);
+    let uv = match sphere_uv(vertex.position) {
+        Some(uv) => uv,
+        None => return Vector3::new(0.0, 0.0, 0.0),
+    };
+    let (u, v) = (uv.x, uv.y);
+    // Clouds drift at the body's own rate (1.0 = default speed)
+    let cloud_time = time * animation.cloud;
     
     // Get material color from MTL file if available
     let material_color = vertex.color;
@@ -649,9 +811,9 @@ fn venus_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3 {
     let base_with_material = mix_color(base_color, material_color, 0.25);  // Increased from 0.15
     
     // Layer 2: Thick toxic cloud swirls (MUCH more detailed)
-    let cloud_swirl1 = fbm(uv * 5.0 + time * 0.2, 4);
-    let cloud_swirl2 = fbm(uv * 8.0 - time * 0.15, 4);
-    let cloud_swirl3 = fbm(uv * 3.0 + time * 0.08, 3);
+    let cloud_swirl1 = fbm(uv * 5.0 + cloud_time * 0.2, 4);
+    let cloud_swirl2 = fbm(uv * 8.0 - cloud_time * 0.15, 4);
+    let cloud_swirl3 = fbm(uv * 3.0 + cloud_time * 0.08, 3);
     let clouds_combined = (cloud_swirl1 + cloud_swirl2 + cloud_swirl3) / 3.0;
     let cloud_color = mix_color(
         Vector3::new(1.0, 0.9, 0.3),   // Light yellow clouds
@@ -681,26 +843,22 @@ fn venus_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3 {
     let volcanic_mask2 = ((volcano2 - 0.28) * 3.0).clamp(0.0, 1.0);
     let volcanic_mask3 = ((volcano3 - 0.35) * 3.0).clamp(0.0, 1.0);
     
-    let hot_spot_color1 = mix_color(
-        Vector3::new(1.0, 0.2, 0.0),   // Bright red-hot
-        Vector3::new(1.0, 0.6, 0.0),   // Orange hot
-        volcano1
-    );
+    let hot_spot_color1 = LAVA_RAMP.sample(0.3 + volcano1 * 0.7); // Red-hot to orange hot
     
     let with_volcanoes = mix_color(with_surface, hot_spot_color1, 
         (volcanic_mask1 * 0.6 + volcanic_mask2 * 0.3 + volcanic_mask3 * 0.2) * 0.75);
     
     // Layer 5: Atmospheric banding (super-rotation patterns)
-    let super_rotate = ((v * 25.0 + u * 5.0 - time * 0.25).sin() * 0.5 + 0.5).max(0.0).min(1.0);
+    let super_rotate = ((v * 25.0 + u * 5.0 - cloud_time * 0.25).sin() * 0.5 + 0.5).max(0.0).min(1.0);
     let band_noise1 = fbm(uv * 15.0, 3);
-    let band_noise2 = fbm(uv * 20.0 - time * 0.05, 2);
+    let band_noise2 = fbm(uv * 20.0 - cloud_time * 0.05, 2);
     let band_combined = band_noise1 * 0.6 + band_noise2 * 0.4;
     let band_color = Vector3::new(0.9, 0.6, 0.0);
     let with_bands = mix_color(with_volcanoes, band_color, super_rotate * band_combined * 0.4);
     
     // Layer 6: Sulfuric acid layer markings (caustic patterns)
-    let sulfur_pattern1 = fbm(uv * 12.0 + time * 0.12, 3);
-    let sulfur_pattern2 = fbm(uv * 18.0 - time * 0.08, 2);
+    let sulfur_pattern1 = fbm(uv * 12.0 + cloud_time * 0.12, 3);
+    let sulfur_pattern2 = fbm(uv * 18.0 - cloud_time * 0.08, 2);
     let sulfur_combined = (sulfur_pattern1 + sulfur_pattern2) * 0.5;
     let sulfur_color = Vector3::new(1.0, 0.95, 0.5);
     let sulfur_mask = smoothstep(0.3, 0.7, sulfur_combined) * 0.2;
@@ -715,40 +873,563 @@ fn venus_shader(_fragment: &Fragment, vertex: &Vertex, time: f32) -> Vector3 {
     result
 }
 
-/// Get the appropriate shader color based on planet type
-pub fn get_planet_color(fragment: &Fragment, vertex: &Vertex, time: f32, planet_type: u32) -> Vector3 {
-    // Get base color from shader
-    let base_color = match planet_type {
-        0 => sun_shader(fragment, vertex, time),
-        1 => earth_shader(fragment, vertex, time),
-        2 => gas_giant_shader(fragment, vertex, time),
-        3 => moon_shader(fragment, vertex, time),    // Moon shader
-        4 => ring_shader(fragment, vertex, time),    // Ring shader
-        5 => neptune_shader(fragment, vertex, time), // Neptune shader
-        6 => uranus_shader(fragment, vertex, time),  // Uranus shader
-        7 => venus_shader(fragment, vertex, time),   // Venus shader
-        _ => Vector3::new(1.0, 1.0, 1.0), // Default white
+/// MARS - Dry rocky planet: iron-oxide dunes, canyons, CO2 polar caps and dust storms (5 layers)
+fn mars_shader(_fragment: &Fragment, vertex: &Vertex, time: f32, animation: AnimationSpeeds) -> Vector3 {
+    // UV coordinates from position
+    let uv = match sphere_uv(vertex.position) {
+        Some(uv) => uv,
+        None => return Vector3::new(0.0, 0.0, 0.0),
     };
-    
-    // Apply lighting (except for Sun and rings)
-    if planet_type == 0 || planet_type == 4 {
-        // Sun emits light, rings are flat
-        base_color
-    } else {
-        // Light direction (from Sun at origin towards positive Z)
-        let light_dir = Vector3::new(0.3, 0.5, 1.0);
-        
-        // View direction (camera looking at planet)
-        let view_dir = Vector3::new(0.0, 0.0, -1.0);
-        
-        // Calculate lighting factor
-        let lighting = calculate_lighting(vertex.normal, light_dir, view_dir);
-        
-        // Apply lighting to base color
-        Vector3::new(
-            base_color.x * lighting,
-            base_color.y * lighting,
-            base_color.z * lighting,
-        )
-    }
-}
\ No newline at end of file
+    let v = uv.y;
+    let cloud_time = time * animation.cloud;
+    let storm_time = time * animation.storm;
+
+    // Get material color from MTL file if available
+    let material_color = vertex.color;
+
+    // Layer 1: Iron-oxide terrain from basalt to bright dust
+    let terrain = fbm(uv * 3.0, 4);
+    let base_color = MARS_DUNE_RAMP.sample(terrain);
+    let base_with_material = mix_color(base_color, material_color, 0.25);
+
+    // Layer 2: Dune fields (stretched ridges, light crests)
+    let dune_warp = fbm(uv * 6.0, 2);
+    let dunes = ((uv.x * 60.0 + dune_warp * 8.0).sin() * 0.5 + 0.5) * smoothstep(0.45, 0.65, fbm(uv * 4.0 + 3.7, 3));
+    let with_dunes = mix_color(base_with_material, MARS_DUNE_RAMP.sample(0.9), dunes * 0.35);
+
+    // Layer 3: Canyons and valleys (thin dark channels where the noise crosses its midpoint)
+    let canyon_noise = fbm(uv * 5.0 + 11.3, 5);
+    let canyon_mask = 1.0 - smoothstep(0.0, 0.035, (canyon_noise - 0.5).abs());
+    let canyon_color = Vector3::new(0.25, 0.08, 0.04);
+    let with_canyons = mix_color(with_dunes, canyon_color, canyon_mask * 0.7);
+
+    // Layer 4: CO2 polar ice caps with a frayed edge
+    let polar_distance = (v - 0.5).abs() * 2.0;
+    let cap_edge = 0.82 + (fbm(uv * 10.0, 3) - 0.5) * 0.12;
+    let cap_mask = smoothstep(cap_edge - 0.04, cap_edge + 0.02, polar_distance);
+    let ice_color = ICE_RAMP.sample(0.55 + fbm(uv * 30.0, 2) * 0.3);
+    let with_caps = mix_color(with_canyons, ice_color, cap_mask * 0.9);
+
+    // Layer 5: Dust storms sweeping across the surface, with thin haze elsewhere
+    let storm_field = fbm(uv * 3.5 + Vector2::new(storm_time * 0.04, storm_time * 0.01), 4);
+    let storm_mask = smoothstep(0.55, 0.75, storm_field);
+    let haze = fbm(uv * 8.0 - cloud_time * 0.03, 2) * 0.15;
+    let dust_color = Vector3::new(0.85, 0.55, 0.3);
+    let result = mix_color(with_caps, dust_color, (storm_mask * 0.7 + haze).min(1.0));
+
+    result
+}
+
+/// ACCRETION DISK - Glowing gas torus: temperature gradient, turbulent swirl, spiral streaks,
+/// inner-edge flare and Doppler beaming (5 layers). Reads the torus UVs: u around the disk,
+/// v around the tube with 0.5 on the inner edge. Emissive; drawn with additive blending.
+fn accretion_disk_shader(fragment: &Fragment, time: f32, disk: AccretionDisk) -> Vector3 {
+    let two_pi = std::f32::consts::PI * 2.0;
+    let u = fragment.tex_coords.x;
+    let v = fragment.tex_coords.y;
+
+    // Layer 1: Temperature, hottest on the inner edge of the tube
+    let heat = 0.5 - 0.5 * (v * two_pi).cos();
+    let base_color = mix_color(disk.outer_color, disk.inner_color, heat * heat);
+
+    // Layer 2: Turbulent swirl; inner gas orbits faster. The noise is sampled on a circle so
+    // it wraps around the disk without a seam
+    let angle = u * two_pi - time * disk.swirl_speed * (0.5 + heat);
+    let ring_point = Vector2::new(angle.cos(), angle.sin()) * (2.0 + heat * 3.0);
+    let turbulence = fbm(ring_point * 2.0 + Vector2::new(heat * 4.0, 0.0), 4);
+    let with_turbulence = base_color * (0.45 + turbulence * 1.1);
+
+    // Layer 3: Spiral streaks winding inwards
+    let streaks = smoothstep(0.6, 1.0, (angle * 3.0 + heat * 9.0).sin() * 0.5 + 0.5);
+    let with_streaks = with_turbulence + base_color * (streaks * turbulence * 0.5);
+
+    // Layer 4: White-hot flare along the inner edge
+    let flare = smoothstep(0.85, 1.0, heat);
+    let with_flare = mix_color(with_streaks, Vector3::new(1.0, 1.0, 1.0), flare * 0.5);
+
+    // Layer 5: Doppler beaming, the side moving towards the viewer is brighter
+    let beaming = 1.0 + 0.35 * (u * two_pi).sin();
+    let result = with_flare * beaming;
+
+    result
+}
+
+/// Colors and motion of the accretion-disk shader
+#[derive(Clone, Copy, Debug)]
+pub struct AccretionDisk {
+    pub inner_color: Vector3, // Hot inner edge
+    pub outer_color: Vector3, // Cool outer edge
+    pub swirl_speed: f32,     // Radians per time unit the gas turns around the disk
+}
+
+impl Default for AccretionDisk {
+    fn default() -> Self {
+        AccretionDisk {
+            inner_color: Vector3::new(0.75, 0.85, 1.0),
+            outer_color: Vector3::new(0.7, 0.12, 0.03),
+            swirl_speed: 0.6,
+        }
+    }
+}
+
+/// How a body's surface responds to light. Metallic surfaces tint their highlights with the
+/// base color; dielectric ones (ice, glossy clouds) reflect the light's own white.
+/// The default reproduces the original fixed weighting (0.7 diffuse, 0.3 tinted specular).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SurfaceMaterial {
+    pub diffuse: f32,  // Weight of the Lambertian term
+    pub specular: f32, // Weight of the Phong highlight
+    pub metallic: bool,
+}
+
+impl Default for SurfaceMaterial {
+    fn default() -> Self {
+        SurfaceMaterial { diffuse: 0.7, specular: 0.3, metallic: true }
+    }
+}
+
+/// Ring system whose shadow falls on its planet, with directions in world space around the planet's
+/// center: the surface is the unit sphere, radii are in planet radii and the plane passes through the center.
+#[derive(Clone, Copy, Debug)]
+pub struct RingShadow {
+    pub plane_normal: Vector3,
+    pub inner_radius: f32,
+    pub outer_radius: f32,
+    pub softness: f32, // Half-width of the blurred shadow edges, in planet radii
+    pub darkness: f32, // Light blocked at the middle of the band (0 = no shadow, 1 = black)
+}
+
+/// Fraction of the sunlight reaching the surface point in direction `point` from the planet center
+/// (normalized here, so a world-space normal works) past the rings: the ray towards
+/// the light is intersected with the ring plane, and a hit between the radii (outside the gaps) darkens the point,
+/// fading in over `softness` at both edges. 1 when the light doesn't cross the rings.
+pub fn ring_shadow_factor(point: Vector3, light_dir: Vector3, shadow: &RingShadow) -> f32 {
+    let point = normalize(point);
+    let n = normalize(shadow.plane_normal);
+    let l = normalize(light_dir);
+    let facing = l.x * n.x + l.y * n.y + l.z * n.z;
+    if facing.abs() < 1e-4 {
+        return 1.0; // Light grazing the plane: the ring is seen edge-on from the sun
+    }
+
+    let distance = -(point.x * n.x + point.y * n.y + point.z * n.z) / facing;
+    if distance <= 0.0 {
+        return 1.0; // The ring plane is behind the point, away from the light
+    }
+
+    let radius = (point + l * distance).length();
+    let softness = shadow.softness.max(1e-4);
+    let coverage = smoothstep(shadow.inner_radius - softness, shadow.inner_radius + softness, radius)
+        * (1.0 - smoothstep(shadow.outer_radius - softness, shadow.outer_radius + softness, radius));
+
+    // Sunlight passes through the ring gaps
+    let width = (shadow.outer_radius - shadow.inner_radius).max(1e-4);
+    let u = (radius - shadow.inner_radius) / width;
+    let coverage = coverage * smoothstep(0.0, softness / width, distance_to_gap(u, RING_GAPS));
+    1.0 - shadow.darkness.clamp(0.0, 1.0) * coverage
+}
+
+/// Per-body multipliers of the shaders' weather animation, independent of the body's spin.
+/// 1.0 keeps each shader's built-in rate.
+#[derive(Clone, Copy, Debug)]
+pub struct AnimationSpeeds {
+    pub cloud: f32, // Cloud and band drift
+    pub storm: f32, // Storm and spot swirl
+}
+
+impl Default for AnimationSpeeds {
+    fn default() -> Self {
+        AnimationSpeeds { cloud: 1.0, storm: 1.0 }
+    }
+}
+
+/// Direction towards the light used for bodies unless it is overridden by hand
+pub const DEFAULT_LIGHT_DIR: Vector3 = Vector3 { x: 0.3, y: 0.5, z: 1.0 };
+
+/// Directional light of one star on the body being lit: the world-space direction towards the
+/// star and the color of its light (brighter than white adds up past 1)
+#[derive(Clone, Copy, Debug)]
+pub struct StarLight {
+    pub direction: Vector3,
+    pub color: Vector3,
+}
+
+/// White light from `DEFAULT_LIGHT_DIR`, for views without a star to light them
+pub const DEFAULT_STAR_LIGHT: StarLight = StarLight { direction: DEFAULT_LIGHT_DIR, color: Vector3 { x: 1.0, y: 1.0, z: 1.0 } };
+
+/// Towards an unrotated camera, which looks down world +Z (smaller z is nearer)
+pub const DEFAULT_VIEW_DIR: Vector3 = Vector3 { x: 0.0, y: 0.0, z: -1.0 };
+
+/// Unit direction for an azimuth (around Y, from +Z towards +X) and elevation (towards +Y), in radians
+pub fn light_direction(azimuth: f32, elevation: f32) -> Vector3 {
+    Vector3::new(elevation.cos() * azimuth.sin(), elevation.sin(), elevation.cos() * azimuth.cos())
+}
+
+/// Inverse of `light_direction`: (azimuth, elevation) of a direction, in radians
+pub fn light_angles(direction: Vector3) -> (f32, f32) {
+    let d = normalize(direction);
+    (d.x.atan2(d.z), d.y.clamp(-1.0, 1.0).asin())
+}
+
+/// Per-body inputs of the surface shaders besides the fragment itself
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ShaderParams {
+    pub animation: AnimationSpeeds, // Speed of the cloud/storm animation
+    pub disk: AccretionDisk,        // Colors and swirl of the accretion-disk shader
+}
+
+/// Surface shader of a body: the unlit base color of a fragment, plus how lighting treats it
+pub trait FragmentShader: Send + Sync {
+    fn shade(&self, fragment: &Fragment, vertex: &Vertex, time: f32, params: &ShaderParams) -> Vector3;
+
+    /// Whether diffuse and specular lighting apply (emissive and flat surfaces opt out)
+    fn lit(&self) -> bool {
+        true
+    }
+
+    /// Fraction of the specular highlight reflected at `vertex`
+    fn glossiness(&self, _vertex: &Vertex, _time: f32) -> f32 {
+        1.0
+    }
+}
+
+pub struct SunShader;
+pub struct EarthShader;
+pub struct GasGiantShader;
+pub struct MoonShader;
+pub struct RingShader;
+pub struct NeptuneShader;
+pub struct UranusShader;
+pub struct VenusShader;
+pub struct MarsShader;
+pub struct AccretionDiskShader;
+
+impl FragmentShader for SunShader {
+    fn shade(&self, fragment: &Fragment, vertex: &Vertex, time: f32, _params: &ShaderParams) -> Vector3 {
+        sun_shader(fragment, vertex, time)
+    }
+
+    fn lit(&self) -> bool {
+        false
+    }
+}
+
+impl FragmentShader for EarthShader {
+    fn shade(&self, fragment: &Fragment, vertex: &Vertex, time: f32, params: &ShaderParams) -> Vector3 {
+        earth_shader(fragment, vertex, time, params.animation)
+    }
+
+    // Land is rough: the glint only shows on the oceans
+    fn glossiness(&self, vertex: &Vertex, time: f32) -> f32 {
+        sphere_uv(vertex.position).map_or(0.0, |uv| 1.0 - earth_land_mask(uv, time))
+    }
+}
+
+impl FragmentShader for GasGiantShader {
+    fn shade(&self, fragment: &Fragment, vertex: &Vertex, time: f32, params: &ShaderParams) -> Vector3 {
+        gas_giant_shader(fragment, vertex, time, params.animation)
+    }
+}
+
+impl FragmentShader for MoonShader {
+    fn shade(&self, fragment: &Fragment, vertex: &Vertex, time: f32, _params: &ShaderParams) -> Vector3 {
+        moon_shader(fragment, vertex, time)
+    }
+}
+
+impl FragmentShader for RingShader {
+    fn shade(&self, fragment: &Fragment, vertex: &Vertex, time: f32, _params: &ShaderParams) -> Vector3 {
+        ring_shader(fragment, vertex, time)
+    }
+
+    fn lit(&self) -> bool {
+        false
+    }
+}
+
+impl FragmentShader for NeptuneShader {
+    fn shade(&self, fragment: &Fragment, vertex: &Vertex, time: f32, params: &ShaderParams) -> Vector3 {
+        neptune_shader(fragment, vertex, time, params.animation)
+    }
+}
+
+impl FragmentShader for UranusShader {
+    fn shade(&self, fragment: &Fragment, vertex: &Vertex, time: f32, params: &ShaderParams) -> Vector3 {
+        uranus_shader(fragment, vertex, time, params.animation)
+    }
+}
+
+impl FragmentShader for VenusShader {
+    fn shade(&self, fragment: &Fragment, vertex: &Vertex, time: f32, params: &ShaderParams) -> Vector3 {
+        venus_shader(fragment, vertex, time, params.animation)
+    }
+}
+
+impl FragmentShader for MarsShader {
+    fn shade(&self, fragment: &Fragment, vertex: &Vertex, time: f32, params: &ShaderParams) -> Vector3 {
+        mars_shader(fragment, vertex, time, params.animation)
+    }
+}
+
+impl FragmentShader for AccretionDiskShader {
+    fn shade(&self, fragment: &Fragment, _vertex: &Vertex, time: f32, params: &ShaderParams) -> Vector3 {
+        accretion_disk_shader(fragment, time, params.disk)
+    }
+
+    fn lit(&self) -> bool {
+        false
+    }
+}
+
+/// Shader used for each `PlanetType`. Starts with the built-in shaders; `register` swaps one out
+pub struct ShaderRegistry {
+    shaders: HashMap<PlanetType, Box<dyn FragmentShader>>,
+}
+
+impl ShaderRegistry {
+    fn builtin() -> Self {
+        let mut registry = ShaderRegistry { shaders: HashMap::new() };
+        registry.register(PlanetType::Sun, Box::new(SunShader));
+        registry.register(PlanetType::EarthLike, Box::new(EarthShader));
+        registry.register(PlanetType::GasGiant, Box::new(GasGiantShader));
+        registry.register(PlanetType::Moon, Box::new(MoonShader));
+        registry.register(PlanetType::Ring, Box::new(RingShader));
+        registry.register(PlanetType::Neptune, Box::new(NeptuneShader));
+        registry.register(PlanetType::Uranus, Box::new(UranusShader));
+        registry.register(PlanetType::Venus, Box::new(VenusShader));
+        registry.register(PlanetType::Mars, Box::new(MarsShader));
+        registry.register(PlanetType::AccretionDisk, Box::new(AccretionDiskShader));
+        registry
+    }
+
+    /// Sets the shader for `planet_type`, replacing the current one
+    pub fn register(&mut self, planet_type: PlanetType, shader: Box<dyn FragmentShader>) {
+        self.shaders.insert(planet_type, shader);
+    }
+
+    pub fn get(&self, planet_type: PlanetType) -> &dyn FragmentShader {
+        // Every type gets a built-in shader and entries are only ever replaced
+        self.shaders[&planet_type].as_ref()
+    }
+}
+
+static SHADERS: LazyLock<RwLock<ShaderRegistry>> = LazyLock::new(|| RwLock::new(ShaderRegistry::builtin()));
+
+/// The shader registry, locked for reading. Hold it for a whole draw rather than per fragment
+pub fn shader_registry() -> RwLockReadGuard<'static, ShaderRegistry> {
+    SHADERS.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Replaces the shader drawn for `planet_type`, from the next draw on
+pub fn register_shader(planet_type: PlanetType, shader: Box<dyn FragmentShader>) {
+    SHADERS.write().unwrap_or_else(|poisoned| poisoned.into_inner()).register(planet_type, shader);
+}
+
+/// Lit color of a fragment: `shader`'s base color plus diffuse/specular lighting when the shader is lit.
+/// Every star in `stars` lights the fragment on its own and their terms add up, each tinted by
+/// its color; `ambient` is the minimum illumination of lit bodies, so their night side never goes
+/// fully black; `material` weights the diffuse and specular terms
+#[allow(clippy::too_many_arguments)]
+pub fn get_planet_color(
+    fragment: &Fragment,
+    vertex: &Vertex,
+    time: f32,
+    shader: &dyn FragmentShader,
+    params: &ShaderParams,
+    stars: &[StarLight],
+    view_dir: Vector3,
+    ambient: Vector3,
+    material: SurfaceMaterial,
+) -> Vector3 {
+    let base_color = shader.shade(fragment, vertex, time, params);
+
+    // A non-finite color points at a math bug in the shader (division, asin/atan2 domain...)
+    debug_assert!(
+        base_color.x.is_finite() && base_color.y.is_finite() && base_color.z.is_finite(),
+        "shader returned a non-finite color {:?} at {:?}",
+        base_color,
+        vertex.transformed_position,
+    );
+
+    if !shader.lit() {
+        // Sun and disk emit light, rings are flat
+        return base_color;
+    }
+
+    // Sum the lighting terms of every star, each in its own color
+    let mut diffuse = Vector3::zero();
+    let mut specular = Vector3::zero();
+    for star in stars {
+        let (star_diffuse, star_specular) = calculate_lighting(vertex.normal, star.direction, view_dir);
+        diffuse += star.color * star_diffuse;
+        specular += star.color * star_specular;
+    }
+    let diffuse = diffuse * material.diffuse;
+    let highlight = specular * (material.specular * shader.glossiness(vertex, time));
+
+    // Diffuse plus the ambient floor tints the base color; the highlight is tinted only when metallic
+    let lit = Vector3::new(
+        base_color.x * (diffuse.x + ambient.x),
+        base_color.y * (diffuse.y + ambient.y),
+        base_color.z * (diffuse.z + ambient.z),
+    );
+    let highlight_color = if material.metallic { base_color } else { Vector3::new(1.0, 1.0, 1.0) };
+    lit + Vector3::new(highlight_color.x * highlight.x, highlight_color.y * highlight.y, highlight_color.z * highlight.z)
+}
+
+/// Point on the unit sphere that `sphere_uv` maps back to `uv` (inverse mapping)
+fn uv_to_sphere(uv: Vector2) -> Vector3 {
+    let theta = (uv.x * 2.0 - 1.0) * std::f32::consts::PI;
+    let phi = (uv.y - 0.5) * std::f32::consts::PI;
+    Vector3::new(theta.sin() * phi.cos(), phi.sin(), theta.cos() * phi.cos())
+}
+
+/// Raw (unlit) output of a shader at a sphere `uv`, with a synthetic unit-sphere position
+/// reconstructed from the UV and a neutral gray material. The ring and accretion-disk shaders read `uv` directly.
+pub fn shade_unlit(planet_type: PlanetType, uv: Vector2, time: f32) -> Vector3 {
+    let position = uv_to_sphere(uv);
+    let mut vertex = Vertex::new(position, position, uv);
+    vertex.color = Vector3::new(0.5, 0.5, 0.5);
+    let mut fragment = Fragment::new(0.0, 0.0, vertex.color, 0.0);
+    fragment.tex_coords = uv;
+
+    shader_registry().get(planet_type).shade(&fragment, &vertex, time, &ShaderParams::default())
+}
+
+/// Representative (unlit) color of a shader: the average of a grid of samples over the
+/// sphere at a fixed time, with a neutral gray material. Used for the legend swatches.
+pub fn representative_color(planet_type: PlanetType) -> Vector3 {
+    const SAMPLES_U: usize = 6;
+    const SAMPLES_V: usize = 4;
+
+    let mut sum = Vector3::zero();
+    for i in 0..SAMPLES_U {
+        for j in 0..SAMPLES_V {
+            let u = (i as f32 + 0.5) / SAMPLES_U as f32;
+            let v = (j as f32 + 0.5) / SAMPLES_V as f32;
+
+            sum = sum + shade_unlit(planet_type, Vector2::new(u, v), 0.0);
+        }
+    }
+
+    sum * (1.0 / (SAMPLES_U * SAMPLES_V) as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn planet_type_ids_round_trip() {
+        for planet_type in PlanetType::ALL {
+            assert_eq!(PlanetType::try_from(planet_type.id()), Ok(planet_type));
+        }
+        // ALL is in id order, so its position is the id
+        for (id, planet_type) in PlanetType::ALL.iter().enumerate() {
+            assert_eq!(planet_type.id() as usize, id);
+        }
+        for id in [PlanetType::ALL.len() as u32, 42, u32::MAX] {
+            assert_eq!(PlanetType::try_from(id), Err(UnknownPlanetType(id)));
+        }
+    }
+
+    #[test]
+    fn point_lights_fade_in_3d_and_only_light_the_side_facing_them() {
+        let light = PointLight { position: Vector3::new(0.0, 0.0, 10.0), color: Vector3::one(), intensity: 1.0, radius: 20.0 };
+        let lit = |position: Vector3, normal: Vector3| apply_point_lights(Vector3::zero(), position, normal, &[light]).x;
+        let up = Vector3::new(0.0, 0.0, 1.0);
+
+        let near = lit(Vector3::zero(), up);
+        assert!(near > 0.0);
+        // Farther along any axis, depth included, is dimmer, and nothing reaches past the radius
+        assert!(lit(Vector3::new(0.0, 0.0, -5.0), up) < near);
+        assert!(lit(Vector3::new(0.0, 0.0, -5.0), up) > lit(Vector3::new(0.0, 0.0, -9.0), up));
+        assert_eq!(lit(Vector3::new(0.0, 0.0, -10.0), up), 0.0);
+        // Grazing light is weaker than head-on, and the far side gets none
+        let slanted = Vector3::new(1.0, 0.0, 1.0).normalized();
+        assert!(lit(Vector3::zero(), slanted) < near);
+        assert_eq!(lit(Vector3::zero(), -up), 0.0);
+    }
+
+    #[test]
+    fn every_star_adds_its_own_light() {
+        // Flat gray surface taking the light fully, without highlights
+        struct Gray;
+        impl FragmentShader for Gray {
+            fn shade(&self, _: &Fragment, _: &Vertex, _: f32, _: &ShaderParams) -> Vector3 {
+                Vector3::new(0.5, 0.5, 0.5)
+            }
+        }
+        let up = Vector3::new(0.0, 1.0, 0.0);
+        let vertex = Vertex::new(up, up, Vector2::zero());
+        let fragment = Fragment::new(0.0, 0.0, Vector3::zero(), 0.0);
+        let matte = SurfaceMaterial { diffuse: 1.0, specular: 0.0, metallic: false };
+        let lit = |stars: &[StarLight]| {
+            get_planet_color(&fragment, &vertex, 0.0, &Gray, &ShaderParams::default(), stars, DEFAULT_VIEW_DIR, Vector3::zero(), matte)
+        };
+
+        let overhead = StarLight { direction: up, color: Vector3::one() };
+        let one = lit(&[overhead]);
+        assert_eq!(one, Vector3::new(0.5, 0.5, 0.5));
+        // A red star at 45° adds its own N·L, in red only
+        let red = StarLight { direction: Vector3::new(1.0, 1.0, 0.0), color: Vector3::new(1.0, 0.0, 0.0) };
+        let both = lit(&[overhead, red]);
+        assert!((both.x - 0.5 * (1.0 + std::f32::consts::FRAC_1_SQRT_2)).abs() < 1e-5, "{:?}", both);
+        assert_eq!((both.y, both.z), (0.5, 0.5));
+        // A star below the horizon adds nothing, and without stars only the ambient is left
+        let below = StarLight { direction: -up, color: Vector3::one() };
+        assert_eq!(lit(&[overhead, below]), one);
+        assert_eq!(lit(&[]), Vector3::zero());
+    }
+
+    #[test]
+    fn shaders_stay_finite_on_a_degenerate_vertex() {
+        // Everything zero: the origin, no normal, no UV. atan2(0, 0), normalizing a zero vector
+        // and dividing by the distance from the center must not leak NaN into the color
+        let vertex = Vertex::new(Vector3::zero(), Vector3::zero(), Vector2::zero());
+        let fragment = Fragment::new(0.0, 0.0, Vector3::zero(), 0.0);
+
+        let registry = shader_registry();
+        for planet_type in PlanetType::ALL {
+            for time in [0.0, 12.5] {
+                // get_planet_color also debug-asserts on the shader's own output
+                let color = get_planet_color(
+                    &fragment,
+                    &vertex,
+                    time,
+                    registry.get(planet_type),
+                    &ShaderParams::default(),
+                    &[DEFAULT_STAR_LIGHT],
+                    DEFAULT_VIEW_DIR,
+                    Vector3::new(0.1, 0.1, 0.1),
+                    SurfaceMaterial::default(),
+                );
+                assert!(
+                    color.x.is_finite() && color.y.is_finite() && color.z.is_finite(),
+                    "{:?} returned {:?}",
+                    planet_type,
+                    color
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn log_depth_spans_the_ndc_range_and_keeps_far_precision() {
+        let far = 4000.0;
+        assert!((log_depth(0.0, far) + 1.0).abs() < 1e-6);
+        assert!((log_depth(far, far) - 1.0).abs() < 1e-5);
+        assert!(log_depth(100.0, far) < log_depth(101.0, far));
+
+        // Two surfaces one unit apart near the far plane: the projection's depth barely separates
+        // them, the logarithmic one keeps a gap over ten times larger
+        let projection = crate::matrix::perspective(std::f32::consts::PI / 3.0, 1.0, 10.0, far);
+        let ndc_depth = |w: f32| {
+            let clip = multiply_matrix_vector4(&projection, &Vector4::new(0.0, 0.0, -w, 1.0));
+            clip.z / clip.w
+        };
+        let linear_gap = ndc_depth(3001.0) - ndc_depth(3000.0);
+        let log_gap = log_depth(3001.0, far) - log_depth(3000.0, far);
+        assert!(log_gap > 10.0 * linear_gap, "log {} vs projection {}", log_gap, linear_gap);
+    }
+}
@@ -0,0 +1,197 @@
+use crate::vertex::Vertex;
+use raylib::math::{Vector2, Vector3};
+use std::collections::HashMap;
+
+/// Highest icosphere subdivision honored (level 6 is already 81920 triangles)
+const MAX_ICOSPHERE_SUBDIVISIONS: u32 = 6;
+
+/// Generate an indexed UV sphere (radius 1 matches a normalized OBJ model).
+/// Follows the OBJ loader's conventions: Y is flipped and faces wind like OBJ front faces.
+/// UVs follow the shaders' `sphere_uv`: u = atan2(x, z) mapped to 0..1 around the equator,
+/// v = asin(y) mapped to 0..1 from the top pole (y = -1 after the flip) to the bottom one.
+pub fn generate_uv_sphere(radius: f32, stacks: usize, slices: usize) -> (Vec<Vertex>, Vec<u32>) {
+    let stacks = stacks.max(2);
+    let slices = slices.max(3);
+    let pi = std::f32::consts::PI;
+
+    let mut vertices = Vec::with_capacity((stacks + 1) * (slices + 1));
+    for i in 0..=stacks {
+        let v = i as f32 / stacks as f32;
+        let (sin_phi, cos_phi) = (v * pi).sin_cos();
+
+        // The seam column is duplicated so u reaches 1 without wrapping back to 0
+        for j in 0..=slices {
+            let u = j as f32 / slices as f32;
+            // atan2(x, z) runs from -π at u = 0 to π at u = 1
+            let (sin_theta, cos_theta) = ((u * 2.0 - 1.0) * pi).sin_cos();
+
+            // Y up from the top pole, then flipped like the loader does
+            let normal = Vector3::new(sin_phi * sin_theta, -cos_phi, sin_phi * cos_theta);
+            let mut vertex = Vertex::new(normal * radius, normal, Vector2::new(u, v));
+            vertex.color = Vector3::new(1.0, 1.0, 1.0); // Same as a model without material
+            vertices.push(vertex);
+        }
+    }
+
+    let mut indices = Vec::with_capacity(stacks * slices * 6);
+    let row = (slices + 1) as u32;
+    for i in 0..stacks as u32 {
+        for j in 0..slices as u32 {
+            let a = i * row + j;
+            let b = a + row;
+
+            // The quads touching a pole collapse to a single triangle
+            if i != 0 {
+                indices.extend_from_slice(&[a, b, a + 1]);
+            }
+            if i + 1 != stacks as u32 {
+                indices.extend_from_slice(&[a + 1, b, b + 1]);
+            }
+        }
+    }
+
+    (vertices, indices)
+}
+
+/// Generate an indexed icosphere: an icosahedron whose triangles are split into 4, `subdivisions`
+/// times, with the new vertices pushed onto the sphere. Triangles are nearly uniform, without the
+/// UV sphere's pinching at the poles; level n has 10 * 4^n + 2 vertices and 20 * 4^n triangles.
+/// Same conventions as `generate_uv_sphere` (Y flipped, OBJ winding, `sphere_uv` UVs), but
+/// vertices are not duplicated along the seam, so triangles crossing it interpolate u across
+/// the whole texture. Levels above `MAX_ICOSPHERE_SUBDIVISIONS` are clamped.
+#[allow(dead_code)] // Alternative to the UV sphere; the built-in scene doesn't use it yet
+pub fn generate_icosphere(radius: f32, subdivisions: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let t = (1.0 + 5.0_f32.sqrt()) / 2.0;
+    let mut directions: Vec<Vector3> = [
+        (-1.0, t, 0.0), (1.0, t, 0.0), (-1.0, -t, 0.0), (1.0, -t, 0.0),
+        (0.0, -1.0, t), (0.0, 1.0, t), (0.0, -1.0, -t), (0.0, 1.0, -t),
+        (t, 0.0, -1.0), (t, 0.0, 1.0), (-t, 0.0, -1.0), (-t, 0.0, 1.0),
+    ]
+    .iter()
+    .map(|&(x, y, z)| Vector3::new(x, y, z).normalized())
+    .collect();
+
+    // Counter-clockwise seen from outside, like OBJ front faces before the Y flip
+    let mut indices: Vec<u32> = vec![
+        0, 11, 5, 0, 5, 1, 0, 1, 7, 0, 7, 10, 0, 10, 11,
+        1, 5, 9, 5, 11, 4, 11, 10, 2, 10, 7, 6, 7, 1, 8,
+        3, 9, 4, 3, 4, 2, 3, 2, 6, 3, 6, 8, 3, 8, 9,
+        4, 9, 5, 2, 4, 11, 6, 2, 10, 8, 6, 7, 9, 8, 1,
+    ];
+
+    for _ in 0..subdivisions.min(MAX_ICOSPHERE_SUBDIVISIONS) {
+        // Edges shared by two triangles must reuse the same midpoint vertex
+        let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut midpoint = |a: u32, b: u32, directions: &mut Vec<Vector3>| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                directions.push((directions[a as usize] + directions[b as usize]).normalized());
+                directions.len() as u32 - 1
+            })
+        };
+
+        let mut next_indices = Vec::with_capacity(indices.len() * 4);
+        for face in indices.chunks_exact(3) {
+            let (a, b, c) = (face[0], face[1], face[2]);
+            let ab = midpoint(a, b, &mut directions);
+            let bc = midpoint(b, c, &mut directions);
+            let ca = midpoint(c, a, &mut directions);
+            next_indices.extend_from_slice(&[a, ab, ca, ab, b, bc, ca, bc, c, ab, bc, ca]);
+        }
+        indices = next_indices;
+    }
+
+    let pi = std::f32::consts::PI;
+    let vertices = directions
+        .into_iter()
+        .map(|direction| {
+            // Flipped like the loader does, which also turns the faces clockwise on screen
+            let normal = Vector3::new(direction.x, -direction.y, direction.z);
+            let uv = Vector2::new((normal.x.atan2(normal.z) / pi + 1.0) * 0.5, normal.y.clamp(-1.0, 1.0).asin() / pi + 0.5);
+            let mut vertex = Vertex::new(normal * radius, normal, uv);
+            vertex.color = Vector3::new(1.0, 1.0, 1.0); // Same as a model without material
+            vertex
+        })
+        .collect();
+
+    (vertices, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RADIUS: f32 = 2.5;
+
+    fn assert_on_sphere(vertices: &[Vertex]) {
+        for vertex in vertices {
+            assert!((vertex.position.length() - RADIUS).abs() < 1e-4, "{:?} is off the sphere", vertex.position);
+            assert!((vertex.normal.length() - 1.0).abs() < 1e-5);
+            assert!((vertex.normal * RADIUS - vertex.position).length() < 1e-4, "normal {:?} is not radial", vertex.normal);
+        }
+    }
+
+    /// Faces wind like the loader's flipped OBJ faces (see `obj::face_normal`): outward is (c - a) × (b - a)
+    fn assert_wound_outward(vertices: &[Vertex], indices: &[u32]) {
+        for face in indices.chunks_exact(3) {
+            let [a, b, c] = [face[0], face[1], face[2]].map(|index| vertices[index as usize].position);
+            let center = (a + b + c) / 3.0;
+            assert!((c - a).cross(b - a).dot(center) > 0.0, "face at {:?} is wound inward", center);
+        }
+    }
+
+    #[test]
+    fn uv_sphere_vertices_lie_on_the_sphere() {
+        let (vertices, indices) = generate_uv_sphere(RADIUS, 8, 12);
+        assert_eq!(vertices.len(), 9 * 13);
+        assert_on_sphere(&vertices);
+        assert_wound_outward(&vertices, &indices);
+    }
+
+    #[test]
+    fn uv_sphere_has_two_triangles_per_quad_but_one_at_the_poles() {
+        let (stacks, slices) = (8, 12);
+        let (_, indices) = generate_uv_sphere(RADIUS, stacks, slices);
+        // stacks * slices * 2, minus the collapsed half of every quad touching a pole
+        assert_eq!(indices.len() / 3, stacks * slices * 2 - 2 * slices);
+    }
+
+    #[test]
+    fn uv_sphere_uvs_match_the_shaders_mapping() {
+        let pi = std::f32::consts::PI;
+        let (vertices, _) = generate_uv_sphere(RADIUS, 8, 12);
+        for vertex in &vertices {
+            let n = vertex.normal;
+            let v = n.y.clamp(-1.0, 1.0).asin() / pi + 0.5;
+            assert!((vertex.tex_coords.y - v).abs() < 1e-4);
+            // u is undefined at the poles and is both 0 and 1 on the seam
+            let u = (n.x.atan2(n.z) / pi + 1.0) * 0.5;
+            if n.y.abs() < 0.999 && vertex.tex_coords.x > 1e-4 && vertex.tex_coords.x < 1.0 - 1e-4 {
+                assert!((vertex.tex_coords.x - u).abs() < 1e-4, "u {} != {}", vertex.tex_coords.x, u);
+            }
+        }
+    }
+
+    #[test]
+    fn icosphere_counts_follow_the_subdivision_level() {
+        for level in 0..4 {
+            let (vertices, indices) = generate_icosphere(RADIUS, level);
+            let faces = 4_usize.pow(level);
+            // Shared edges reuse their midpoint, so no vertex is duplicated
+            assert_eq!(vertices.len(), 10 * faces + 2, "level {}", level);
+            assert_eq!(indices.len() / 3, 20 * faces, "level {}", level);
+        }
+    }
+
+    #[test]
+    fn icosphere_vertices_are_on_the_sphere_with_unit_normals() {
+        let (vertices, indices) = generate_icosphere(RADIUS, 3);
+        assert_on_sphere(&vertices);
+        assert_wound_outward(&vertices, &indices);
+    }
+
+    #[test]
+    fn icosphere_levels_above_the_maximum_are_clamped() {
+        let (clamped, _) = generate_icosphere(1.0, MAX_ICOSPHERE_SUBDIVISIONS + 3);
+        assert_eq!(clamped.len(), 10 * 4_usize.pow(MAX_ICOSPHERE_SUBDIVISIONS) + 2);
+    }
+}
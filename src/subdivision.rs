@@ -0,0 +1,79 @@
+use crate::vertex::Vertex;
+use std::collections::HashMap;
+
+/// Highest subdivision level honored (each level multiplies the triangle count by 4)
+pub const MAX_SUBDIVISION_LEVEL: u32 = 3;
+
+/// Stop subdividing before a level would produce more triangles than this
+const MAX_SUBDIVIDED_TRIANGLES: usize = 400_000;
+
+/// Midpoint subdivision: splits every triangle into 4, `levels` times.
+/// Midpoints interpolate every vertex attribute; for `spherical` meshes they are pushed out
+/// to the average distance of their endpoints from the origin so silhouettes get rounder.
+/// Levels above `MAX_SUBDIVISION_LEVEL` or past the triangle budget are ignored.
+pub fn subdivide(vertices: &[Vertex], indices: &[u32], levels: u32, spherical: bool) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = vertices.to_vec();
+    let mut indices = indices.to_vec();
+
+    for level in 0..levels.min(MAX_SUBDIVISION_LEVEL) {
+        if indices.len() / 3 * 4 > MAX_SUBDIVIDED_TRIANGLES {
+            eprintln!("✗ Subdivision stopped at level {} (triangle budget reached)", level);
+            break;
+        }
+
+        // Edges shared by two triangles must reuse the same midpoint vertex
+        let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut next_indices = Vec::with_capacity(indices.len() * 4);
+
+        for face in indices.chunks_exact(3) {
+            let (a, b, c) = (face[0], face[1], face[2]);
+            let ab = midpoint_index(&mut vertices, &mut midpoints, a, b, spherical);
+            let bc = midpoint_index(&mut vertices, &mut midpoints, b, c, spherical);
+            let ca = midpoint_index(&mut vertices, &mut midpoints, c, a, spherical);
+
+            next_indices.extend_from_slice(&[a, ab, ca, ab, b, bc, ca, bc, c, ab, bc, ca]);
+        }
+
+        indices = next_indices;
+    }
+
+    (vertices, indices)
+}
+
+fn midpoint_index(
+    vertices: &mut Vec<Vertex>,
+    midpoints: &mut HashMap<(u32, u32), u32>,
+    a: u32,
+    b: u32,
+    spherical: bool,
+) -> u32 {
+    let key = if a < b { (a, b) } else { (b, a) };
+    if let Some(&index) = midpoints.get(&key) {
+        return index;
+    }
+
+    let va = &vertices[a as usize];
+    let vb = &vertices[b as usize];
+
+    let mut position = (va.position + vb.position) * 0.5;
+    if spherical {
+        let radius = (va.position.length() + vb.position.length()) * 0.5;
+        let len = position.length();
+        if len > 0.0001 {
+            position = position * (radius / len);
+        }
+    }
+
+    let normal_sum = va.normal + vb.normal;
+    let normal = if normal_sum.length() > 0.0001 { normal_sum.normalized() } else { normal_sum };
+
+    let mut vertex = Vertex::new(position, normal, (va.tex_coords + vb.tex_coords) * 0.5);
+    vertex.color = (va.color + vb.color) * 0.5;
+    let tangent_sum = va.tangent + vb.tangent;
+    vertex.tangent = if tangent_sum.length() > 0.0001 { tangent_sum.normalized() } else { tangent_sum };
+
+    let index = vertices.len() as u32;
+    vertices.push(vertex);
+    midpoints.insert(key, index);
+    index
+}
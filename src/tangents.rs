@@ -0,0 +1,112 @@
+use crate::vertex::Vertex;
+use raylib::math::{Vector2, Vector3};
+
+/// Fills `tangent` for every vertex from the positions and UVs of the triangles that use it.
+/// Each face contributes its tangent (direction of increasing u) to its three corners; the sum
+/// is then made orthogonal to the vertex normal (Gram-Schmidt) and normalized.
+/// Vertices without a usable UV gradient get any unit vector perpendicular to their normal.
+pub fn compute_tangents(vertices: &mut [Vertex], indices: &[u32]) {
+    let mut accumulated = vec![Vector3::zero(); vertices.len()];
+
+    for face in indices.chunks_exact(3) {
+        let [a, b, c] = [face[0] as usize, face[1] as usize, face[2] as usize];
+        if let Some(tangent) = face_tangent(&vertices[a], &vertices[b], &vertices[c]) {
+            accumulated[a] += tangent;
+            accumulated[b] += tangent;
+            accumulated[c] += tangent;
+        }
+    }
+
+    for (vertex, tangent) in vertices.iter_mut().zip(accumulated) {
+        vertex.tangent = orthonormal_tangent(vertex.normal, tangent);
+    }
+}
+
+/// Same as `compute_tangents` for a flat triangle list (every 3 vertices form a face)
+pub fn compute_tangents_unindexed(vertices: &mut [Vertex]) {
+    let indices: Vec<u32> = (0..vertices.len() as u32).collect();
+    compute_tangents(vertices, &indices);
+}
+
+/// Tangent of a triangle in object space, or None when its UVs are degenerate
+fn face_tangent(a: &Vertex, b: &Vertex, c: &Vertex) -> Option<Vector3> {
+    let e1 = b.position - a.position;
+    let e2 = c.position - a.position;
+    let d1: Vector2 = b.tex_coords - a.tex_coords;
+    let d2: Vector2 = c.tex_coords - a.tex_coords;
+
+    let det = d1.x * d2.y - d2.x * d1.y;
+    if det.abs() < 1e-8 {
+        return None;
+    }
+    let r = 1.0 / det;
+    Some((e1 * d2.y - e2 * d1.y) * r)
+}
+
+fn orthonormal_tangent(normal: Vector3, tangent: Vector3) -> Vector3 {
+    let n = if normal.length() > 0.0001 { normal.normalized() } else { normal };
+    let t = tangent - n * n.dot(tangent);
+    if t.length() > 0.0001 {
+        return t.normalized();
+    }
+
+    // No UV direction: pick the world axis least aligned with the normal
+    let axis = if n.x.abs() < 0.9 { Vector3::new(1.0, 0.0, 0.0) } else { Vector3::new(0.0, 1.0, 0.0) };
+    let t = axis - n * n.dot(axis);
+    if t.length() > 0.0001 { t.normalized() } else { axis }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit quad on the z = 0 plane, two triangles, with the given normal and UV per corner
+    fn quad(normal: Vector3, uvs: [Vector2; 4]) -> (Vec<Vertex>, Vec<u32>) {
+        let corners = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let vertices = corners.iter().zip(uvs).map(|(&(x, y), uv)| Vertex::new(Vector3::new(x, y, 0.0), normal, uv)).collect();
+        (vertices, vec![0, 1, 2, 0, 2, 3])
+    }
+
+    fn assert_orthonormal(vertices: &[Vertex]) {
+        for vertex in vertices {
+            assert!((vertex.tangent.length() - 1.0).abs() < 1e-5, "tangent {:?} is not unit", vertex.tangent);
+            assert!(vertex.tangent.dot(vertex.normal.normalized()).abs() < 1e-5, "tangent {:?} not orthogonal to {:?}", vertex.tangent, vertex.normal);
+        }
+    }
+
+    #[test]
+    fn quad_tangents_follow_increasing_u() {
+        let square_uvs = [Vector2::new(0.0, 0.0), Vector2::new(1.0, 0.0), Vector2::new(1.0, 1.0), Vector2::new(0.0, 1.0)];
+        let (mut vertices, indices) = quad(Vector3::new(0.0, 0.0, 1.0), square_uvs);
+        compute_tangents(&mut vertices, &indices);
+        assert_orthonormal(&vertices);
+        for vertex in &vertices {
+            assert!((vertex.tangent - Vector3::new(1.0, 0.0, 0.0)).length() < 1e-5);
+        }
+
+        // UVs turned a quarter: u now grows along +Y
+        let turned_uvs = [Vector2::new(0.0, 1.0), Vector2::new(0.0, 0.0), Vector2::new(1.0, 0.0), Vector2::new(1.0, 1.0)];
+        let (mut vertices, indices) = quad(Vector3::new(0.0, 0.0, 1.0), turned_uvs);
+        compute_tangents(&mut vertices, &indices);
+        for vertex in &vertices {
+            assert!((vertex.tangent - Vector3::new(0.0, 1.0, 0.0)).length() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn tangents_are_orthogonal_to_tilted_normals() {
+        // Smoothed normals rarely match the face: the tangent is projected onto their plane
+        let uvs = [Vector2::new(0.0, 0.0), Vector2::new(1.0, 0.0), Vector2::new(1.0, 1.0), Vector2::new(0.0, 1.0)];
+        let (mut vertices, indices) = quad(Vector3::new(0.4, -0.2, 1.0), uvs);
+        compute_tangents(&mut vertices, &indices);
+        assert_orthonormal(&vertices);
+        assert!(vertices[0].tangent.x > 0.9);
+    }
+
+    #[test]
+    fn degenerate_uvs_still_give_a_perpendicular_tangent() {
+        let (mut vertices, indices) = quad(Vector3::new(1.0, 0.0, 0.0), [Vector2::zero(); 4]);
+        compute_tangents(&mut vertices, &indices);
+        assert_orthonormal(&vertices);
+    }
+}
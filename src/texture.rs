@@ -22,8 +22,33 @@ impl Texture {
         })
     }
 
+    /// Magenta/black checkerboard with `size`-texel squares, used as a loud stand-in for
+    /// textures that failed to load
+    pub fn checkerboard(width: u32, height: u32, size: u32) -> Self {
+        let size = size.max(1);
+        let mut data = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let magenta = (x / size + y / size) % 2 == 0;
+                let texel: [u8; 4] = if magenta { [255, 0, 255, 255] } else { [0, 0, 0, 255] };
+                data.extend_from_slice(&texel);
+            }
+        }
+        Texture { width, height, data }
+    }
+
+    /// True when there are no texels to sample (zero-sized or truncated data)
+    fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0 || self.data.len() < (self.width * self.height * 4) as usize
+    }
+
     /// Sample texture at UV coordinates (0.0 - 1.0 range)
+    /// Empty textures sample as white.
     pub fn sample(&self, u: f32, v: f32) -> Vector3 {
+        if self.is_empty() {
+            return Vector3::new(1.0, 1.0, 1.0);
+        }
+
         // Wrap UV coordinates
         let u = u.fract();
         let v = v.fract();
@@ -46,21 +71,32 @@ impl Texture {
         }
     }
 
-    /// Sample with bilinear filtering for smoother results
+    /// Sample with bilinear filtering for smoother results.
+    /// Texels outside the image are clamped to the edge, so 1xN/Nx1 textures filter along
+    /// their long axis only; a 1x1 texture returns its single texel and an empty one white.
     pub fn sample_bilinear(&self, u: f32, v: f32) -> Vector3 {
+        if self.is_empty() {
+            return Vector3::new(1.0, 1.0, 1.0);
+        }
+        if self.width == 1 && self.height == 1 {
+            return self.get_pixel(0, 0);
+        }
+
         let u = u.fract();
         let v = v.fract();
         
         let x = u * self.width as f32 - 0.5;
         let y = v * self.height as f32 - 0.5;
         
-        let x0 = x.floor().max(0.0) as u32;
-        let y0 = y.floor().max(0.0) as u32;
-        let x1 = (x0 + 1).min(self.width - 1);
-        let y1 = (y0 + 1).min(self.height - 1);
-        
         let fx = x - x.floor();
         let fy = y - y.floor();
+
+        let max_x = (self.width - 1) as f32;
+        let max_y = (self.height - 1) as f32;
+        let x0 = x.floor().clamp(0.0, max_x) as u32;
+        let y0 = y.floor().clamp(0.0, max_y) as u32;
+        let x1 = (x.floor() + 1.0).clamp(0.0, max_x) as u32;
+        let y1 = (y.floor() + 1.0).clamp(0.0, max_y) as u32;
         
         let c00 = self.get_pixel(x0, y0);
         let c10 = self.get_pixel(x1, y0);
@@ -94,3 +130,53 @@ fn mix_vec3(a: Vector3, b: Vector3, t: f32) -> Vector3 {
         a.z * (1.0 - t) + b.z * t,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Texture whose texels are gray levels (0-255), row by row
+    fn gray_texture(width: u32, height: u32, levels: &[u8]) -> Texture {
+        let data = levels.iter().flat_map(|&level| [level, level, level, 255]).collect();
+        Texture { width, height, data }
+    }
+
+    fn gray(color: Vector3) -> f32 {
+        color.x * 255.0
+    }
+
+    #[test]
+    fn one_texel_texture_samples_its_texel_everywhere() {
+        let texture = gray_texture(1, 1, &[200]);
+        for (u, v) in [(0.0, 0.0), (0.5, 0.5), (0.99, 0.01), (3.7, -2.2)] {
+            assert!((gray(texture.sample(u, v)) - 200.0).abs() < 1e-3);
+            assert!((gray(texture.sample_bilinear(u, v)) - 200.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn one_texel_wide_texture_filters_along_its_height() {
+        let texture = gray_texture(1, 4, &[0, 80, 160, 240]);
+        // Texel centers return the texel, for any u
+        for (row, level) in [0.0, 80.0, 160.0, 240.0].into_iter().enumerate() {
+            let v = (row as f32 + 0.5) / 4.0;
+            for u in [0.0, 0.3, 0.9] {
+                assert!((gray(texture.sample(u, v)) - level).abs() < 1e-3);
+                assert!((gray(texture.sample_bilinear(u, v)) - level).abs() < 1e-3);
+            }
+        }
+        // Halfway between rows 1 and 2
+        assert!((gray(texture.sample_bilinear(0.5, 0.5)) - 120.0).abs() < 1e-3);
+        // Past the last texel center the edge is clamped
+        assert!((gray(texture.sample_bilinear(0.5, 0.99)) - 240.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn empty_textures_sample_white() {
+        let white = Vector3::new(1.0, 1.0, 1.0);
+        for texture in [gray_texture(0, 0, &[]), gray_texture(0, 4, &[]), gray_texture(2, 2, &[10, 20])] {
+            assert_eq!(texture.sample(0.5, 0.5), white);
+            assert_eq!(texture.sample_bilinear(0.5, 0.5), white);
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use crate::framebuffer::BlendMode;
+use crate::AppState;
+use std::fs;
+
+/// One scripted change to the interactive state
+#[derive(Clone, Debug)]
+pub enum Action {
+    Select(String), // Body name
+    Isolate(bool),
+    Satellites(bool),
+    Axes(bool),
+    Halo(bool),
+    Legend(bool),
+    MapView(bool),
+    AutoRotate(bool),
+    AutoOrbit(bool),
+    Zoom(f32),
+    OrbitSpeed(f32),
+    RotationSpeed(f32),
+    RingBlend(BlendMode),
+    Shake { intensity: f32, duration: f32 },
+}
+
+impl Action {
+    /// Applies the action; `body_names` resolves `Select` (unknown names are ignored)
+    pub fn apply(&self, state: &mut AppState, body_names: &[&str]) {
+        match self {
+            Action::Select(name) => {
+                if let Some(index) = body_names.iter().position(|body| body == name) {
+                    state.selected_body = index;
+                }
+            }
+            Action::Isolate(on) => state.isolate_body = *on,
+            Action::Satellites(on) => state.isolate_satellites = *on,
+            Action::Axes(on) => state.show_axes = *on,
+            Action::Halo(on) => state.sun_halo = *on,
+            Action::Legend(on) => state.show_legend = *on,
+            Action::MapView(on) => state.map_view = *on,
+            Action::AutoRotate(on) => state.auto_rotate = *on,
+            Action::AutoOrbit(on) => state.auto_orbit = *on,
+            Action::Zoom(zoom) => state.camera_zoom = zoom.clamp(0.3, 3.0),
+            Action::OrbitSpeed(mult) => state.orbit_speed_mult = *mult,
+            Action::RotationSpeed(mult) => state.rotation_speed_mult = *mult,
+            Action::RingBlend(mode) => state.ring_blend = *mode,
+            Action::Shake { intensity, duration } => state.trigger_shake(*intensity, *duration),
+        }
+    }
+
+    /// Parses `name args...` (e.g. `select Tierra`, `isolate on`, `shake 12 0.6`)
+    fn parse(name: &str, args: &str) -> Result<Action, String> {
+        let flag = || match args {
+            "on" | "true" => Ok(true),
+            "off" | "false" => Ok(false),
+            _ => Err(format!("expected on/off, got `{}`", args)),
+        };
+        let number = || args.parse::<f32>().map_err(|_| format!("expected a number, got `{}`", args));
+
+        match name {
+            "select" if !args.is_empty() => Ok(Action::Select(args.to_string())),
+            "isolate" => Ok(Action::Isolate(flag()?)),
+            "satellites" => Ok(Action::Satellites(flag()?)),
+            "axes" => Ok(Action::Axes(flag()?)),
+            "halo" => Ok(Action::Halo(flag()?)),
+            "legend" => Ok(Action::Legend(flag()?)),
+            "map" => Ok(Action::MapView(flag()?)),
+            "rotate" => Ok(Action::AutoRotate(flag()?)),
+            "orbit" => Ok(Action::AutoOrbit(flag()?)),
+            "zoom" => Ok(Action::Zoom(number()?)),
+            "orbit_speed" => Ok(Action::OrbitSpeed(number()?)),
+            "rotation_speed" => Ok(Action::RotationSpeed(number()?)),
+            "ring_blend" => match args {
+                "alpha" => Ok(Action::RingBlend(BlendMode::AlphaBlend)),
+                "additive" => Ok(Action::RingBlend(BlendMode::Additive)),
+                "multiply" => Ok(Action::RingBlend(BlendMode::Multiply)),
+                _ => Err(format!("unknown blend mode `{}`", args)),
+            },
+            "shake" => {
+                let values: Vec<f32> = args
+                    .split_whitespace()
+                    .map(|value| value.parse().ok())
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| format!("expected `intensity duration`, got `{}`", args))?;
+                match values.as_slice() {
+                    [intensity, duration] => Ok(Action::Shake { intensity: *intensity, duration: *duration }),
+                    _ => Err(format!("expected `intensity duration`, got `{}`", args)),
+                }
+            }
+            _ => Err(format!("unknown action `{} {}`", name, args)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TimelineEvent {
+    pub time: f32, // Seconds since the start of the (current loop of the) timeline
+    pub action: Action,
+}
+
+/// Scripted sequence of actions fired as `time` advances, optionally looping
+pub struct Timeline {
+    events: Vec<TimelineEvent>, // Sorted by time
+    loop_length: Option<f32>,
+    cycle_start: f32, // Absolute time at which the current loop started
+    next: usize,      // First event not fired yet in this loop
+}
+
+impl Timeline {
+    pub fn new(mut events: Vec<TimelineEvent>, loop_length: Option<f32>) -> Self {
+        // Stable sort keeps the file order of events sharing a time
+        events.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Timeline {
+            events,
+            loop_length: loop_length.filter(|length| *length > 0.0),
+            cycle_start: 0.0,
+            next: 0,
+        }
+    }
+
+    /// Loads a timeline file: one `<seconds> <action> [args]` per line, `loop <seconds>` to
+    /// restart after that long, `#` starts a comment
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut events = Vec::new();
+        let mut loop_length = None;
+
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (first, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let rest = rest.trim();
+            if first == "loop" {
+                loop_length = Some(
+                    rest.parse()
+                        .map_err(|_| format!("line {}: invalid loop length `{}`", line_number + 1, rest))?,
+                );
+                continue;
+            }
+
+            let time: f32 = first
+                .parse()
+                .map_err(|_| format!("line {}: invalid time `{}`", line_number + 1, first))?;
+            let (name, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            let action = Action::parse(name, args.trim()).map_err(|e| format!("line {}: {}", line_number + 1, e))?;
+            events.push(TimelineEvent { time, action });
+        }
+
+        Ok(Timeline::new(events, loop_length))
+    }
+
+    /// Fires every event whose time has been reached since the last update
+    pub fn update(&mut self, time: f32, state: &mut AppState, body_names: &[&str]) {
+        if let Some(length) = self.loop_length {
+            while time - self.cycle_start >= length {
+                // Finish the current loop, then start over
+                for event in &self.events[self.next..] {
+                    event.action.apply(state, body_names);
+                }
+                self.cycle_start += length;
+                self.next = 0;
+            }
+        }
+
+        let local_time = time - self.cycle_start;
+        while let Some(event) = self.events.get(self.next) {
+            if event.time > local_time {
+                break;
+            }
+            event.action.apply(state, body_names);
+            self.next += 1;
+        }
+    }
+}
@@ -2,7 +2,24 @@ use crate::fragment::Fragment;
 use crate::vertex::Vertex;
 use raylib::prelude::Vector3;
 
-pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> Vec<Fragment> {
+/// Twice the signed screen-space area below which a triangle counts as degenerate
+const DEGENERATE_AREA_EPSILON: f32 = 1e-4;
+
+/// Where inside a pixel coverage is sampled: 0.5 samples pixel centers (the usual convention,
+/// unbiased coverage for thin and sub-pixel triangles); 0.0 samples the top-left corners
+const PIXEL_CENTER: f32 = 0.5;
+
+/// Helper function to compute barycentric coordinates (twice the signed area of p1, p2, p3)
+fn sign(p1: Vector3, p2: Vector3, p3: Vector3) -> f32 {
+    (p1.x - p3.x) * (p2.y - p3.y) - (p2.x - p3.x) * (p1.y - p3.y)
+}
+
+/// Rasterizes a triangle into fragments. Front faces wind clockwise on screen: OBJ front faces are
+/// counter-clockwise, and the loader's Y flip mirrors them (the procedural sphere and torus are built
+/// the same way). With `cull_backfaces` the counter-clockwise ones produce no fragments; leave it
+/// off for open, two-sided geometry such as the flat ring.
+/// Only pixels inside `viewport` are visited, since near-clipped triangles can reach far off screen.
+pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex, cull_backfaces: bool, viewport: (u32, u32)) -> Vec<Fragment> {
     let mut fragments = Vec::new();
 
     // Get screen coordinates
@@ -10,43 +27,260 @@ pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> Vec<Fragment> {
     let p2 = v2.transformed_position;
     let p3 = v3.transformed_position;
 
-    // Find bounding box
-    let min_x = (p1.x.min(p2.x).min(p3.x)).floor() as i32;
-    let max_x = (p1.x.max(p2.x).max(p3.x)).ceil() as i32;
-    let min_y = (p1.y.min(p2.y).min(p3.y)).floor() as i32;
-    let max_y = (p1.y.max(p2.y).max(p3.y)).ceil() as i32;
+    // Skip degenerate (collinear) triangles: they cover no area, and the barycentric
+    // weights below would only produce a stray line of fragments with bogus depth
+    let signed_area = sign(p1, p2, p3);
+    if signed_area.abs() < DEGENERATE_AREA_EPSILON {
+        return fragments;
+    }
 
-    // Helper function to compute barycentric coordinates
-    fn sign(p1: Vector3, p2: Vector3, p3: Vector3) -> f32 {
-        (p1.x - p3.x) * (p2.y - p3.y) - (p2.x - p3.x) * (p1.y - p3.y)
+    // Screen y points down, so a clockwise triangle on screen has a positive area
+    let front_facing = signed_area > 0.0;
+    if cull_backfaces && !front_facing {
+        return fragments;
     }
 
+    let Some((min_x, min_y, max_x, max_y)) = pixel_bounds(p1, p2, p3, viewport) else {
+        return fragments;
+    };
+
+    // Edge functions scaled so the interior is positive whatever the winding. Each edge is
+    // named after the vertex it faces, whose barycentric weight it measures
+    let orientation = signed_area.signum();
+    let edges = [(p2, p3), (p3, p1), (p1, p2)];
+    let owns_ties = edges.map(|(a, b)| is_top_left(a, b, orientation));
+
     // Iterate through all pixels in bounding box
     for y in min_y..=max_y {
         for x in min_x..=max_x {
-            let pt = Vector3::new(x as f32, y as f32, 0.0);
+            let pt = Vector3::new(x as f32 + PIXEL_CENTER, y as f32 + PIXEL_CENTER, 0.0);
+            let d = edges.map(|(a, b)| sign(pt, a, b) * orientation);
 
-            let d1 = sign(pt, v1.transformed_position, v2.transformed_position);
-            let d2 = sign(pt, v2.transformed_position, v3.transformed_position);
-            let d3 = sign(pt, v3.transformed_position, v1.transformed_position);
+            // Top-left rule: a sample exactly on an edge belongs only to the triangle whose top
+            // or left edge it is, so triangles sharing the edge don't both cover it
+            let inside = (0..3).all(|i| d[i] > 0.0 || (d[i] == 0.0 && owns_ties[i]));
 
-            let has_neg = (d1 < 0.0) || (d2 < 0.0) || (d3 < 0.0);
-            let has_pos = (d1 > 0.0) || (d2 > 0.0) || (d3 > 0.0);
-
-            if !(has_neg && has_pos) {
+            if inside {
                 // Point is inside triangle - interpolate depth
-                let total = (d1.abs() + d2.abs() + d3.abs()).max(0.0001);
-                let w1 = d1.abs() / total;
-                let w2 = d2.abs() / total;
-                let w3 = d3.abs() / total;
+                let total = signed_area.abs();
+                let (w1, w2, w3) = (d[0] / total, d[1] / total, d[2] / total);
 
+                // Depth is already divided by w, so it stays linear on screen
                 let z = p1.z * w1 + p2.z * w2 + p3.z * w3;
                 let color = Vector3::new(1.0, 1.0, 1.0); // Will be overridden by shader
 
-                fragments.push(Fragment::new(x as f32, y as f32, color, z));
+                // Attributes are linear in the surface, not on screen: weight them by 1/w and
+                // renormalize, so UVs and normals don't swim across foreshortened triangles
+                let (a1, a2, a3) = perspective_weights(w1, w2, w3, v1.inv_w, v2.inv_w, v3.inv_w);
+
+                let mut fragment = Fragment::new(x as f32, y as f32, color, z);
+                fragment.normal = v1.normal * a1 + v2.normal * a2 + v3.normal * a3;
+                fragment.object_position = v1.position * a1 + v2.position * a2 + v3.position * a3;
+                fragment.transformed_normal = v1.transformed_normal * a1 + v2.transformed_normal * a2 + v3.transformed_normal * a3;
+                fragment.tangent = v1.tangent * a1 + v2.tangent * a2 + v3.tangent * a3;
+                fragment.tex_coords = v1.tex_coords * a1 + v2.tex_coords * a2 + v3.tex_coords * a3;
+                fragment.front_facing = front_facing;
+                fragments.push(fragment);
             }
         }
     }
 
     fragments
 }
+
+/// Whether the edge from `a` to `b` is a top or a left edge of its triangle (screen y points
+/// down): the interior lies below a horizontal edge or to the right of any other. `orientation`
+/// is the sign of the triangle's area, which puts the interior on the edge function's positive side.
+fn is_top_left(a: Vector3, b: Vector3, orientation: f32) -> bool {
+    // Gradient of `sign(pt, a, b) * orientation` in pt: it points into the triangle
+    let (towards_x, towards_y) = ((a.y - b.y) * orientation, (b.x - a.x) * orientation);
+    towards_x > 0.0 || (towards_x == 0.0 && towards_y > 0.0)
+}
+
+/// Screen-space barycentric weights corrected for perspective: each is scaled by its vertex's
+/// 1/w and the three renormalized. Falls back to the screen weights if the 1/w sum vanishes.
+fn perspective_weights(w1: f32, w2: f32, w3: f32, inv_w1: f32, inv_w2: f32, inv_w3: f32) -> (f32, f32, f32) {
+    let (c1, c2, c3) = (w1 * inv_w1, w2 * inv_w2, w3 * inv_w3);
+    let total = c1 + c2 + c3;
+    if total.abs() > f32::EPSILON {
+        (c1 / total, c2 / total, c3 / total)
+    } else {
+        (w1, w2, w3)
+    }
+}
+
+/// Bounding box of the pixels whose sample point can fall inside the triangle with screen
+/// positions `p1`, `p2`, `p3`, within `viewport`: (min_x, min_y, max_x, max_y), inclusive.
+/// None when it covers no pixel of the viewport.
+pub fn pixel_bounds(p1: Vector3, p2: Vector3, p3: Vector3, viewport: (u32, u32)) -> Option<(i32, i32, i32, i32)> {
+    let min_x = ((p1.x.min(p2.x).min(p3.x) - PIXEL_CENTER).ceil() as i32).max(0);
+    let max_x = ((p1.x.max(p2.x).max(p3.x) - PIXEL_CENTER).floor() as i32).min(viewport.0 as i32 - 1);
+    let min_y = ((p1.y.min(p2.y).min(p3.y) - PIXEL_CENTER).ceil() as i32).max(0);
+    let max_y = ((p1.y.max(p2.y).max(p3.y) - PIXEL_CENTER).floor() as i32).min(viewport.1 as i32 - 1);
+    (min_x <= max_x && min_y <= max_y).then_some((min_x, min_y, max_x, max_y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::{multiply_matrix_vector4, perspective, viewport_transform};
+    use raylib::prelude::{Vector2, Vector4};
+    use std::f32::consts::PI;
+
+    /// Screen the test triangles are rasterized on
+    const VIEWPORT: (u32, u32) = (400, 300);
+
+    /// Vertex already on screen (no perspective)
+    fn on_screen(x: f32, y: f32) -> Vertex {
+        Vertex::new(Vector3::new(x, y, 0.0), Vector3::new(0.0, 0.0, -1.0), Vector2::zero())
+    }
+
+    #[test]
+    fn collinear_triangles_produce_no_fragments() {
+        let (a, b, c) = (on_screen(10.0, 10.0), on_screen(30.0, 20.0), on_screen(50.0, 30.0));
+        assert!(triangle(&a, &b, &c, false, VIEWPORT).is_empty());
+        assert!(triangle(&a, &c, &b, false, VIEWPORT).is_empty());
+        // A repeated vertex is the extreme case
+        assert!(triangle(&a, &a, &c, false, VIEWPORT).is_empty());
+    }
+
+    #[test]
+    fn clockwise_triangles_are_front_facing() {
+        // Screen y points down: right, then down-left is clockwise on screen
+        let (a, b, c) = (on_screen(10.0, 10.0), on_screen(50.0, 10.0), on_screen(10.0, 50.0));
+
+        let front = triangle(&a, &b, &c, true, VIEWPORT);
+        assert!(!front.is_empty());
+        assert!(front.iter().all(|fragment| fragment.front_facing));
+
+        // The same triangle wound the other way is culled, or kept and marked as a back face
+        assert!(triangle(&a, &c, &b, true, VIEWPORT).is_empty());
+        let back = triangle(&a, &c, &b, false, VIEWPORT);
+        assert_eq!(back.len(), front.len());
+        assert!(back.iter().all(|fragment| !fragment.front_facing));
+    }
+
+    #[test]
+    fn small_triangle_around_a_pixel_center_covers_that_pixel() {
+        // Clockwise triangle about one pixel wide around the center of pixel (5, 7), (5.5, 7.5).
+        // Sampling at corners would miss it: it contains none of them
+        let (a, b, c) = (on_screen(5.1, 7.1), on_screen(5.9, 7.3), on_screen(5.4, 7.9));
+        let fragments = triangle(&a, &b, &c, true, VIEWPORT);
+        assert_eq!(fragments.len(), 1);
+        assert_eq!((fragments[0].position.x, fragments[0].position.y), (5.0, 7.0));
+    }
+
+    #[test]
+    fn pixel_square_covers_exactly_its_pixels() {
+        // Two triangles over the 2x2 pixel square from (2, 2) to (4, 4): four centers inside
+        let (a, b, c, d) = (on_screen(2.0, 2.0), on_screen(4.0, 2.0), on_screen(4.0, 4.0), on_screen(2.0, 4.0));
+        let mut covered: Vec<(i32, i32)> = triangle(&a, &b, &c, true, VIEWPORT)
+            .into_iter()
+            .chain(triangle(&a, &c, &d, true, VIEWPORT))
+            .map(|fragment| (fragment.position.x as i32, fragment.position.y as i32))
+            .collect();
+        covered.sort_unstable();
+        // The diagonal runs through two of the centers; each still comes from one triangle only
+        assert_eq!(covered, vec![(2, 2), (2, 3), (3, 2), (3, 3)]);
+    }
+
+    #[test]
+    fn shared_edges_through_pixel_centers_are_covered_exactly_once() {
+        // A square with its corners on pixel centers, split along a diagonal and, separately,
+        // into a fan around its center: every edge runs through pixel centers
+        let corners = [on_screen(2.5, 2.5), on_screen(6.5, 2.5), on_screen(6.5, 6.5), on_screen(2.5, 6.5)];
+        let center = on_screen(4.5, 4.5);
+        let split = [[&corners[0], &corners[1], &corners[2]], [&corners[0], &corners[2], &corners[3]]];
+        let fan: Vec<[&Vertex; 3]> = (0..4).map(|i| [&corners[i], &corners[(i + 1) % 4], &center]).collect();
+
+        // Each also with its triangles wound the other way
+        let meshes = [split.to_vec(), fan];
+        for mesh in meshes.iter().flat_map(|mesh| [mesh.clone(), mesh.iter().map(|&[a, b, c]| [a, c, b]).collect()]) {
+            let mut counts = [[0; 10]; 10];
+            for [a, b, c] in mesh {
+                for fragment in triangle(a, b, c, false, VIEWPORT) {
+                    counts[fragment.position.y as usize][fragment.position.x as usize] += 1;
+                }
+            }
+            // The top and left sides are in, the bottom and right ones out
+            for (y, row) in counts.iter().enumerate() {
+                for (x, &count) in row.iter().enumerate() {
+                    let expected = usize::from((2..6).contains(&x) && (2..6).contains(&y));
+                    assert_eq!(count, expected, "pixel ({}, {})", x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn each_vertex_weighs_its_own_attributes() {
+        // The fragment on a vertex's pixel center carries that vertex's UV
+        let mut vertices = [on_screen(2.5, 2.5), on_screen(12.5, 2.5), on_screen(2.5, 12.5)];
+        for (i, vertex) in vertices.iter_mut().enumerate() {
+            vertex.tex_coords = Vector2::new(i as f32, 0.0);
+        }
+        let fragments = triangle(&vertices[0], &vertices[1], &vertices[2], false, VIEWPORT);
+        let at = |x: f32, y: f32| fragments.iter().find(|fragment| fragment.position == Vector2::new(x, y)).unwrap().tex_coords.x;
+        assert!(at(2.0, 2.0).abs() < 1e-5);
+        assert!((at(11.0, 2.0) - 0.9).abs() < 1e-5);
+        assert!((at(2.0, 11.0) - 1.8).abs() < 1e-5);
+    }
+
+    const NEAR: f32 = 10.0;
+    const FAR: f32 = 1000.0;
+
+    /// A view-space point through a real perspective projection, as the vertex shader outputs it
+    fn projected(position: Vector3, u: f32) -> Vertex {
+        let projection = perspective(PI / 3.0, VIEWPORT.0 as f32 / VIEWPORT.1 as f32, NEAR, FAR);
+        let clip = multiply_matrix_vector4(&projection, &Vector4::new(position.x, position.y, position.z, 1.0));
+        let ndc = Vector3::new(clip.x, clip.y, clip.z) * (1.0 / clip.w);
+        let mut vertex = Vertex::new(position, Vector3::new(0.0, 1.0, 0.0), Vector2::new(u, 0.0));
+        vertex.transformed_position = viewport_transform(ndc, VIEWPORT.0, VIEWPORT.1);
+        vertex.clip_position = clip;
+        vertex.inv_w = 1.0 / clip.w;
+        vertex
+    }
+
+    /// View-space z of a fragment, undoing the projection's depth mapping (NDC depth is exact on screen)
+    fn view_depth(ndc_depth: f32) -> f32 {
+        let a = (FAR + NEAR) / (NEAR - FAR);
+        let b = 2.0 * FAR * NEAR / (NEAR - FAR);
+        b / (-ndc_depth - a)
+    }
+
+    #[test]
+    fn attributes_follow_the_surface_not_the_screen() {
+        // A floor receding from the camera: u goes from 0 at the near edge to 1 at the far tip,
+        // linearly in depth. Its vertices have w from 20 to 400.
+        let (near_z, far_z) = (-20.0, -400.0);
+        let vertices = [
+            projected(Vector3::new(-30.0, -10.0, near_z), 0.0),
+            projected(Vector3::new(0.0, -10.0, far_z), 1.0),
+            projected(Vector3::new(30.0, -10.0, near_z), 0.0),
+        ];
+        let expected_u = |depth: f32| (view_depth(depth) - near_z) / (far_z - near_z);
+
+        let fragments = triangle(&vertices[0], &vertices[1], &vertices[2], false, VIEWPORT);
+        assert!(fragments.len() > 100);
+        for fragment in &fragments {
+            assert!((fragment.tex_coords.x - expected_u(fragment.depth)).abs() < 2e-3);
+            // The object-space position the shaders map their patterns from stays on the floor
+            assert!((fragment.object_position.z - view_depth(fragment.depth)).abs() < 0.5);
+            assert!((fragment.object_position.y + 10.0).abs() < 1e-3);
+        }
+
+        // Without the 1/w weighting (w = 1 everywhere) the same fragments are visibly off
+        let affine = vertices.clone().map(|vertex| Vertex { inv_w: 1.0, ..vertex });
+        let worst = triangle(&affine[0], &affine[1], &affine[2], false, VIEWPORT)
+            .iter()
+            .map(|fragment| (fragment.tex_coords.x - expected_u(fragment.depth)).abs())
+            .fold(0.0, f32::max);
+        assert!(worst > 0.1, "affine error only {}", worst);
+    }
+
+    #[test]
+    fn equal_w_matches_screen_interpolation() {
+        let weights = perspective_weights(0.2, 0.3, 0.5, 0.25, 0.25, 0.25);
+        assert!((weights.0 - 0.2).abs() < 1e-6 && (weights.1 - 0.3).abs() < 1e-6 && (weights.2 - 0.5).abs() < 1e-6);
+    }
+}
@@ -1,37 +1,51 @@
 #![allow(dead_code)]
 
-use raylib::math::{Vector2, Vector3};
+use raylib::math::{Vector2, Vector3, Vector4};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Vertex {
   pub position: Vector3,
   pub normal: Vector3,
+  pub tangent: Vector3, // Direction of increasing u, orthogonal to the normal (see tangents.rs)
   pub tex_coords: Vector2,
   pub color: Vector3,
   pub transformed_position: Vector3,
   pub transformed_normal: Vector3,
+  pub clip_position: Vector4, // Position before the perspective divide, for near-plane clipping
+  pub behind_camera: bool, // Nearer than the near plane in clip space (z < -w): not projected, clipped away
+  pub inv_w: f32,          // 1 / clip-space w, for perspective-correct interpolation (1 before the vertex shader)
 }
 
 impl Vertex {
+  /// Untransformed vertex: the transformed position/normal start as copies of the inputs
   pub fn new(position: Vector3, normal: Vector3, tex_coords: Vector2) -> Self {
     Vertex {
       position,
       normal,
+      tangent: Vector3::new(1.0, 0.0, 0.0), // Placeholder until tangents are computed (see tangents.rs)
       tex_coords,
       color: Vector3::new(0.0, 0.0, 0.0), // Black
       transformed_position: position,
       transformed_normal: normal,
+      clip_position: Vector4::new(position.x, position.y, position.z, 1.0),
+      behind_camera: false,
+      inv_w: 1.0,
     }
   }
 
+  /// Colored point without normal or UVs (debug geometry); transformed position as in `new`
   pub fn new_with_color(position: Vector3, color: Vector3) -> Self {
     Vertex {
       position,
       normal: Vector3::new(0.0, 0.0, 0.0),
+      tangent: Vector3::new(1.0, 0.0, 0.0),
       tex_coords: Vector2::new(0.0, 0.0),
       color,
-      transformed_position: Vector3::new(0.0, 0.0, 0.0),
+      transformed_position: position,
       transformed_normal: Vector3::new(0.0, 0.0, 0.0),
+      clip_position: Vector4::new(position.x, position.y, position.z, 1.0),
+      behind_camera: false,
+      inv_w: 1.0,
     }
   }
 
@@ -46,10 +60,57 @@ impl Default for Vertex {
     Vertex {
       position: Vector3::new(0.0, 0.0, 0.0),
       normal: Vector3::new(0.0, 1.0, 0.0),
+      tangent: Vector3::new(1.0, 0.0, 0.0),
       tex_coords: Vector2::new(0.0, 0.0),
       color: Vector3::new(0.0, 0.0, 0.0), // Black
       transformed_position: Vector3::new(0.0, 0.0, 0.0),
       transformed_normal: Vector3::new(0.0, 1.0, 0.0),
+      clip_position: Vector4::new(0.0, 0.0, 0.0, 1.0),
+      behind_camera: false,
+      inv_w: 1.0,
     }
   }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn new_starts_untransformed() {
+    let position = Vector3::new(1.0, 2.0, 3.0);
+    let normal = Vector3::new(0.0, 0.0, 1.0);
+    let vertex = Vertex::new(position, normal, Vector2::new(0.25, 0.75));
+    assert_eq!(vertex.transformed_position, position);
+    assert_eq!(vertex.transformed_normal, normal);
+    assert_eq!(vertex.clip_position, Vector4::new(1.0, 2.0, 3.0, 1.0));
+    assert_eq!(vertex.tex_coords, Vector2::new(0.25, 0.75));
+    assert!(!vertex.behind_camera);
+    assert_eq!(vertex.inv_w, 1.0);
+  }
+
+  #[test]
+  fn every_constructor_starts_with_the_same_tangent() {
+    let tangent = Vertex::default().tangent;
+    assert_eq!(tangent, Vector3::new(1.0, 0.0, 0.0));
+    assert_eq!(Vertex::new(Vector3::zero(), Vector3::new(0.0, 1.0, 0.0), Vector2::zero()).tangent, tangent);
+    assert_eq!(Vertex::new_with_color(Vector3::zero(), Vector3::one()).tangent, tangent);
+  }
+
+  #[test]
+  fn new_with_color_keeps_the_color() {
+    let vertex = Vertex::new_with_color(Vector3::new(4.0, 5.0, 6.0), Vector3::new(0.2, 0.4, 0.6));
+    assert_eq!(vertex.color, Vector3::new(0.2, 0.4, 0.6));
+    assert_eq!(vertex.transformed_position, Vector3::new(4.0, 5.0, 6.0));
+  }
+
+  #[test]
+  fn set_transformed_leaves_the_inputs_alone() {
+    let mut vertex = Vertex::new(Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0), Vector2::zero());
+    let original = vertex.clone();
+    vertex.set_transformed(Vector3::new(10.0, 20.0, 0.5), Vector3::new(0.0, 0.0, -1.0));
+    assert_eq!(vertex.position, original.position);
+    assert_eq!(vertex.normal, original.normal);
+    assert_eq!(vertex.transformed_position, Vector3::new(10.0, 20.0, 0.5));
+    assert_ne!(vertex, original);
+  }
+}